@@ -0,0 +1,227 @@
+use mongodb::bson::doc;
+use mongodb::error::{Error, ErrorKind, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR};
+use mongodb::options::ClientOptions;
+use mongodb::{Client, Database};
+use std::fmt::{Debug, Display};
+use std::future::Future;
+use std::time::Duration;
+use tonic::server::NamedService;
+use tonic_health::server::HealthReporter;
+
+/// attempts of a query failing with a transient error, see `with_retry`
+pub const MAX_ATTEMPTS: u32 = 3;
+const BACKOFF_MS: u64 = 100;
+/// how long a query waits for a reachable server, the driver default of 30s would hold
+/// every request that long while the db is down
+const SERVER_SELECTION_TIMEOUT_SECS: u64 = 5;
+pub const PING_INTERVAL_SECS: u64 = 5;
+const PING_TIMEOUT_SECS: u64 = 3;
+
+/// Error worth trying again right away, see `with_retry`
+pub trait Transient {
+    fn is_transient(&self) -> bool;
+}
+
+/// e.g. a dropped connection or a primary stepping down.
+/// Not a server selection error, it has waited for a server long enough already
+impl Transient for Error {
+    fn is_transient(&self) -> bool {
+        self.contains_label(RETRYABLE_WRITE_ERROR)
+            || self.contains_label(TRANSIENT_TRANSACTION_ERROR)
+            || matches!(
+                self.kind.as_ref(),
+                ErrorKind::Io(_)
+                    | ErrorKind::ConnectionPoolCleared { .. }
+                    | ErrorKind::DnsResolve { .. }
+            )
+    }
+}
+
+/// the query ran out of time, either on the server or waiting for its answer
+pub fn is_timeout(err: &Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Io(io_err) => io_err.kind() == std::io::ErrorKind::TimedOut,
+        // MaxTimeMSExpired
+        ErrorKind::Command(command_err) => command_err.code == 50,
+        _ => false,
+    }
+}
+
+/// no server can be reached, e.g. the db is down
+pub fn is_unreachable(err: &Error) -> bool {
+    matches!(
+        err.kind.as_ref(),
+        ErrorKind::ServerSelection { .. }
+            | ErrorKind::ConnectionPoolCleared { .. }
+            | ErrorKind::Io(_)
+    )
+}
+
+/// fails on an invalid uri, resolving a `mongodb+srv` uri is retried, see `with_retry`.
+/// No server is contacted, the client connects on the first query
+pub async fn connect(db_connection_uri: &str, db_name: &str) -> Result<Database, Error> {
+    let mut client_options = with_retry(|| ClientOptions::parse(db_connection_uri)).await?;
+    client_options.server_selection_timeout = client_options
+        .server_selection_timeout
+        .or(Some(Duration::from_secs(SERVER_SELECTION_TIMEOUT_SECS)));
+
+    let client = Client::with_options(client_options)?;
+    Ok(client.database(db_name))
+}
+
+/// runs `query` again with an exponential backoff while it fails with a transient error,
+/// `MAX_ATTEMPTS` times at most
+pub async fn with_retry<T, E, F, Fut>(mut query: F) -> Result<T, E>
+where
+    E: Transient + Debug,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match query().await {
+            Err(err) if err.is_transient() && attempt < MAX_ATTEMPTS => {
+                println!("Retrying a query, attempt {attempt} failed: {:?}", err);
+                tokio::time::sleep(backoff(attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(BACKOFF_MS * 2u64.pow(attempt - 1))
+}
+
+#[derive(Debug)]
+pub enum PingErr {
+    QueryErr(Error),
+    /// no answer in time
+    Unreachable,
+}
+
+impl Display for PingErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PingErr::QueryErr(err) => write!(f, "ping failed: {err}"),
+            PingErr::Unreachable => write!(f, "database is unreachable"),
+        }
+    }
+}
+
+pub async fn ping(db: &Database) -> Result<(), PingErr> {
+    let ping = db.run_command(doc! {"ping": 1}, None);
+    match tokio::time::timeout(Duration::from_secs(PING_TIMEOUT_SECS), ping).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(err)) => Err(PingErr::QueryErr(err)),
+        Err(_) => Err(PingErr::Unreachable),
+    }
+}
+
+/// Pings the db every `PING_INTERVAL_SECS` and reports `S` serving only while it answers.
+/// `prepare` runs on the answered pings until it succeeds once, e.g. to migrate the db,
+/// `S` is not serving until then. `on_change` is told whenever the db goes up or down
+pub async fn monitor<S, P, Fut, E, C>(
+    db: Database,
+    mut reporter: HealthReporter,
+    mut prepare: P,
+    mut on_change: C,
+) where
+    S: NamedService,
+    P: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+    E: Debug,
+    C: FnMut(bool),
+{
+    let mut interval = tokio::time::interval(Duration::from_secs(PING_INTERVAL_SECS));
+    let mut prepared = false;
+    let mut reported = None;
+
+    loop {
+        interval.tick().await;
+        let up = match ping(&db).await {
+            Ok(()) if prepared => true,
+            Ok(()) => match prepare().await {
+                Ok(()) => {
+                    prepared = true;
+                    true
+                }
+                Err(err) => {
+                    println!("Could not prepare the db: {:?}", err);
+                    false
+                }
+            },
+            Err(err) => {
+                if reported != Some(false) {
+                    println!("Db is down: {err}");
+                }
+                false
+            }
+        };
+
+        if reported != Some(up) {
+            if up && reported.is_some() {
+                println!("Db is back up");
+            }
+            on_change(up);
+            if up {
+                reporter.set_serving::<S>().await;
+            } else {
+                reporter.set_not_serving::<S>().await;
+            }
+            reported = Some(up);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn io_err(kind: std::io::ErrorKind) -> Error {
+        Error::from(kind)
+    }
+
+    #[test]
+    fn classify_ok() {
+        let reset = io_err(std::io::ErrorKind::ConnectionReset);
+        assert!(reset.is_transient());
+        assert!(is_unreachable(&reset));
+        assert!(!is_timeout(&reset));
+
+        assert!(is_timeout(&io_err(std::io::ErrorKind::TimedOut)));
+    }
+
+    #[tokio::test]
+    async fn with_retry_ok() {
+        let attempts = AtomicU32::new(0);
+        let result = with_retry(|| async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(io_err(std::io::ErrorKind::ConnectionReset)),
+                n => Ok(n),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 1);
+
+        attempts.store(0, Ordering::SeqCst);
+        let result: Result<(), _> = with_retry(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(io_err(std::io::ErrorKind::ConnectionReset))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), MAX_ATTEMPTS);
+
+        attempts.store(0, Ordering::SeqCst);
+        let result: Result<(), _> = with_retry(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Error::custom("not transient"))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,10 +1,17 @@
 use std::sync::OnceLock;
 
+#[cfg(feature = "db")]
+pub mod db;
+
 pub mod env {
     pub fn required(env_var: &str) -> String {
         std::env::var(env_var)
             .unwrap_or_else(|_| panic!("Missing required env variable: {}", env_var))
     }
+
+    pub fn optional(env_var: &str) -> Option<String> {
+        std::env::var(env_var).ok().filter(|val| !val.is_empty())
+    }
 }
 
 /* Example
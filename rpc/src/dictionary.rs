@@ -9,10 +9,118 @@ pub struct GetWordDefinitionsRequest {
 pub struct GetWordDefinitionsResponse {
     #[prost(string, tag = "1")]
     pub word: ::prost::alloc::string::String,
-    #[prost(message, optional, tag = "2")]
-    pub vocabulary_definition: ::core::option::Option<VocabularyDefinition>,
-    #[prost(message, optional, tag = "3")]
-    pub oxford_definition: ::core::option::Option<OxfordDefinition>,
+    #[prost(message, repeated, tag = "4")]
+    pub definitions: ::prost::alloc::vec::Vec<SourceDefinition>,
+    /// similar words suggested by the sources which do not know the word
+    #[prost(string, repeated, tag = "5")]
+    pub suggestions: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// headword the query was resolved to, e.g. "cat" for "Cats"
+    #[prost(string, tag = "6")]
+    pub lemma: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetWordDefinitionsBatchRequest {
+    #[prost(string, repeated, tag = "1")]
+    pub words: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// Outcome of a single word of the batch, streamed as soon as the word is resolved
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WordDefinitionsResult {
+    #[prost(string, tag = "1")]
+    pub word: ::prost::alloc::string::String,
+    #[prost(oneof = "word_definitions_result::Result", tags = "2, 3, 4")]
+    pub result: ::core::option::Option<word_definitions_result::Result>,
+}
+/// Nested message and enum types in `WordDefinitionsResult`.
+pub mod word_definitions_result {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Result {
+        #[prost(message, tag = "2")]
+        Found(super::GetWordDefinitionsResponse),
+        #[prost(message, tag = "3")]
+        NotFound(super::WordNotFound),
+        #[prost(message, tag = "4")]
+        Error(super::LookupError),
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LookupError {
+    /// gRPC status code
+    #[prost(int32, tag = "1")]
+    pub code: i32,
+    #[prost(string, tag = "2")]
+    pub message: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EnqueueWordRequest {
+    #[prost(string, tag = "1")]
+    pub word: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EnqueueWordResponse {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WatchWordRequest {
+    #[prost(string, tag = "1")]
+    pub word: ::prost::alloc::string::String,
+}
+/// Progress of a single source, the stream ends once every source is done or the job gave up
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WatchWordEvent {
+    #[prost(string, tag = "1")]
+    pub word: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub source: ::prost::alloc::string::String,
+    #[prost(enumeration = "SourceStage", tag = "3")]
+    pub stage: i32,
+    /// set when the source is done and has the word
+    #[prost(message, optional, tag = "4")]
+    pub definition: ::core::option::Option<SourceDefinition>,
+    #[prost(string, optional, tag = "5")]
+    pub error: ::core::option::Option<::prost::alloc::string::String>,
+}
+/// Details of the NOT_FOUND status returned by GetWordDefinitions
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WordNotFound {
+    #[prost(string, tag = "1")]
+    pub word: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub suggestions: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, tag = "3")]
+    pub lemma: ::prost::alloc::string::String,
+}
+/// Definition scraped from a single dictionary source
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SourceDefinition {
+    #[prost(string, tag = "1")]
+    pub source: ::prost::alloc::string::String,
+    #[prost(oneof = "source_definition::Definition", tags = "2, 3, 4")]
+    pub definition: ::core::option::Option<source_definition::Definition>,
+}
+/// Nested message and enum types in `SourceDefinition`.
+pub mod source_definition {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Definition {
+        #[prost(message, tag = "2")]
+        Vocabulary(super::VocabularyDefinition),
+        #[prost(message, tag = "3")]
+        Oxford(super::OxfordDefinition),
+        #[prost(message, tag = "4")]
+        Wiktionary(super::WiktionaryDefinition),
+    }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -63,6 +171,85 @@ pub struct OxfordDefinition {
     pub idioms: ::prost::alloc::vec::Vec<Idiom>,
     #[prost(message, repeated, tag = "13")]
     pub phrasal_verbs: ::prost::alloc::vec::Vec<WordRef>,
+    #[prost(message, repeated, tag = "14")]
+    pub verb_forms: ::prost::alloc::vec::Vec<VerbForm>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VerbForm {
+    /// root, thirdps, past, pastpart, prespart
+    #[prost(string, tag = "1")]
+    pub form: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub note: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub word: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "4")]
+    pub pronunciations: ::prost::alloc::vec::Vec<Pronunciation>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WiktionaryDefinition {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub word: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub entries: ::prost::alloc::vec::Vec<WiktionaryEntry>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WiktionaryEntry {
+    #[prost(string, tag = "1")]
+    pub pos: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub etymology: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub senses: ::prost::alloc::vec::Vec<WiktionarySense>,
+    #[prost(message, repeated, tag = "4")]
+    pub sounds: ::prost::alloc::vec::Vec<WiktionarySound>,
+    #[prost(message, repeated, tag = "5")]
+    pub forms: ::prost::alloc::vec::Vec<WiktionaryForm>,
+    #[prost(message, repeated, tag = "6")]
+    pub translations: ::prost::alloc::vec::Vec<WiktionaryTranslation>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WiktionarySense {
+    #[prost(string, repeated, tag = "1")]
+    pub glosses: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, repeated, tag = "2")]
+    pub examples: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, repeated, tag = "3")]
+    pub tags: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WiktionarySound {
+    #[prost(string, tag = "1")]
+    pub ipa: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub tags: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WiktionaryForm {
+    #[prost(string, tag = "1")]
+    pub form: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub tags: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WiktionaryTranslation {
+    #[prost(string, tag = "1")]
+    pub lang: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub code: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub word: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub sense: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -173,6 +360,8 @@ pub struct VocabularySubDefinition {
     pub short_examples: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
     #[prost(string, repeated, tag = "8")]
     pub synonyms: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "9")]
+    pub image_id: ::core::option::Option<::prost::alloc::string::String>,
     #[prost(oneof = "vocabulary_sub_definition::WordVariant", tags = "1, 2")]
     pub word_variant: ::core::option::Option<vocabulary_sub_definition::WordVariant>,
 }
@@ -244,18 +433,67 @@ pub struct VocabularyExample {
 pub struct InvalidateWordRequest {
     #[prost(string, tag = "1")]
     pub word: ::prost::alloc::string::String,
+    /// sources to re-scrape right away, the whole definition is dropped when empty
+    #[prost(string, repeated, tag = "2")]
+    pub sources: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct InvalidateWordResponse {
     #[prost(bool, tag = "1")]
     pub success: bool,
+    /// sources which were re-scraped
+    #[prost(string, repeated, tag = "2")]
+    pub refreshed: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VerifyRequest {
+    /// fixes what is found instead of only reporting it
+    #[prost(bool, tag = "1")]
+    pub repair: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VerifyResponse {
+    /// definitions pointing to a source doc which does not exist
+    #[prost(message, repeated, tag = "1")]
+    pub dangling_ids: ::prost::alloc::vec::Vec<DanglingId>,
+    /// source docs no definition points to
+    #[prost(message, repeated, tag = "2")]
+    pub orphaned_docs: ::prost::alloc::vec::Vec<SourceDocRef>,
+    /// ids of the audio no source doc refers to
+    #[prost(string, repeated, tag = "3")]
+    pub orphaned_audio: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(bool, tag = "4")]
+    pub repaired: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DanglingId {
+    #[prost(string, tag = "1")]
+    pub word: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub source: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SourceDocRef {
+    #[prost(string, tag = "1")]
+    pub source: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub id: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetAudioRequest {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
+    /// the original is returned when the audio is not available in the format
+    #[prost(enumeration = "AudioFormat", tag = "2")]
+    pub format: i32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -267,6 +505,182 @@ pub struct GetAudioResponse {
     #[prost(bytes = "vec", tag = "3")]
     pub bytes: ::prost::alloc::vec::Vec<u8>,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StreamAudioRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    /// bytes to stream, the whole audio when not set
+    #[prost(message, optional, tag = "2")]
+    pub range: ::core::option::Option<ByteRange>,
+    #[prost(enumeration = "AudioFormat", tag = "3")]
+    pub format: i32,
+    /// etags of the `If-None-Match` header, only the info is streamed when one is the hash of the audio
+    #[prost(string, repeated, tag = "4")]
+    pub if_none_match: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// Range as in the HTTP `Range` header: `first-last`, `first-` or `-last` where `last` is the suffix length
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ByteRange {
+    #[prost(uint64, optional, tag = "1")]
+    pub first: ::core::option::Option<u64>,
+    #[prost(uint64, optional, tag = "2")]
+    pub last: ::core::option::Option<u64>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AudioChunk {
+    /// set in the first chunk only
+    #[prost(message, optional, tag = "1")]
+    pub info: ::core::option::Option<AudioInfo>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AudioInfo {
+    #[prost(string, tag = "1")]
+    pub content_type: ::prost::alloc::string::String,
+    /// sha256 of the whole audio
+    #[prost(string, tag = "2")]
+    pub hash: ::prost::alloc::string::String,
+    /// size of the whole audio
+    #[prost(uint64, tag = "3")]
+    pub size: u64,
+    /// streamed part of the audio
+    #[prost(uint64, tag = "4")]
+    pub start: u64,
+    #[prost(uint64, tag = "5")]
+    pub length: u64,
+    /// the original is streamed while the requested format is not transcoded yet
+    #[prost(enumeration = "AudioFormat", tag = "6")]
+    pub format: i32,
+    /// the hash matched `if_none_match`, nothing is streamed
+    #[prost(bool, tag = "7")]
+    pub not_modified: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetImageRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetImageResponse {
+    #[prost(string, tag = "1")]
+    pub content_type: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "2")]
+    pub bytes: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetWordRelationsRequest {
+    #[prost(string, tag = "1")]
+    pub word: ::prost::alloc::string::String,
+}
+/// WordNet relations of every sense of the word
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetWordRelationsResponse {
+    #[prost(string, tag = "1")]
+    pub word: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub senses: ::prost::alloc::vec::Vec<WordSense>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WordSense {
+    #[prost(message, optional, tag = "1")]
+    pub synset: ::core::option::Option<Synset>,
+    #[prost(string, repeated, tag = "2")]
+    pub antonyms: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "3")]
+    pub hypernyms: ::prost::alloc::vec::Vec<Synset>,
+    #[prost(message, repeated, tag = "4")]
+    pub hyponyms: ::prost::alloc::vec::Vec<Synset>,
+    #[prost(message, repeated, tag = "5")]
+    pub meronyms: ::prost::alloc::vec::Vec<Synset>,
+    #[prost(message, repeated, tag = "6")]
+    pub holonyms: ::prost::alloc::vec::Vec<Synset>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Synset {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub pos: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "3")]
+    pub words: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, tag = "4")]
+    pub gloss: ::prost::alloc::string::String,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum SourceStage {
+    Queued = 0,
+    Scraping = 1,
+    DownloadingAudio = 2,
+    Done = 3,
+    Failed = 4,
+}
+impl SourceStage {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            SourceStage::Queued => "QUEUED",
+            SourceStage::Scraping => "SCRAPING",
+            SourceStage::DownloadingAudio => "DOWNLOADING_AUDIO",
+            SourceStage::Done => "DONE",
+            SourceStage::Failed => "FAILED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "QUEUED" => Some(Self::Queued),
+            "SCRAPING" => Some(Self::Scraping),
+            "DOWNLOADING_AUDIO" => Some(Self::DownloadingAudio),
+            "DONE" => Some(Self::Done),
+            "FAILED" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum AudioFormat {
+    Original = 0,
+    Opus = 1,
+    Mp3 = 2,
+}
+impl AudioFormat {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            AudioFormat::Original => "ORIGINAL",
+            AudioFormat::Opus => "OPUS",
+            AudioFormat::Mp3 => "MP3",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ORIGINAL" => Some(Self::Original),
+            "OPUS" => Some(Self::Opus),
+            "MP3" => Some(Self::Mp3),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod dictionary_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -377,6 +791,83 @@ pub mod dictionary_client {
                 .insert(GrpcMethod::new("dictionary.Dictionary", "GetWordDefinitions"));
             self.inner.unary(req, path, codec).await
         }
+        pub async fn get_word_definitions_batch(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetWordDefinitionsBatchRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::WordDefinitionsResult>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/dictionary.Dictionary/GetWordDefinitionsBatch",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("dictionary.Dictionary", "GetWordDefinitionsBatch"),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+        pub async fn enqueue_word(
+            &mut self,
+            request: impl tonic::IntoRequest<super::EnqueueWordRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::EnqueueWordResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/dictionary.Dictionary/EnqueueWord",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("dictionary.Dictionary", "EnqueueWord"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn watch_word(
+            &mut self,
+            request: impl tonic::IntoRequest<super::WatchWordRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::WatchWordEvent>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/dictionary.Dictionary/WatchWord",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("dictionary.Dictionary", "WatchWord"));
+            self.inner.server_streaming(req, path, codec).await
+        }
         pub async fn invalidate_word(
             &mut self,
             request: impl tonic::IntoRequest<super::InvalidateWordRequest>,
@@ -427,13 +918,110 @@ pub mod dictionary_client {
                 .insert(GrpcMethod::new("dictionary.Dictionary", "GetAudio"));
             self.inner.unary(req, path, codec).await
         }
-    }
-}
-/// Generated server implementations.
-pub mod dictionary_server {
-    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with DictionaryServer.
+        pub async fn stream_audio(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StreamAudioRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::AudioChunk>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/dictionary.Dictionary/StreamAudio",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("dictionary.Dictionary", "StreamAudio"));
+            self.inner.server_streaming(req, path, codec).await
+        }
+        pub async fn get_image(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetImageRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetImageResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/dictionary.Dictionary/GetImage",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("dictionary.Dictionary", "GetImage"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_word_relations(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetWordRelationsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetWordRelationsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/dictionary.Dictionary/GetWordRelations",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("dictionary.Dictionary", "GetWordRelations"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn verify(
+            &mut self,
+            request: impl tonic::IntoRequest<super::VerifyRequest>,
+        ) -> std::result::Result<tonic::Response<super::VerifyResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/dictionary.Dictionary/Verify",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("dictionary.Dictionary", "Verify"));
+            self.inner.unary(req, path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod dictionary_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with DictionaryServer.
     #[async_trait]
     pub trait Dictionary: Send + Sync + 'static {
         async fn get_word_definitions(
@@ -443,6 +1031,36 @@ pub mod dictionary_server {
             tonic::Response<super::GetWordDefinitionsResponse>,
             tonic::Status,
         >;
+        /// Server streaming response type for the GetWordDefinitionsBatch method.
+        type GetWordDefinitionsBatchStream: futures_core::Stream<
+                Item = std::result::Result<super::WordDefinitionsResult, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        async fn get_word_definitions_batch(
+            &self,
+            request: tonic::Request<super::GetWordDefinitionsBatchRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::GetWordDefinitionsBatchStream>,
+            tonic::Status,
+        >;
+        async fn enqueue_word(
+            &self,
+            request: tonic::Request<super::EnqueueWordRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::EnqueueWordResponse>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the WatchWord method.
+        type WatchWordStream: futures_core::Stream<
+                Item = std::result::Result<super::WatchWordEvent, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        async fn watch_word(
+            &self,
+            request: tonic::Request<super::WatchWordRequest>,
+        ) -> std::result::Result<tonic::Response<Self::WatchWordStream>, tonic::Status>;
         async fn invalidate_word(
             &self,
             request: tonic::Request<super::InvalidateWordRequest>,
@@ -457,6 +1075,37 @@ pub mod dictionary_server {
             tonic::Response<super::GetAudioResponse>,
             tonic::Status,
         >;
+        /// Server streaming response type for the StreamAudio method.
+        type StreamAudioStream: futures_core::Stream<
+                Item = std::result::Result<super::AudioChunk, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        async fn stream_audio(
+            &self,
+            request: tonic::Request<super::StreamAudioRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::StreamAudioStream>,
+            tonic::Status,
+        >;
+        async fn get_image(
+            &self,
+            request: tonic::Request<super::GetImageRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetImageResponse>,
+            tonic::Status,
+        >;
+        async fn get_word_relations(
+            &self,
+            request: tonic::Request<super::GetWordRelationsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetWordRelationsResponse>,
+            tonic::Status,
+        >;
+        async fn verify(
+            &self,
+            request: tonic::Request<super::VerifyRequest>,
+        ) -> std::result::Result<tonic::Response<super::VerifyResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct DictionaryServer<T: Dictionary> {
@@ -583,6 +1232,147 @@ pub mod dictionary_server {
                     };
                     Box::pin(fut)
                 }
+                "/dictionary.Dictionary/GetWordDefinitionsBatch" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetWordDefinitionsBatchSvc<T: Dictionary>(pub Arc<T>);
+                    impl<
+                        T: Dictionary,
+                    > tonic::server::ServerStreamingService<
+                        super::GetWordDefinitionsBatchRequest,
+                    > for GetWordDefinitionsBatchSvc<T> {
+                        type Response = super::WordDefinitionsResult;
+                        type ResponseStream = T::GetWordDefinitionsBatchStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::GetWordDefinitionsBatchRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).get_word_definitions_batch(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetWordDefinitionsBatchSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/dictionary.Dictionary/EnqueueWord" => {
+                    #[allow(non_camel_case_types)]
+                    struct EnqueueWordSvc<T: Dictionary>(pub Arc<T>);
+                    impl<
+                        T: Dictionary,
+                    > tonic::server::UnaryService<super::EnqueueWordRequest>
+                    for EnqueueWordSvc<T> {
+                        type Response = super::EnqueueWordResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::EnqueueWordRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).enqueue_word(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = EnqueueWordSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/dictionary.Dictionary/WatchWord" => {
+                    #[allow(non_camel_case_types)]
+                    struct WatchWordSvc<T: Dictionary>(pub Arc<T>);
+                    impl<
+                        T: Dictionary,
+                    > tonic::server::ServerStreamingService<super::WatchWordRequest>
+                    for WatchWordSvc<T> {
+                        type Response = super::WatchWordEvent;
+                        type ResponseStream = T::WatchWordStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::WatchWordRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).watch_word(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = WatchWordSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/dictionary.Dictionary/InvalidateWord" => {
                     #[allow(non_camel_case_types)]
                     struct InvalidateWordSvc<T: Dictionary>(pub Arc<T>);
@@ -673,6 +1463,185 @@ pub mod dictionary_server {
                     };
                     Box::pin(fut)
                 }
+                "/dictionary.Dictionary/StreamAudio" => {
+                    #[allow(non_camel_case_types)]
+                    struct StreamAudioSvc<T: Dictionary>(pub Arc<T>);
+                    impl<
+                        T: Dictionary,
+                    > tonic::server::ServerStreamingService<super::StreamAudioRequest>
+                    for StreamAudioSvc<T> {
+                        type Response = super::AudioChunk;
+                        type ResponseStream = T::StreamAudioStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::StreamAudioRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).stream_audio(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = StreamAudioSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/dictionary.Dictionary/GetImage" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetImageSvc<T: Dictionary>(pub Arc<T>);
+                    impl<
+                        T: Dictionary,
+                    > tonic::server::UnaryService<super::GetImageRequest>
+                    for GetImageSvc<T> {
+                        type Response = super::GetImageResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetImageRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).get_image(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetImageSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/dictionary.Dictionary/GetWordRelations" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetWordRelationsSvc<T: Dictionary>(pub Arc<T>);
+                    impl<
+                        T: Dictionary,
+                    > tonic::server::UnaryService<super::GetWordRelationsRequest>
+                    for GetWordRelationsSvc<T> {
+                        type Response = super::GetWordRelationsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetWordRelationsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).get_word_relations(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetWordRelationsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/dictionary.Dictionary/Verify" => {
+                    #[allow(non_camel_case_types)]
+                    struct VerifySvc<T: Dictionary>(pub Arc<T>);
+                    impl<T: Dictionary> tonic::server::UnaryService<super::VerifyRequest>
+                    for VerifySvc<T> {
+                        type Response = super::VerifyResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::VerifyRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).verify(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = VerifySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
@@ -5,3 +5,11 @@ pub mod account {
 pub mod dictionary {
     include!("./dictionary.rs");
 }
+
+pub mod google {
+    pub mod rpc {
+        include!("./google.rpc.rs");
+    }
+}
+
+pub mod status;
@@ -0,0 +1,82 @@
+//! Machine readable details of a failed call, sent as a `google.rpc.Status`
+//! in the `grpc-status-details-bin` trailer like the other gRPC implementations do
+
+use crate::google::rpc::{ErrorInfo, RetryInfo, Status as RichStatus};
+use prost::Message;
+use prost_types::Any;
+use std::collections::HashMap;
+use std::time::Duration;
+use tonic::{Code, Status};
+
+const TYPE_URL_PREFIX: &str = "type.googleapis.com/";
+pub const ERROR_INFO: &str = "google.rpc.ErrorInfo";
+pub const RETRY_INFO: &str = "google.rpc.RetryInfo";
+
+/// `message` packed with its full proto name, e.g. "dictionary.WordNotFound"
+pub fn pack<M: Message>(name: &str, message: &M) -> Any {
+    Any {
+        type_url: format!("{TYPE_URL_PREFIX}{name}"),
+        value: message.encode_to_vec(),
+    }
+}
+
+/// status with the `ErrorInfo` of `reason` followed by the other `details`
+pub fn with_error_info(
+    code: Code,
+    message: impl Into<String>,
+    domain: &str,
+    reason: &str,
+    metadata: HashMap<String, String>,
+    details: Vec<Any>,
+) -> Status {
+    let message = message.into();
+    let info = ErrorInfo {
+        reason: reason.to_string(),
+        domain: domain.to_string(),
+        metadata,
+    };
+    let rich = RichStatus {
+        code: code as i32,
+        message: message.clone(),
+        details: std::iter::once(pack(ERROR_INFO, &info))
+            .chain(details)
+            .collect(),
+    };
+
+    Status::with_details(code, message, rich.encode_to_vec().into())
+}
+
+pub fn error_info(status: &Status) -> Option<ErrorInfo> {
+    detail(status, ERROR_INFO)
+}
+
+/// `RetryInfo` detail telling the client to wait `delay` before retrying
+pub fn retry_info(delay: Duration) -> Any {
+    let info = RetryInfo {
+        retry_delay: Some(prost_types::Duration {
+            seconds: delay.as_secs() as i64,
+            nanos: delay.subsec_nanos() as i32,
+        }),
+    };
+    pack(RETRY_INFO, &info)
+}
+
+/// how long the client should wait before retrying, from the `RetryInfo` detail
+pub fn retry_delay(status: &Status) -> Option<Duration> {
+    let delay = detail::<RetryInfo>(status, RETRY_INFO)?.retry_delay?;
+    Some(Duration::new(
+        u64::try_from(delay.seconds).ok()?,
+        u32::try_from(delay.nanos).ok()?,
+    ))
+}
+
+/// first detail of the given proto name, `None` when the status has no rich details
+pub fn detail<M: Message + Default>(status: &Status, name: &str) -> Option<M> {
+    let rich = RichStatus::decode(status.details()).ok()?;
+    let type_url = format!("{TYPE_URL_PREFIX}{name}");
+
+    rich.details
+        .iter()
+        .find(|any| any.type_url == type_url)
+        .and_then(|any| M::decode(any.value.as_slice()).ok())
+}
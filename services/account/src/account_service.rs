@@ -9,7 +9,7 @@ use crate::{
     auth::{create_jwt, verify},
     db::{
         models::{CollectionName, User},
-        Db,
+        Db, DbErr,
     },
     google,
 };
@@ -53,8 +53,7 @@ impl Account for AccountService {
 
                 if let Err(err) = self.db.insert_or_replace(user.clone()).await {
                     println!("{:?}", err);
-                    let err = format!("{:?}", err);
-                    return Err(Status::new(tonic::Code::Internal, err));
+                    return Err(db_status(err));
                 }
 
                 let token = create_jwt(&user);
@@ -80,7 +79,7 @@ impl Account for AccountService {
             .await
             .find_one(doc! {"google_id": claims.sub}, None)
             .await
-            .map_err(|_| Status::internal(""))?
+            .map_err(|err| db_status(DbErr::QueryErr(err)))?
             .ok_or(Status::not_found(""))?;
 
         let response = Response::new(MeResponse {
@@ -92,3 +91,12 @@ impl Account for AccountService {
         Ok(response)
     }
 }
+
+/// unavailable while the db is down so the clients retry, internal otherwise
+fn db_status(err: DbErr) -> Status {
+    if err.is_unreachable() {
+        Status::unavailable("database is unavailable")
+    } else {
+        Status::internal(format!("{:?}", err))
+    }
+}
@@ -1,7 +1,10 @@
-use mongodb::options::{ClientOptions, ReplaceOptions};
+use common_rs::db::{self, with_retry, Transient};
+use mongodb::options::ReplaceOptions;
+use mongodb::Database;
 use mongodb::{bson::Document, Collection};
-use mongodb::{Client, Database};
 use serde::{de::DeserializeOwned, Serialize};
+use tonic::server::NamedService;
+use tonic_health::server::HealthReporter;
 
 use super::models::{CollectionName, DbCollection};
 
@@ -12,32 +15,44 @@ pub trait FindOneFilter {
 #[derive(Debug)]
 pub enum DbErr {
     QueryErr(mongodb::error::Error),
+    ConnectErr(mongodb::error::Error),
 }
 
+impl DbErr {
+    /// no server can be reached, e.g. the db is down
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            DbErr::ConnectErr(_) => true,
+            DbErr::QueryErr(err) => db::is_unreachable(err),
+        }
+    }
+}
+
+impl Transient for DbErr {
+    fn is_transient(&self) -> bool {
+        match self {
+            DbErr::QueryErr(err) | DbErr::ConnectErr(err) => err.is_transient(),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Db {
     db: Database,
 }
 
 impl Db {
-    pub async fn new(db_connection_uri: String, db_name: &str) -> Db {
-        let client_options = ClientOptions::parse(&db_connection_uri)
+    /// fails on an invalid uri, no server is contacted until the first query
+    pub async fn new(db_connection_uri: String, db_name: &str) -> Result<Db, DbErr> {
+        let db = db::connect(&db_connection_uri, db_name)
             .await
-            .unwrap_or_else(|err| {
-                panic!(
-                    "Could not parse db_connection_uri: {}. Err: {}",
-                    &db_connection_uri, err
-                )
-            });
-
-        let client = Client::with_options(client_options).unwrap_or_else(|err| {
-            panic!(
-                "Could not create client with db_connection_uri: {}, Err: {}",
-                &db_connection_uri, err
-            )
-        });
+            .map_err(DbErr::ConnectErr)?;
+        Ok(Db { db })
+    }
 
-        let db = client.database(db_name);
-        Db { db }
+    /// reports `S` serving only while the db answers, see `common_rs::db::monitor`
+    pub async fn monitor<S: NamedService>(self, reporter: HealthReporter) {
+        db::monitor::<S, _, _, DbErr, _>(self.db, reporter, || async { Ok(()) }, |_| {}).await
     }
 
     pub async fn get_collection<T: Serialize>(
@@ -54,15 +69,18 @@ impl Db {
         &self,
         doc: Doc,
     ) -> Result<(), DbErr> {
-        let collection = self.get_collection(doc.get_collection_name()).await;
+        let collection = self.get_collection::<Doc>(doc.get_collection_name()).await;
 
         let mut replace_options = ReplaceOptions::default();
         // inserts when finds None
         replace_options.upsert = Some(true);
-        collection
-            .replace_one(doc.find_one_filter(), doc, replace_options)
-            .await
-            .map_err(DbErr::QueryErr)?;
+        with_retry(|| async {
+            collection
+                .replace_one(doc.find_one_filter(), &doc, replace_options.clone())
+                .await
+                .map_err(DbErr::QueryErr)
+        })
+        .await?;
 
         Ok(())
     }
@@ -21,11 +21,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
 
+    // serving once the db answers, see `Db::monitor`
     health_reporter
-        .set_serving::<AccountServer<AccountService>>()
+        .set_not_serving::<AccountServer<AccountService>>()
         .await;
 
-    let db = Db::new(Env::vars().db_connection_uri.clone(), "account").await;
+    let db = Db::new(Env::vars().db_connection_uri.clone(), "account")
+        .await
+        .map_err(|err| format!("Could not create the db client: {:?}", err))?;
+    tokio::spawn(
+        db.clone()
+            .monitor::<AccountServer<AccountService>>(health_reporter),
+    );
 
     let service = AccountService::new(db);
 
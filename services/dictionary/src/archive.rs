@@ -0,0 +1,162 @@
+use crate::db::database::DbErr;
+use crate::http::HttpResponse;
+use crate::models::raw_page::RawPageDoc;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::TryStreamExt;
+use mongodb::bson::{doc, spec::BinarySubtype, Binary, DateTime};
+use mongodb::options::ReplaceOptions;
+use mongodb::{Collection, Database};
+use std::io::{Read, Write};
+
+/// the html page with the definition
+pub const DEFINITION_PAGE: &str = "definition";
+/// json with the example sentences of vocabulary
+pub const EXAMPLES_PAGE: &str = "examples";
+
+/// Page as fetched by a scraper
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawPage {
+    pub kind: String,
+    pub url: String,
+    pub final_url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub bytes: Vec<u8>,
+    pub fetched_at: DateTime,
+}
+
+impl RawPage {
+    pub fn new(kind: &str, url: &str, res: &HttpResponse) -> Self {
+        RawPage {
+            kind: kind.to_string(),
+            url: url.to_string(),
+            final_url: res.url.clone(),
+            status: res.status.as_u16(),
+            content_type: res.content_type(),
+            bytes: res.bytes.clone(),
+            fetched_at: DateTime::now(),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.bytes).to_string()
+    }
+}
+
+/// Compressed raw pages keyed by the source and the ref of the doc built from them,
+/// so a parser fix can be rolled out by re-parsing instead of re-scraping
+#[derive(Debug, Clone)]
+pub struct Archive {
+    pages: Collection<RawPageDoc>,
+}
+
+impl Archive {
+    pub fn new(db: &Database) -> Self {
+        Archive {
+            pages: db.collection("raw_pages"),
+        }
+    }
+
+    /// replaces the pages of the same kind archived before
+    pub async fn save(&self, source: &str, word_ref: &str, pages: &[RawPage]) -> Result<(), DbErr> {
+        for page in pages {
+            let doc = RawPageDoc {
+                id: None,
+                source: source.to_string(),
+                word_ref: word_ref.to_string(),
+                kind: page.kind.clone(),
+                url: page.url.clone(),
+                final_url: page.final_url.clone(),
+                status: page.status as i32,
+                content_type: page.content_type.clone(),
+                body: Binary {
+                    subtype: BinarySubtype::Generic,
+                    bytes: compress(&page.bytes).map_err(DbErr::CompressErr)?,
+                },
+                fetched_at: page.fetched_at,
+            };
+
+            self.pages
+                .replace_one(
+                    doc! {"source": source, "word_ref": word_ref, "kind": &page.kind},
+                    doc,
+                    ReplaceOptions::builder().upsert(true).build(),
+                )
+                .await
+                .map_err(DbErr::QueryErr)?;
+        }
+        Ok(())
+    }
+
+    pub async fn get(&self, source: &str, word_ref: &str) -> Result<Vec<RawPage>, DbErr> {
+        let docs: Vec<RawPageDoc> = self
+            .pages
+            .find(doc! {"source": source, "word_ref": word_ref}, None)
+            .await
+            .map_err(DbErr::QueryErr)?
+            .try_collect()
+            .await
+            .map_err(DbErr::QueryErr)?;
+
+        let pages = docs
+            .into_iter()
+            .filter_map(|doc| match decompress(&doc.body.bytes) {
+                Ok(bytes) => Some(RawPage {
+                    kind: doc.kind,
+                    url: doc.url,
+                    final_url: doc.final_url,
+                    status: doc.status as u16,
+                    content_type: doc.content_type,
+                    bytes,
+                    fetched_at: doc.fetched_at,
+                }),
+                Err(err) => {
+                    println!("Could not decompress the archived {}: {:?}", doc.url, err);
+                    None
+                }
+            })
+            .collect();
+        Ok(pages)
+    }
+
+    /// refs of all the docs of the source with an archived page
+    pub async fn refs(&self, source: &str) -> Result<Vec<String>, DbErr> {
+        let values = self
+            .pages
+            .distinct("word_ref", doc! {"source": source}, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+
+        Ok(values
+            .iter()
+            .filter_map(|val| val.as_str().map(|x| x.to_string()))
+            .collect())
+    }
+}
+
+fn compress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+fn decompress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compress_ok() {
+        let html = "<html><body>cat cat cat cat cat cat cat cat</body></html>".repeat(10);
+
+        let compressed = compress(html.as_bytes()).unwrap();
+        assert!(compressed.len() < html.len());
+        assert_eq!(decompress(&compressed).unwrap(), html.as_bytes());
+        assert!(decompress(b"not gzip").is_err());
+    }
+}
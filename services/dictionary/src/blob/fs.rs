@@ -0,0 +1,117 @@
+use super::{BlobErr, BlobStore};
+use std::io::{ErrorKind, SeekFrom};
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Blobs as files in a local directory, sharded by the first two characters of the key
+#[derive(Debug)]
+pub struct FsStore {
+    root: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsStore { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let shard = key.get(..2).unwrap_or(key);
+        self.root.join(shard).join(key)
+    }
+}
+
+#[tonic::async_trait]
+impl BlobStore for FsStore {
+    async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), BlobErr> {
+        let path = self.path(key);
+        if tokio::fs::try_exists(&path).await.map_err(BlobErr::IoErr)? {
+            return Ok(());
+        }
+
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(BlobErr::IoErr)?;
+        }
+
+        // readers never see a partially written blob
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        tokio::fs::write(&tmp, bytes)
+            .await
+            .map_err(BlobErr::IoErr)?;
+        tokio::fs::rename(&tmp, &path).await.map_err(BlobErr::IoErr)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, BlobErr> {
+        match tokio::fs::read(self.path(key)).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(BlobErr::IoErr(err)),
+        }
+    }
+
+    async fn get_range(
+        &self,
+        key: &str,
+        start: u64,
+        length: u64,
+    ) -> Result<Option<Vec<u8>>, BlobErr> {
+        let mut file = match tokio::fs::File::open(self.path(key)).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(BlobErr::IoErr(err)),
+        };
+        file.seek(SeekFrom::Start(start))
+            .await
+            .map_err(BlobErr::IoErr)?;
+
+        let mut bytes = Vec::new();
+        file.take(length)
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(BlobErr::IoErr)?;
+        Ok(Some(bytes))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), BlobErr> {
+        match tokio::fs::remove_file(self.path(key)).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(BlobErr::IoErr(err)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob::key;
+
+    #[tokio::test]
+    async fn fs_store_ok() {
+        let root = std::env::temp_dir().join(format!("fs_store_ok_{}", std::process::id()));
+        let store = FsStore::new(&root);
+        let bytes = b"audio".to_vec();
+        let key = key(&bytes);
+
+        assert_eq!(store.get(&key).await.unwrap(), None);
+
+        store.put(&key, &bytes).await.unwrap();
+        store.put(&key, &bytes).await.unwrap();
+        assert_eq!(store.get(&key).await.unwrap(), Some(bytes));
+        assert_eq!(
+            store.get_range(&key, 1, 3).await.unwrap(),
+            Some(b"udi".to_vec())
+        );
+        assert_eq!(
+            store.get_range(&key, 3, 10).await.unwrap(),
+            Some(b"io".to_vec())
+        );
+        assert!(root.join(&key[..2]).join(&key).exists());
+
+        store.delete(&key).await.unwrap();
+        store.delete(&key).await.unwrap();
+        assert_eq!(store.get(&key).await.unwrap(), None);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
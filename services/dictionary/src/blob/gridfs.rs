@@ -0,0 +1,137 @@
+use super::{BlobErr, BlobStore};
+use futures::{AsyncReadExt, TryStreamExt};
+use mongodb::bson::{doc, Binary, Bson};
+use mongodb::gridfs::{FilesCollectionDocument, GridFsBucket};
+use mongodb::options::{FindOptions, GridFsBucketOptions};
+use mongodb::{Collection, Database};
+use serde::Deserialize;
+
+/// Blobs in the `blobs` GridFS bucket of the db, the key is used as the file id
+#[derive(Debug)]
+pub struct GridFsStore {
+    bucket: GridFsBucket,
+    /// read directly for the ranges, the bucket only streams whole files
+    chunks: Collection<Chunk>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chunk {
+    data: Binary,
+}
+
+impl GridFsStore {
+    pub fn new(db: &Database) -> Self {
+        let options = GridFsBucketOptions::builder()
+            .bucket_name("blobs".to_string())
+            .build();
+
+        GridFsStore {
+            bucket: db.gridfs_bucket(options),
+            chunks: db.collection("blobs.chunks"),
+        }
+    }
+
+    async fn file(&self, key: &str) -> Result<Option<FilesCollectionDocument>, BlobErr> {
+        let mut cursor = self
+            .bucket
+            .find(doc! {"_id": key}, None)
+            .await
+            .map_err(BlobErr::DbErr)?;
+
+        cursor.try_next().await.map_err(BlobErr::DbErr)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, BlobErr> {
+        Ok(self.file(key).await?.is_some())
+    }
+}
+
+#[tonic::async_trait]
+impl BlobStore for GridFsStore {
+    async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), BlobErr> {
+        if self.exists(key).await? {
+            return Ok(());
+        }
+
+        let result = self
+            .bucket
+            .upload_from_futures_0_3_reader_with_id(Bson::String(key.to_string()), key, bytes, None)
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            // uploaded by someone else in the meantime
+            Err(_) if self.exists(key).await? => Ok(()),
+            Err(err) => Err(BlobErr::DbErr(err)),
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, BlobErr> {
+        if !self.exists(key).await? {
+            return Ok(None);
+        }
+
+        let mut stream = self
+            .bucket
+            .open_download_stream(Bson::String(key.to_string()))
+            .await
+            .map_err(BlobErr::DbErr)?;
+
+        let mut bytes = Vec::new();
+        stream
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(BlobErr::IoErr)?;
+        Ok(Some(bytes))
+    }
+
+    /// reads only the chunks holding the range
+    async fn get_range(
+        &self,
+        key: &str,
+        start: u64,
+        length: u64,
+    ) -> Result<Option<Vec<u8>>, BlobErr> {
+        let file = match self.file(key).await? {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        let end = start.saturating_add(length).min(file.length);
+        if start >= end {
+            return Ok(Some(Vec::new()));
+        }
+
+        let chunk_size = file.chunk_size_bytes as u64;
+        let (first, last) = (start / chunk_size, (end - 1) / chunk_size);
+        let mut chunks = self
+            .chunks
+            .find(
+                doc! {"files_id": key, "n": {"$gte": first as i64, "$lte": last as i64}},
+                FindOptions::builder().sort(doc! {"n": 1}).build(),
+            )
+            .await
+            .map_err(BlobErr::DbErr)?;
+
+        let mut bytes = Vec::with_capacity((end - start) as usize);
+        let mut offset = first * chunk_size;
+        while let Some(chunk) = chunks.try_next().await.map_err(BlobErr::DbErr)? {
+            let data = chunk.data.bytes;
+            let to = ((end - offset) as usize).min(data.len());
+            let from = (start.saturating_sub(offset) as usize).min(to);
+            bytes.extend_from_slice(&data[from..to]);
+            offset += data.len() as u64;
+        }
+        Ok(Some(bytes))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), BlobErr> {
+        if !self.exists(key).await? {
+            return Ok(());
+        }
+
+        self.bucket
+            .delete(Bson::String(key.to_string()))
+            .await
+            .map_err(BlobErr::DbErr)
+    }
+}
@@ -0,0 +1,65 @@
+use crate::env::Env;
+use common_rs::EnvStore;
+use mongodb::Database;
+use sha2::{Digest, Sha256};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+pub mod fs;
+pub mod gridfs;
+pub mod s3;
+
+#[derive(Debug)]
+pub enum BlobErr {
+    IoErr(std::io::Error),
+    DbErr(mongodb::error::Error),
+    RequestErr(reqwest::Error),
+    UnexpectedStatus(u16),
+}
+
+/// Storage of binary content addressed by its hash, see `blob::key`
+#[tonic::async_trait]
+pub trait BlobStore: Debug + Send + Sync {
+    /// stores the bytes, does nothing when the key is stored already
+    async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), BlobErr>;
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, BlobErr>;
+
+    /// `length` bytes from `start`, fewer at the end of the blob
+    async fn get_range(
+        &self,
+        key: &str,
+        start: u64,
+        length: u64,
+    ) -> Result<Option<Vec<u8>>, BlobErr>;
+
+    async fn delete(&self, key: &str) -> Result<(), BlobErr>;
+}
+
+/// hex encoded sha256 of the bytes
+pub fn key(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// store configured by `BLOB_STORE`, GridFS in the dictionary db by default
+pub fn from_env(db: &Database) -> Arc<dyn BlobStore> {
+    let env = Env::vars();
+    let required = |val: Option<String>, name: &str| {
+        val.unwrap_or_else(|| panic!("{} is required for the s3 blob store", name))
+    };
+
+    match env.blob_store.as_deref() {
+        None | Some("gridfs") => Arc::new(gridfs::GridFsStore::new(db)),
+        Some("fs") => Arc::new(fs::FsStore::new(
+            env.blob_dir.unwrap_or_else(|| "./blobs".to_string()),
+        )),
+        Some("s3") => Arc::new(s3::S3Store::new(
+            required(env.s3_endpoint, "S3_ENDPOINT"),
+            required(env.s3_bucket, "S3_BUCKET"),
+            env.s3_region.unwrap_or_else(|| "us-east-1".to_string()),
+            required(env.s3_access_key, "S3_ACCESS_KEY"),
+            required(env.s3_secret_key, "S3_SECRET_KEY"),
+        )),
+        Some(other) => panic!("Unknown blob store: {}", other),
+    }
+}
@@ -0,0 +1,71 @@
+use serde::Serialize;
+
+/// Which of the sections expected on a scraped page came out empty,
+/// mostly because the markup changed and a selector does not match anymore
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Completeness {
+    pub checked: Vec<&'static str>,
+    pub empty: Vec<&'static str>,
+    /// empty sections without which the page is useless
+    pub missing_required: Vec<&'static str>,
+}
+
+impl Completeness {
+    pub fn required(mut self, section: &'static str, present: bool) -> Self {
+        if !present {
+            self.missing_required.push(section);
+        }
+        self.expected(section, present)
+    }
+
+    pub fn expected(mut self, section: &'static str, present: bool) -> Self {
+        self.checked.push(section);
+        if !present {
+            self.empty.push(section);
+        }
+        self
+    }
+
+    /// share of the checked sections which are present
+    pub fn score(&self) -> f64 {
+        if self.checked.is_empty() {
+            return 1.0;
+        }
+        (self.checked.len() - self.empty.len()) as f64 / self.checked.len() as f64
+    }
+
+    /// `false` when the page should not be stored
+    pub fn is_acceptable(&self, threshold: f64) -> bool {
+        self.missing_required.is_empty() && self.score() >= threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completeness_ok() {
+        let complete = Completeness::default()
+            .required("header", true)
+            .expected("note", true);
+        assert_eq!(complete.score(), 1.0);
+        assert!(complete.is_acceptable(1.0));
+
+        let partial = Completeness::default()
+            .required("header", true)
+            .expected("note", false)
+            .expected("origin", false)
+            .expected("audio", true);
+        assert_eq!(partial.empty, vec!["note", "origin"]);
+        assert_eq!(partial.score(), 0.5);
+        assert!(partial.is_acceptable(0.5));
+        assert!(!partial.is_acceptable(0.6));
+
+        let broken = Completeness::default()
+            .required("header", false)
+            .expected("note", true);
+        assert_eq!(broken.missing_required, vec!["header"]);
+        assert!(!broken.is_acceptable(0.0));
+    }
+}
@@ -1,35 +1,145 @@
-use mongodb::options::ClientOptions;
-use mongodb::{Client, Database};
+use crate::blob::BlobErr;
+use common_rs::db::{self, Transient};
+use mongodb::bson::{doc, oid::ObjectId, to_document, Document};
+use mongodb::error::{Error, ErrorKind, WriteFailure};
+use mongodb::options::{FindOneAndUpdateOptions, FindOneOptions, ReplaceOptions, ReturnDocument};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum DbErr {
-    QueryErr(mongodb::error::Error),
-    ParseBsonErr(mongodb::bson::oid::Error),
-    Unexpected,
+    #[error("query failed: {0}")]
+    QueryErr(#[source] mongodb::error::Error),
+    #[error("invalid id: {0}")]
+    ParseBsonErr(#[source] mongodb::bson::oid::Error),
+    #[error("could not serialize: {0}")]
+    SerializeBsonErr(#[source] mongodb::bson::ser::Error),
+    #[error("blob store failed: {0:?}")]
+    BlobErr(BlobErr),
+    #[error("could not compress: {0}")]
+    CompressErr(#[source] std::io::Error),
+    /// e.g. an upsert which did not return the doc
+    #[error("missing {0}")]
+    MissingDoc(&'static str),
+    #[error("could not connect: {0}")]
+    ConnectErr(#[source] mongodb::error::Error),
+    /// the last ping got no answer, see `DbHealth`
+    #[error("database is unreachable")]
+    Unreachable,
+    /// another instance is applying the migration of the version
+    #[error("migration {0} is locked")]
+    MigrationLocked(i32),
+    /// the lease of the job expired and another worker took it over
+    #[error("lost the lease of the job")]
+    LeaseLost,
 }
 
-impl From<DbErr> for String {
-    fn from(value: DbErr) -> Self {
-        format!("{:?}", value)
+impl DbErr {
+    /// the query ran out of time, either on the server or waiting for its answer
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            DbErr::QueryErr(err) => db::is_timeout(err),
+            _ => false,
+        }
+    }
+
+    /// no server can be reached, e.g. the db is down
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            DbErr::Unreachable | DbErr::ConnectErr(_) => true,
+            DbErr::QueryErr(err) => db::is_unreachable(err),
+            _ => false,
+        }
+    }
+}
+
+impl Transient for DbErr {
+    fn is_transient(&self) -> bool {
+        match self {
+            DbErr::QueryErr(err) | DbErr::ConnectErr(err) => err.is_transient(),
+            _ => false,
+        }
     }
 }
 
-pub async fn get_database_client(db_connection_uri: String, db_name: &str) -> Database {
-    let client_options = ClientOptions::parse(&db_connection_uri)
+pub async fn get_database_client(
+    db_connection_uri: String,
+    db_name: &str,
+) -> Result<Database, DbErr> {
+    db::connect(&db_connection_uri, db_name)
         .await
-        .unwrap_or_else(|err| {
-            panic!(
-                "Could not parse db_connection_uri: {}. Err: {}",
-                &db_connection_uri, err
-            )
-        });
-
-    let client = Client::with_options(client_options).unwrap_or_else(|err| {
-        panic!(
-            "Could not create client with db_connection_uri: {}, Err: {}",
-            &db_connection_uri, err
+        .map_err(DbErr::ConnectErr)
+}
+
+#[derive(Deserialize)]
+struct Id {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+}
+
+pub async fn find_id<T>(collection: &Collection<T>, filter: Document) -> Option<ObjectId> {
+    collection
+        .clone_with_type::<Id>()
+        .find_one(
+            filter,
+            FindOneOptions::builder()
+                .projection(doc! {"_id": 1})
+                .build(),
         )
-    });
+        .await
+        .ok()
+        .flatten()
+        .map(|val| val.id)
+}
+
+/// inserts `doc` unless one matching `filter` is stored already, returns the id of the stored one.
+/// Backed by a unique index on the filtered fields, concurrent inserts of the same doc store it once
+pub async fn insert_unique<T: Serialize>(
+    collection: &Collection<T>,
+    filter: Document,
+    doc: &T,
+) -> Result<Option<ObjectId>, DbErr> {
+    let doc = to_document(doc).map_err(DbErr::SerializeBsonErr)?;
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .projection(doc! {"_id": 1})
+        .build();
+
+    let stored = collection
+        .clone_with_type::<Id>()
+        .find_one_and_update(filter.clone(), doc! {"$setOnInsert": doc}, options)
+        .await;
+    match stored {
+        Ok(stored) => Ok(stored.map(|val| val.id)),
+        // lost to a concurrent upsert, the doc of the winner is stored by now
+        Err(err) if is_duplicate_key(&err) => Ok(find_id(collection, filter).await),
+        Err(err) => Err(DbErr::QueryErr(err)),
+    }
+}
+
+pub fn is_duplicate_key(err: &Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(err)) => err.code == 11000,
+        ErrorKind::Command(err) => err.code == 11000,
+        _ => false,
+    }
+}
+
+/// inserts when the doc matching `filter` does not exist, replaces otherwise
+pub async fn upsert<T: Serialize>(
+    collection: &Collection<T>,
+    filter: Document,
+    doc: T,
+) -> Result<Option<ObjectId>, DbErr> {
+    let mut replace_options = ReplaceOptions::default();
+    // inserts when finds None
+    replace_options.upsert = Some(true);
+
+    collection
+        .replace_one(filter.clone(), doc, replace_options)
+        .await
+        .map_err(DbErr::QueryErr)?;
 
-    client.database(db_name)
+    Ok(find_id(collection, filter).await)
 }
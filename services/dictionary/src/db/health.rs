@@ -0,0 +1,86 @@
+use crate::blob::BlobStore;
+use crate::db::database::DbErr;
+use crate::db::{indexes, migrations};
+use common_rs::db;
+use mongodb::Database;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tonic::server::NamedService;
+use tonic_health::server::HealthReporter;
+
+/// Whether the db answered the last ping, shared by the service to stop storing
+/// the scraped definitions while it does not. Down until the db answered and is prepared, see `monitor`
+#[derive(Debug, Clone)]
+pub struct DbHealth(Arc<AtomicBool>);
+
+impl Default for DbHealth {
+    fn default() -> Self {
+        DbHealth(Arc::new(AtomicBool::new(false)))
+    }
+}
+
+impl DbHealth {
+    pub fn is_up(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// `true` when it changed
+    pub fn set(&self, up: bool) -> bool {
+        self.0.swap(up, Ordering::Relaxed) != up
+    }
+}
+
+/// Reports `S` serving only while the db answers, see `common_rs::db::monitor`.
+/// The first time it answers the db is migrated and indexed, see `prepare`
+pub async fn monitor<S: NamedService>(
+    db: Database,
+    blobs: Arc<dyn BlobStore>,
+    health: DbHealth,
+    reporter: HealthReporter,
+) {
+    let prepared = db.clone();
+    db::monitor::<S, _, _, _, _>(
+        db,
+        reporter,
+        move || {
+            let db = prepared.clone();
+            let blobs = blobs.clone();
+            async move { prepare(&db, blobs.as_ref()).await }
+        },
+        move |up| {
+            health.set(up);
+        },
+    )
+    .await
+}
+
+/// applies the pending migrations and creates the missing indexes
+async fn prepare(db: &Database, blobs: &dyn BlobStore) -> Result<(), DbErr> {
+    // before the indexes, a unique index may need the data migrated first
+    let migrated = migrations::migrate(db, blobs).await?;
+    if !migrated.is_empty() {
+        println!("Applied migrations: {}", migrated.join(", "));
+    }
+    if let Err(err) = indexes::ensure_indexes(db).await {
+        println!("Could not ensure the indexes: {:?}", err);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn db_health_ok() {
+        let health = DbHealth::default();
+        let shared = health.clone();
+
+        assert!(!health.is_up());
+        assert!(health.set(true));
+        assert!(shared.is_up());
+        assert!(!health.set(true));
+        assert!(shared.set(false));
+        assert!(!health.is_up());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::db::database::DbErr;
+use mongodb::bson::{doc, Document};
+use mongodb::options::IndexOptions;
+use mongodb::{Database, IndexModel};
+
+/// collection, keys and whether the keys are unique
+type Index = (&'static str, Document, bool);
+/// collection, keys and the filter of the docs among which the keys are unique
+type PartialIndex = (&'static str, Document, Document);
+
+/// indexes of every field the service filters on, the unique ones are the keys docs are upserted by
+fn indexes() -> Vec<Index> {
+    vec![
+        ("definitions", doc! {"word": 1}, true),
+        ("vocabulary_definitions", doc! {"voc_ref": 1}, true),
+        ("vocabulary_definitions", doc! {"searched_word": 1}, false),
+        ("oxford_definitions", doc! {"oxford_ref": 1}, true),
+        ("oxford_definitions", doc! {"searched_word": 1}, false),
+        ("wiktionary_definitions", doc! {"word": 1}, true),
+        ("audio", doc! {"hash": 1}, true),
+        ("images", doc! {"hash": 1}, true),
+        (
+            "raw_pages",
+            doc! {"source": 1, "word_ref": 1, "kind": 1},
+            true,
+        ),
+        ("inflections", doc! {"form": 1}, true),
+        ("jobs", doc! {"word": 1, "status": 1}, false),
+        ("jobs", doc! {"status": 1, "run_at": 1}, false),
+        ("migrations", doc! {"version": 1}, true),
+    ]
+}
+
+fn partial_indexes() -> Vec<PartialIndex> {
+    vec![
+        // a word has at most one pending job, see `JobQueue::enqueue`
+        (
+            "jobs",
+            doc! {"word": 1},
+            doc! {"status": {"$in": ["queued", "running"]}},
+        ),
+    ]
+}
+
+/// creates the missing indexes, the existing ones are left as they are
+pub async fn ensure_indexes(db: &Database) -> Result<(), DbErr> {
+    let options = indexes()
+        .into_iter()
+        .map(|(collection, keys, unique)| {
+            let options = IndexOptions::builder().unique(unique).build();
+            (collection, keys, options)
+        })
+        .chain(
+            partial_indexes()
+                .into_iter()
+                .map(|(collection, keys, filter)| {
+                    let options = IndexOptions::builder()
+                        .unique(true)
+                        .partial_filter_expression(filter)
+                        .build();
+                    (collection, keys, options)
+                }),
+        );
+
+    for (collection, keys, options) in options {
+        let index = IndexModel::builder().keys(keys).options(options).build();
+
+        db.collection::<Document>(collection)
+            .create_index(index, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn indexes_ok() {
+        let indexes = indexes();
+        let unique: HashSet<_> = indexes
+            .iter()
+            .map(|(collection, keys, _)| format!("{collection} {keys}"))
+            .collect();
+
+        assert_eq!(unique.len(), indexes.len());
+        // at most one unique key per collection, it identifies the doc
+        let keyed: Vec<_> = indexes.iter().filter(|x| x.2).map(|x| x.0).collect();
+        assert_eq!(keyed.len(), keyed.iter().collect::<HashSet<_>>().len());
+    }
+}
@@ -0,0 +1,386 @@
+use crate::blob::{self, BlobStore};
+use crate::db::database::{find_id, is_duplicate_key, DbErr};
+use crate::models::migration::MigrationDoc;
+use futures::future::BoxFuture;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use mongodb::options::{IndexOptions, UpdateOptions};
+use mongodb::{Collection, Database, IndexModel};
+use serde::Deserialize;
+
+/// a migration still not applied this long after it started is taken to have died with its instance
+const LOCK_TIMEOUT_SECS: i64 = 30 * 60;
+
+/// Change of the stored data, e.g. a renamed field, applied once per database in the order of `version`.
+/// Steps should be idempotent, a step which fails halfway is run again on the next start
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    run: Step,
+}
+
+/// the blob store is for the steps moving bytes out of the db
+type Step = for<'a> fn(&'a Database, &'a dyn BlobStore) -> BoxFuture<'a, Result<(), DbErr>>;
+
+/// append only, a released migration must not be changed or reordered
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "rename_veb_forms",
+        run: rename_veb_forms,
+    },
+    Migration {
+        version: 2,
+        name: "move_source_ids",
+        run: move_source_ids,
+    },
+    Migration {
+        version: 3,
+        name: "move_audio_bytes",
+        run: move_audio_bytes,
+    },
+    Migration {
+        version: 4,
+        name: "dedupe_audio_and_images",
+        run: dedupe_audio_and_images,
+    },
+];
+
+/// fields of the source ids the definitions had before `DefinitionDoc::sources`
+const LEGACY_SOURCE_IDS: &[(&str, &str)] =
+    &[("vocabulary_id", "vocabulary"), ("oxford_id", "oxford")];
+
+fn rename_veb_forms<'a>(
+    db: &'a Database,
+    _: &'a dyn BlobStore,
+) -> BoxFuture<'a, Result<(), DbErr>> {
+    Box::pin(async move {
+        db.collection::<Document>("oxford_definitions")
+            .update_many(
+                doc! {"veb_forms": {"$exists": true}},
+                doc! {"$rename": {"veb_forms": "verb_forms"}},
+                None,
+            )
+            .await
+            .map_err(DbErr::QueryErr)?;
+        Ok(())
+    })
+}
+
+/// moves `vocabulary_id` and `oxford_id` into `sources`, an id stored since under the new key wins
+fn move_source_ids<'a>(db: &'a Database, _: &'a dyn BlobStore) -> BoxFuture<'a, Result<(), DbErr>> {
+    Box::pin(async move {
+        let definitions = db.collection::<Document>("definitions");
+        for &(field, source) in LEGACY_SOURCE_IDS {
+            let key = format!("sources.{source}");
+            definitions
+                .update_many(
+                    doc! {field: {"$type": "objectId"}, &key: {"$exists": false}},
+                    vec![doc! {"$set": {&key: format!("${field}")}}],
+                    None,
+                )
+                .await
+                .map_err(DbErr::QueryErr)?;
+        }
+
+        let fields: Vec<_> = LEGACY_SOURCE_IDS.iter().map(|(field, _)| *field).collect();
+        let exists: Vec<_> = fields
+            .iter()
+            .map(|field| doc! {*field: {"$exists": true}})
+            .collect();
+        let unset: Document = fields
+            .iter()
+            .map(|field| (field.to_string(), "".into()))
+            .collect();
+        definitions
+            .update_many(doc! {"$or": exists}, doc! {"$unset": unset}, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+        Ok(())
+    })
+}
+
+/// collection referring to a deduped doc, the path of the id, the update path of the id
+/// with `$[ref]` at the array holding it and the array filter field of the id
+type IdRef = (&'static str, &'static str, &'static str, &'static str);
+
+const AUDIO_REFS: &[IdRef] = &[
+    (
+        "oxford_definitions",
+        "pronunciations.audio_id",
+        "pronunciations.$[ref].audio_id",
+        "ref.audio_id",
+    ),
+    (
+        "oxford_definitions",
+        "verb_forms.pronunciations.audio_id",
+        "verb_forms.$[].pronunciations.$[ref].audio_id",
+        "ref.audio_id",
+    ),
+    (
+        "vocabulary_definitions",
+        "pronunciations.audio_id",
+        "pronunciations.$[ref].audio_id",
+        "ref.audio_id",
+    ),
+];
+
+const IMAGE_REFS: &[IdRef] = &[(
+    "vocabulary_definitions",
+    "definitions.image_id",
+    "definitions.$[ref].image_id",
+    "ref.image_id",
+)];
+
+#[derive(Deserialize)]
+struct LegacyAudioDoc {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    bytes: Vec<u8>,
+}
+
+/// moves the bytes of the audio stored before the blob store out of the `audio` collection.
+/// The legacy docs have no hash yet, one whose bytes are stored already is merged into the stored doc
+fn move_audio_bytes<'a>(
+    db: &'a Database,
+    blobs: &'a dyn BlobStore,
+) -> BoxFuture<'a, Result<(), DbErr>> {
+    Box::pin(async move {
+        let audio = db.collection::<LegacyAudioDoc>("audio");
+        let mut cursor = audio
+            .find(doc! {"bytes": {"$exists": true}}, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+
+        while let Some(legacy) = cursor.try_next().await.map_err(DbErr::QueryErr)? {
+            let hash = blob::key(&legacy.bytes);
+            blobs
+                .put(&hash, &legacy.bytes)
+                .await
+                .map_err(DbErr::BlobErr)?;
+
+            let update = doc! {
+                "$set": {"hash": &hash, "size": legacy.bytes.len() as i64},
+                "$unset": {"bytes": ""},
+            };
+            match audio
+                .update_one(doc! {"_id": legacy.id}, update, None)
+                .await
+            {
+                Ok(_) => {}
+                // the unique index of the hash is there from an earlier start
+                Err(err) if is_duplicate_key(&err) => {
+                    let stored = find_id(&audio, doc! {"hash": &hash, "bytes": {"$exists": false}})
+                        .await
+                        .ok_or(DbErr::MissingDoc("audio of the hash"))?;
+                    repoint(db, AUDIO_REFS, &stored, &[legacy.id]).await?;
+                    audio
+                        .delete_one(doc! {"_id": legacy.id}, None)
+                        .await
+                        .map_err(DbErr::QueryErr)?;
+                }
+                Err(err) => return Err(DbErr::QueryErr(err)),
+            }
+        }
+        Ok(())
+    })
+}
+
+/// concurrent lookups stored the same bytes more than once, the hashes have to be unique for the index
+fn dedupe_audio_and_images<'a>(
+    db: &'a Database,
+    _: &'a dyn BlobStore,
+) -> BoxFuture<'a, Result<(), DbErr>> {
+    Box::pin(async move {
+        dedupe(db, "audio", AUDIO_REFS).await?;
+        dedupe(db, "images", IMAGE_REFS).await
+    })
+}
+
+/// keeps the oldest doc of each hash, points the refs to the other ones at it and deletes them.
+/// The blobs are keyed by the hash, so they are shared and stay
+async fn dedupe(db: &Database, collection: &str, refs: &[IdRef]) -> Result<(), DbErr> {
+    let docs = db.collection::<Document>(collection);
+    let groups: Vec<Document> = docs
+        .aggregate(
+            vec![
+                // a doc without a hash is not a duplicate of the other ones without
+                doc! {"$match": {"hash": {"$type": "string"}}},
+                doc! {"$sort": {"_id": 1}},
+                doc! {"$group": {"_id": "$hash", "ids": {"$push": "$_id"}}},
+                doc! {"$match": {"ids.1": {"$exists": true}}},
+            ],
+            None,
+        )
+        .await
+        .map_err(DbErr::QueryErr)?
+        .try_collect()
+        .await
+        .map_err(DbErr::QueryErr)?;
+
+    for group in groups {
+        let ids: Vec<ObjectId> = group
+            .get_array("ids")
+            .map(|ids| ids.iter().filter_map(|id| id.as_object_id()).collect())
+            .unwrap_or_default();
+        let Some((keep, duplicates)) = ids.split_first() else {
+            continue;
+        };
+
+        repoint(db, refs, keep, duplicates).await?;
+        docs.delete_many(doc! {"_id": {"$in": duplicates.to_vec()}}, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+    }
+    Ok(())
+}
+
+/// points the refs to the duplicates at `keep`
+async fn repoint(
+    db: &Database,
+    refs: &[IdRef],
+    keep: &ObjectId,
+    duplicates: &[ObjectId],
+) -> Result<(), DbErr> {
+    for &(referring, path, update_path, filter_field) in refs {
+        db.collection::<Document>(referring)
+            .update_many(
+                doc! {path: {"$in": duplicates.to_vec()}},
+                doc! {"$set": {update_path: keep}},
+                UpdateOptions::builder()
+                    .array_filters(vec![doc! {filter_field: {"$in": duplicates.to_vec()}}])
+                    .build(),
+            )
+            .await
+            .map_err(DbErr::QueryErr)?;
+    }
+    Ok(())
+}
+
+/// applies the migrations not applied yet, returns the names of the applied ones.
+/// Fails with `MigrationLocked` while another instance applies one, the later ones have to wait for it
+pub async fn migrate(db: &Database, blobs: &dyn BlobStore) -> Result<Vec<&'static str>, DbErr> {
+    let migrations: Collection<MigrationDoc> = db.collection("migrations");
+    // the unique version is the lock of a running migration
+    migrations
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"version": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await
+        .map_err(DbErr::QueryErr)?;
+
+    // the locks of the running ones have no `applied_at`
+    let applied: Vec<i32> = migrations
+        .distinct("version", doc! {"applied_at": {"$type": "date"}}, None)
+        .await
+        .map_err(DbErr::QueryErr)?
+        .iter()
+        .filter_map(|val| val.as_i32())
+        .collect();
+
+    let mut names = Vec::new();
+    for migration in pending(MIGRATIONS, &applied) {
+        let lock = MigrationDoc {
+            id: None,
+            version: migration.version,
+            name: migration.name.to_string(),
+            started_at: DateTime::now(),
+            applied_at: None,
+        };
+        match migrations.insert_one(lock, None).await {
+            Ok(_) => {}
+            Err(err) if is_duplicate_key(&err) => {
+                if !take_over(&migrations, migration.version).await? {
+                    return Err(DbErr::MigrationLocked(migration.version));
+                }
+            }
+            Err(err) => return Err(DbErr::QueryErr(err)),
+        }
+
+        println!(
+            "Applying migration {} {}",
+            migration.version, migration.name
+        );
+        let filter = doc! {"version": migration.version};
+        if let Err(err) = (migration.run)(db, blobs).await {
+            // unlocked, so it is retried on the next start
+            migrations
+                .delete_one(filter, None)
+                .await
+                .map_err(DbErr::QueryErr)?;
+            return Err(err);
+        }
+
+        migrations
+            .update_one(filter, doc! {"$set": {"applied_at": DateTime::now()}}, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+        names.push(migration.name);
+    }
+
+    Ok(names)
+}
+
+/// locks a migration again when the instance which locked it did not apply it in time,
+/// `false` when it is still locked
+async fn take_over(migrations: &Collection<MigrationDoc>, version: i32) -> Result<bool, DbErr> {
+    let stale =
+        DateTime::from_millis(DateTime::now().timestamp_millis() - LOCK_TIMEOUT_SECS * 1000);
+    let res = migrations
+        .update_one(
+            doc! {"version": version, "applied_at": null, "started_at": {"$lte": stale}},
+            doc! {"$set": {"started_at": DateTime::now()}},
+            None,
+        )
+        .await
+        .map_err(DbErr::QueryErr)?;
+
+    if res.modified_count > 0 {
+        println!("Taking over the stale lock of migration {version}");
+    }
+    Ok(res.modified_count > 0)
+}
+
+fn pending<'a>(migrations: &'a [Migration], applied: &[i32]) -> Vec<&'a Migration> {
+    migrations
+        .iter()
+        .filter(|x| !applied.contains(&x.version))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrations_are_ordered() {
+        let versions: Vec<_> = MIGRATIONS.iter().map(|x| x.version).collect();
+
+        assert!(versions.windows(2).all(|x| x[0] < x[1]));
+        assert_eq!(versions.first(), Some(&1));
+    }
+
+    #[test]
+    fn pending_ok() {
+        let noop: Step = |_, _| Box::pin(async { Ok(()) });
+        let migrations = [1, 2, 3].map(|version| Migration {
+            version,
+            name: "noop",
+            run: noop,
+        });
+
+        let versions = |applied: &[i32]| -> Vec<i32> {
+            pending(&migrations, applied)
+                .iter()
+                .map(|x| x.version)
+                .collect()
+        };
+        assert_eq!(versions(&[]), vec![1, 2, 3]);
+        assert_eq!(versions(&[1, 3]), vec![2]);
+        assert!(versions(&[1, 2, 3]).is_empty());
+    }
+}
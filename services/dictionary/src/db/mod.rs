@@ -1,2 +1,5 @@
 pub mod database;
+pub mod health;
+pub mod indexes;
+pub mod migrations;
 pub mod repository;
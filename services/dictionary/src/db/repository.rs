@@ -1,39 +1,43 @@
-use crate::models::audio::AudioDoc;
-use crate::models::oxford::DefinitionDoc as OxDefinitionDoc;
-use crate::models::shared::{Pronunciation, PronunciationDoc};
-use crate::models::vocabulary::DefinitionDoc as VocDefinitionDoc;
-use crate::oxford::Definition as OxScrapedDefinition;
-use crate::vocabulary::Definition as VocScrapedDefinition;
-use crate::{db::database::DbErr, models::definition::DefinitionDoc};
-use mongodb::options::FindOneOptions;
+use crate::archive::Archive;
+use crate::blob::{self, BlobStore};
+use crate::db::database::{find_id, insert_unique, DbErr};
+use crate::lemmatizer::Lemmatizer;
+use crate::models::audio::{AudioDoc, AudioVariant};
+use crate::models::definition::DefinitionDoc;
+use crate::models::image::ImageDoc;
+use crate::models::shared::{Audio, Image, Pronunciation, PronunciationDoc};
+use crate::transcoder::{Format, Transcoder};
+use futures::TryStreamExt;
 use mongodb::{
-    bson::{doc, oid::ObjectId},
-    options::ReplaceOptions,
+    bson::{doc, oid::ObjectId, to_bson},
+    options::{ReplaceOptions, UpdateOptions},
     Collection, Database,
 };
-use serde::Deserialize;
+use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Repository {
     pub definitions: Collection<DefinitionDoc>,
     pub audio: Collection<AudioDoc>,
-    pub voc_definitions: Collection<VocDefinitionDoc>,
-    pub ox_definitions: Collection<OxDefinitionDoc>,
-}
-
-#[derive(Deserialize)]
-struct Id {
-    #[serde(rename = "_id")]
-    pub id: ObjectId,
+    pub images: Collection<ImageDoc>,
+    /// raw pages the stored source docs were parsed from
+    pub archive: Archive,
+    /// inflected forms learned from the scraped headwords
+    pub lemmatizer: Lemmatizer,
+    pub blobs: Arc<dyn BlobStore>,
+    pub transcoder: Transcoder,
 }
 
 impl Repository {
-    pub fn new(db: Database) -> Self {
+    pub fn new(db: Database, blobs: Arc<dyn BlobStore>, transcoder: Transcoder) -> Self {
         Repository {
             definitions: db.collection("definitions"),
             audio: db.collection("audio"),
-            voc_definitions: db.collection("vocabulary_definitions"),
-            ox_definitions: db.collection("oxford_definitions"),
+            images: db.collection("images"),
+            archive: Archive::new(&db),
+            lemmatizer: Lemmatizer::new(&db),
+            blobs,
+            transcoder,
         }
     }
 
@@ -46,66 +50,46 @@ impl Repository {
             .map_err(DbErr::QueryErr)
     }
 
-    pub async fn get_voc_definition_id(&self, word: &str) -> Option<ObjectId> {
-        let filter = doc! {"searched_word" : word};
-        self.voc_definitions
-            .clone_with_type::<Id>()
-            .find_one(
-                filter.clone(),
-                FindOneOptions::builder()
-                    .projection(doc! {"_id": 1})
-                    .build(),
-            )
-            .await
-            .ok()
-            .flatten()
-            .map(|val| val.id)
-    }
+    pub async fn delete_definition(&self, word: &str) -> Result<(), DbErr> {
+        let filter = doc! {"word" : word};
 
-    pub async fn get_ox_definition_id(&self, word: &str) -> Option<ObjectId> {
-        let filter = doc! {"searched_word" : word};
-        self.ox_definitions
-            .clone_with_type::<Id>()
-            .find_one(
-                filter.clone(),
-                FindOneOptions::builder()
-                    .projection(doc! {"_id": 1})
-                    .build(),
-            )
+        self.definitions
+            .delete_one(filter, None)
             .await
-            .ok()
-            .flatten()
-            .map(|val| val.id)
-    }
-
-    pub async fn get_voc_definition(&self, word: &str) -> Option<VocDefinitionDoc> {
-        let filter = doc! {"voc_ref" : word};
+            .map_err(DbErr::QueryErr)?;
 
-        self.voc_definitions
-            .find_one(filter, None)
-            .await
-            .ok()
-            .flatten()
+        Ok(())
     }
 
-    pub async fn get_ox_definition(&self, word: &str) -> Option<OxDefinitionDoc> {
-        let filter = doc! {"searched_word" : word};
+    /// points the definition of a word to a newly stored source doc
+    pub async fn set_source(&self, word: &str, source: &str, id: &ObjectId) -> Result<(), DbErr> {
+        let filter = doc! {"word" : word};
+        let update = doc! {
+            "$set": {format!("sources.{}", source): id},
+            "$unset": {format!("misses.{}", source): ""},
+        };
 
-        self.ox_definitions
-            .find_one(filter, None)
+        self.definitions
+            .update_one(
+                filter,
+                update,
+                UpdateOptions::builder().upsert(true).build(),
+            )
             .await
-            .ok()
-            .flatten()
+            .map_err(DbErr::QueryErr)?;
+        Ok(())
     }
 
-    pub async fn delete_definition(&self, word: &str) -> Result<(), DbErr> {
-        let filter = doc! {"word" : word};
-
+    /// drops the id of the source doc, so the word is looked up in the source again
+    pub async fn unset_source(&self, word: &str, source: &str) -> Result<(), DbErr> {
         self.definitions
-            .delete_one(filter, None)
+            .update_one(
+                doc! {"word": word},
+                doc! {"$unset": {format!("sources.{}", source): ""}},
+                None,
+            )
             .await
             .map_err(DbErr::QueryErr)?;
-
         Ok(())
     }
 
@@ -122,141 +106,168 @@ impl Repository {
         Ok(())
     }
 
-    pub async fn save_voc_definition(
-        &self,
-        def: VocScrapedDefinition,
-        searched_word: String,
-    ) -> Result<Option<ObjectId>, DbErr> {
-        let pros = self.save_audio(&def.voc_ref, def.pronunciations).await;
-        let def = VocDefinitionDoc {
-            id: None,
-            voc_ref: def.voc_ref,
-            searched_word,
-            header: def.header,
-            pronunciations: pros,
-            other_forms: def.other_forms,
-            short_description: def.short_description,
-            long_description: def.long_description,
-            definitions: def.definitions,
-            examples: def.examples,
-        };
+    pub async fn save_audio(&self, word: &str, data: Vec<Pronunciation>) -> Vec<PronunciationDoc> {
+        let data: Vec<_> = data
+            .into_iter()
+            .map(|val| async {
+                let audio_id = match val.audio {
+                    Some(audio) => self.store_audio(word, audio).await.unwrap_or_else(|err| {
+                        println!("Could not store audio of {word}: {:?}", err);
+                        None
+                    }),
+                    None => None,
+                };
 
-        let filter = doc! {"voc_ref" : &def.voc_ref};
-        let mut replace_options = ReplaceOptions::default();
-        // inserts when finds None
-        replace_options.upsert = Some(true);
+                PronunciationDoc {
+                    variant: val.variant,
+                    ipa_str: val.ipa_str,
+                    audio_id,
+                }
+            })
+            .collect();
 
-        self.voc_definitions
-            .replace_one(filter.clone(), def, replace_options)
-            .await
-            .map_err(DbErr::QueryErr)?;
+        futures::future::join_all(data).await
+    }
 
-        let object_id = self
-            .voc_definitions
-            .clone_with_type::<Id>()
-            .find_one(
-                filter.clone(),
-                FindOneOptions::builder()
-                    .projection(doc! {"_id": 1})
-                    .build(),
-            )
-            .await
-            .ok()
-            .flatten()
-            .map(|val| val.id);
+    /// stores the audio unless the same bytes are stored already, returns the id of the stored one
+    async fn store_audio(&self, word: &str, audio: Audio) -> Result<Option<ObjectId>, DbErr> {
+        let hash = blob::key(&audio.bytes);
 
-        Ok(object_id)
-    }
+        if let Some(id) = find_id(&self.audio, doc! {"hash": &hash}).await {
+            return Ok(Some(id));
+        }
 
-    pub async fn save_ox_definition(
-        &self,
-        def: OxScrapedDefinition,
-        searched_word: String,
-    ) -> Result<Option<ObjectId>, DbErr> {
-        let pros = self.save_audio(&def.oxford_ref, def.pronunciations).await;
-        let def = OxDefinitionDoc {
+        self.blobs
+            .put(&hash, &audio.bytes)
+            .await
+            .map_err(DbErr::BlobErr)?;
+
+        let audio = AudioDoc {
             id: None,
-            oxford_ref: def.oxford_ref,
-            header: def.header,
-            searched_word,
-            inflections: def.inflections,
-            note: def.note,
-            word_variant: def.word_variant,
-            similar_results: def.similar_results,
-            pronunciations: pros,
-            definitions: def.definitions,
-            see_also: def.see_also,
-            word_origin: def.word_origin,
-            idioms: def.idioms,
-            phrasal_verbs: def.phrasal_verbs,
-            veb_forms: def.veb_forms,
+            word: word.to_string(),
+            content_type: audio.content_type,
+            variants: self.transcode_audio(&hash, &audio.bytes).await,
+            hash,
+            size: audio.bytes.len() as i64,
         };
 
-        let filter = doc! {"oxford_ref" : &def.oxford_ref};
-        let mut replace_options = ReplaceOptions::default();
-        // inserts when finds None
-        replace_options.upsert = Some(true);
-        self.ox_definitions
-            .replace_one(filter.clone(), def, replace_options)
+        insert_unique(&self.audio, doc! {"hash": &audio.hash}, &audio).await
+    }
+
+    /// stores the audio converted to all canonical formats, skips the ones which fail
+    pub async fn transcode_audio(&self, hash: &str, bytes: &[u8]) -> Vec<AudioVariant> {
+        let variants = Format::ALL.into_iter().map(|format| async move {
+            let transcoded = self
+                .transcoder
+                .transcode(bytes, format)
+                .await
+                .map_err(|err| format!("{:?}", err))?;
+            let variant_hash = blob::key(&transcoded);
+            self.blobs
+                .put(&variant_hash, &transcoded)
+                .await
+                .map_err(|err| format!("{:?}", err))?;
+
+            Ok::<_, String>(AudioVariant {
+                format: format.name().to_string(),
+                content_type: format.content_type().to_string(),
+                hash: variant_hash,
+                size: transcoded.len() as i64,
+            })
+        });
+
+        futures::future::join_all(variants)
+            .await
+            .into_iter()
+            .filter_map(|variant| {
+                variant
+                    .map_err(|err| println!("Could not transcode audio {hash}: {}", err))
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// transcodes the audio stored before transcoding or when it failed, returns the number of updated docs
+    pub async fn transcode_missing_audio(&self) -> Result<u64, DbErr> {
+        let mut cursor = self
+            .audio
+            .find(doc! {"variants.0": {"$exists": false}}, None)
             .await
             .map_err(DbErr::QueryErr)?;
 
-        let object_id = self
-            .ox_definitions
-            .clone_with_type::<Id>()
-            .find_one(
-                filter.clone(),
-                FindOneOptions::builder()
-                    .projection(doc! {"_id": 1})
-                    .build(),
-            )
+        let mut updated = 0;
+        while let Some(audio) = cursor.try_next().await.map_err(DbErr::QueryErr)? {
+            let bytes = match self.get_blob(&audio.hash).await? {
+                Some(bytes) => bytes,
+                None => continue,
+            };
+            let variants = self.transcode_audio(&audio.hash, &bytes).await;
+            if variants.is_empty() {
+                continue;
+            }
+
+            let variants = to_bson(&variants).map_err(DbErr::SerializeBsonErr)?;
+            self.audio
+                .update_one(
+                    doc! {"_id": audio.id},
+                    doc! {"$set": {"variants": variants}},
+                    None,
+                )
+                .await
+                .map_err(DbErr::QueryErr)?;
+            updated += 1;
+        }
+
+        Ok(updated)
+    }
+
+    pub async fn get_audio(&self, id: String) -> Result<Option<AudioDoc>, DbErr> {
+        let object_id = ObjectId::parse_str(id).map_err(DbErr::ParseBsonErr)?;
+        let filter = doc! {"_id": object_id};
+        self.audio
+            .find_one(filter, None)
             .await
-            .ok()
-            .flatten()
-            .map(|val| val.id);
+            .map_err(DbErr::QueryErr)
+    }
 
-        Ok(object_id)
+    pub async fn get_blob(&self, key: &str) -> Result<Option<Vec<u8>>, DbErr> {
+        self.blobs.get(key).await.map_err(DbErr::BlobErr)
     }
 
-    async fn save_audio(&self, word: &str, data: Vec<Pronunciation>) -> Vec<PronunciationDoc> {
-        let data: Vec<_> = data
-            .into_iter()
-            .map(|val| async {
-                let mut result = PronunciationDoc {
-                    variant: val.variant,
-                    ipa_str: val.ipa_str,
-                    audio_id: None,
-                };
+    pub async fn get_blob_range(
+        &self,
+        key: &str,
+        start: u64,
+        length: u64,
+    ) -> Result<Option<Vec<u8>>, DbErr> {
+        self.blobs
+            .get_range(key, start, length)
+            .await
+            .map_err(DbErr::BlobErr)
+    }
 
-                match val.audio {
-                    Some(audio) => {
-                        let audio = AudioDoc {
-                            id: None,
-                            word: word.to_string(),
-                            content_type: audio.content_type,
-                            bytes: audio.bytes,
-                        };
-
-                        let audio_id: Option<ObjectId> =
-                            match self.audio.insert_one(audio, None).await {
-                                Ok(val) => val.inserted_id.as_object_id(),
-                                Err(_) => None,
-                            };
-                        result.audio_id = audio_id;
-                        result
-                    }
-                    None => result,
-                }
-            })
-            .collect();
+    /// stores the image unless the same bytes are stored already, returns the id of the stored one
+    pub async fn save_image(&self, image: Image) -> Result<Option<ObjectId>, DbErr> {
+        let hash = blob::key(&image.bytes);
 
-        futures::future::join_all(data).await
+        if let Some(id) = find_id(&self.images, doc! {"hash": &hash}).await {
+            return Ok(Some(id));
+        }
+
+        let image = ImageDoc {
+            id: None,
+            hash,
+            content_type: image.content_type,
+            bytes: image.bytes,
+        };
+
+        insert_unique(&self.images, doc! {"hash": &image.hash}, &image).await
     }
 
-    pub async fn get_audio(&self, id: String) -> Result<Option<AudioDoc>, DbErr> {
+    pub async fn get_image(&self, id: String) -> Result<Option<ImageDoc>, DbErr> {
         let object_id = ObjectId::parse_str(id).map_err(DbErr::ParseBsonErr)?;
         let filter = doc! {"_id": object_id};
-        self.audio
+        self.images
             .find_one(filter, None)
             .await
             .map_err(DbErr::QueryErr)
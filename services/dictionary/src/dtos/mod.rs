@@ -1,17 +1,20 @@
 use crate::models;
 use crate::models::oxford::{DefinitionDoc as OxfordDefinitionDoc, DefinitionGroup, SubDefinition};
 use crate::models::shared::PronunciationDoc;
+use crate::models::wiktionary::{DefinitionDoc as WiktionaryDefinitionDoc, Entry};
 use crate::models::{
     oxford::WordRef,
     shared::PronunciationVariant,
     vocabulary::{DefinitionDoc as VocDefinitionDoc, WordVariant},
 };
-use rpc::dictionary::GetWordDefinitionsResponse;
+use crate::wordnet::{Sense, Synset};
+use rpc::dictionary::{GetWordDefinitionsResponse, SourceDefinition};
 
 pub struct GetWordDefinitionsResponseBuilder {
     pub word: String,
-    pub vocabulary: Option<VocDefinitionDoc>,
-    pub oxford: Option<OxfordDefinitionDoc>,
+    pub lemma: String,
+    pub definitions: Vec<SourceDefinition>,
+    pub suggestions: Vec<String>,
 }
 
 impl OxfordDefinitionDoc {
@@ -30,6 +33,7 @@ impl OxfordDefinitionDoc {
             see_also: self.see_also.into_iter().map(|x| x.into()).collect(),
             idioms: self.idioms.into_iter().map(|x| x.into()).collect(),
             phrasal_verbs: self.phrasal_verbs.into_iter().map(|x| x.into()).collect(),
+            verb_forms: self.verb_forms.into_iter().map(|x| x.into()).collect(),
         }
     }
 }
@@ -50,6 +54,7 @@ impl VocDefinitionDoc {
                     short_examples: d.short_examples,
                     synonyms: d.synonyms,
                     word_variant: Some(d.variant.into()),
+                    image_id: d.image_id.map(|id| id.to_string()),
                 })
                 .collect(),
             examples: self
@@ -66,24 +71,46 @@ impl VocDefinitionDoc {
     }
 }
 
+impl WiktionaryDefinitionDoc {
+    pub fn into_response(self) -> rpc::dictionary::WiktionaryDefinition {
+        let mut entries: Vec<_> = self.entries.into_iter().collect();
+        // keys are dump offsets, keep the dump order
+        entries.sort_by_key(|(offset, _)| offset.parse::<u64>().unwrap_or_default());
+
+        rpc::dictionary::WiktionaryDefinition {
+            id: self.id.unwrap_or_default().to_string(),
+            word: self.word,
+            entries: entries.into_iter().map(|(_, e)| e.into()).collect(),
+        }
+    }
+}
+
 impl GetWordDefinitionsResponseBuilder {
-    pub fn new(
-        word: &str,
-        voc: Option<VocDefinitionDoc>,
-        oxford: Option<OxfordDefinitionDoc>,
-    ) -> Self {
+    pub fn new(word: &str, definitions: Vec<SourceDefinition>) -> Self {
         Self {
             word: word.to_string(),
-            vocabulary: voc,
-            oxford,
+            lemma: word.to_string(),
+            definitions,
+            suggestions: Vec::new(),
         }
     }
 
+    pub fn lemma(mut self, lemma: &str) -> Self {
+        self.lemma = lemma.to_string();
+        self
+    }
+
+    pub fn suggestions(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
     pub fn build(self) -> GetWordDefinitionsResponse {
         GetWordDefinitionsResponse {
-            word: self.word.clone(),
-            vocabulary_definition: self.vocabulary.map(|v| v.into_response()),
-            oxford_definition: self.oxford.map(|v| v.into_response()),
+            word: self.word,
+            definitions: self.definitions,
+            suggestions: self.suggestions,
+            lemma: self.lemma,
         }
     }
 }
@@ -153,6 +180,17 @@ impl From<models::oxford::Idiom> for rpc::dictionary::Idiom {
     }
 }
 
+impl From<models::oxford::VerbForm> for rpc::dictionary::VerbForm {
+    fn from(value: models::oxford::VerbForm) -> Self {
+        rpc::dictionary::VerbForm {
+            form: value.form,
+            note: value.note,
+            word: value.word,
+            pronunciations: value.pronunciations.into_iter().map(|x| x.into()).collect(),
+        }
+    }
+}
+
 impl From<PronunciationDoc> for rpc::dictionary::Pronunciation {
     fn from(p: PronunciationDoc) -> Self {
         rpc::dictionary::Pronunciation {
@@ -173,3 +211,71 @@ impl From<PronunciationVariant> for rpc::dictionary::pronunciation::Pronunciatio
         }
     }
 }
+
+impl From<Entry> for rpc::dictionary::WiktionaryEntry {
+    fn from(value: Entry) -> Self {
+        rpc::dictionary::WiktionaryEntry {
+            pos: value.pos,
+            etymology: value.etymology,
+            senses: value
+                .senses
+                .into_iter()
+                .map(|s| rpc::dictionary::WiktionarySense {
+                    glosses: s.glosses,
+                    examples: s.examples,
+                    tags: s.tags,
+                })
+                .collect(),
+            sounds: value
+                .sounds
+                .into_iter()
+                .map(|s| rpc::dictionary::WiktionarySound {
+                    ipa: s.ipa,
+                    tags: s.tags,
+                })
+                .collect(),
+            forms: value
+                .forms
+                .into_iter()
+                .map(|f| rpc::dictionary::WiktionaryForm {
+                    form: f.form,
+                    tags: f.tags,
+                })
+                .collect(),
+            translations: value
+                .translations
+                .into_iter()
+                .map(|t| rpc::dictionary::WiktionaryTranslation {
+                    lang: t.lang,
+                    code: t.code,
+                    word: t.word,
+                    sense: t.sense,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&Synset> for rpc::dictionary::Synset {
+    fn from(value: &Synset) -> Self {
+        rpc::dictionary::Synset {
+            id: value.id.to_key(),
+            pos: value.id.pos.as_str().to_string(),
+            words: value.words.clone(),
+            gloss: value.gloss.clone(),
+        }
+    }
+}
+
+impl From<Sense<'_>> for rpc::dictionary::WordSense {
+    fn from(value: Sense) -> Self {
+        rpc::dictionary::WordSense {
+            synset: Some(value.synset.into()),
+            antonyms: value.antonyms,
+            hypernyms: value.hypernyms.into_iter().map(|x| x.into()).collect(),
+            hyponyms: value.hyponyms.into_iter().map(|x| x.into()).collect(),
+            meronyms: value.meronyms.into_iter().map(|x| x.into()).collect(),
+            holonyms: value.holonyms.into_iter().map(|x| x.into()).collect(),
+        }
+    }
+}
@@ -1,14 +1,107 @@
 use common_rs::{env, EnvStore};
+use std::collections::HashMap;
+use std::str::FromStr;
 
 #[derive(Debug, Clone)]
 pub struct Env {
     pub db_connection_uri: String,
+    /// comma separated list of enabled dictionary sources, e.g. "oxford,vocabulary".
+    /// All known sources are enabled when not set
+    pub dictionary_sources: Option<Vec<String>>,
+    /// directory with the WordNet database files (index.noun, data.noun, ...)
+    pub wordnet_dir: Option<String>,
+    /// seconds after which a source that could not provide a word is asked again, one day by default
+    pub negative_cache_ttl_secs: i64,
+    /// seconds after which a scraped definition is refreshed in the background, 30 days by default
+    pub definition_max_age_secs: i64,
+    /// where the audio bytes are stored: "gridfs" (default), "fs" or "s3"
+    pub blob_store: Option<String>,
+    /// directory of the "fs" blob store, `./blobs` by default
+    pub blob_dir: Option<String>,
+    /// S3 compatible storage of the "s3" blob store, e.g. "http://minio:9000"
+    pub s3_endpoint: Option<String>,
+    pub s3_bucket: Option<String>,
+    pub s3_region: Option<String>,
+    pub s3_access_key: Option<String>,
+    pub s3_secret_key: Option<String>,
+    /// ffmpeg binary used to transcode the audio, "ffmpeg" from the path by default
+    pub ffmpeg_path: String,
+    /// words of a batch looked up at the same time, 4 by default
+    pub batch_concurrency: usize,
+    /// workers processing the scrape job queue, 2 by default
+    pub job_workers: usize,
+    /// requests per second sent to a scraped host, 1 by default
+    pub http_rate_per_sec: f64,
+    /// requests a host may get at once after a quiet period, 3 by default
+    pub http_burst: f64,
+    /// rates of hosts which differ from `http_rate_per_sec`, e.g. "www.vocabulary.com=0.5,audio.oxforddictionaries.com=5"
+    pub http_host_rates: HashMap<String, f64>,
+    /// requests in flight to all hosts together, 8 by default
+    pub http_max_concurrency: usize,
+    pub http_timeout_secs: u64,
+    /// "|" separated user agents rotated by the scrapers, "dictionary-ms/<version>" when not set
+    pub user_agents: Option<Vec<String>>,
+    /// name the robots.txt groups are matched against, only the "*" group applies when not set
+    pub robots_user_agent: Option<String>,
+    /// share of the expected sections a scraped page must have to be stored, 0.5 by default
+    pub min_completeness: f64,
 }
 
 impl EnvStore for Env {
     fn new() -> Self {
         Env {
             db_connection_uri: env::required("DB_CONNECTION_URI"),
+            dictionary_sources: env::optional("DICTIONARY_SOURCES").map(|val| {
+                val.split(',')
+                    .map(|x| x.trim().to_string())
+                    .filter(|x| !x.is_empty())
+                    .collect()
+            }),
+            wordnet_dir: env::optional("WORDNET_DIR"),
+            negative_cache_ttl_secs: number("NEGATIVE_CACHE_TTL_SECS", 24 * 60 * 60),
+            definition_max_age_secs: number("DEFINITION_MAX_AGE_SECS", 30 * 24 * 60 * 60),
+            blob_store: env::optional("BLOB_STORE"),
+            blob_dir: env::optional("BLOB_DIR"),
+            s3_endpoint: env::optional("S3_ENDPOINT"),
+            s3_bucket: env::optional("S3_BUCKET"),
+            s3_region: env::optional("S3_REGION"),
+            s3_access_key: env::optional("S3_ACCESS_KEY"),
+            s3_secret_key: env::optional("S3_SECRET_KEY"),
+            ffmpeg_path: env::optional("FFMPEG_PATH").unwrap_or_else(|| "ffmpeg".to_string()),
+            batch_concurrency: number("BATCH_CONCURRENCY", 4),
+            job_workers: number("JOB_WORKERS", 2),
+            http_rate_per_sec: number("HTTP_RATE_PER_SEC", 1.0),
+            http_burst: number("HTTP_BURST", 3.0),
+            http_host_rates: env::optional("HTTP_HOST_RATES")
+                .map(|val| {
+                    val.split(',')
+                        .filter_map(|x| x.split_once('='))
+                        .map(|(host, rate)| {
+                            let rate = rate.trim().parse().unwrap_or_else(|_| {
+                                panic!("HTTP_HOST_RATES has an invalid rate: {}", rate)
+                            });
+                            (host.trim().to_string(), rate)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            http_max_concurrency: number("HTTP_MAX_CONCURRENCY", 8),
+            http_timeout_secs: number("HTTP_TIMEOUT_SECS", 30),
+            user_agents: env::optional("USER_AGENTS").map(|val| {
+                val.split('|')
+                    .map(|x| x.trim().to_string())
+                    .filter(|x| !x.is_empty())
+                    .collect()
+            }),
+            robots_user_agent: env::optional("ROBOTS_USER_AGENT"),
+            min_completeness: number("MIN_COMPLETENESS", 0.5),
         }
     }
 }
+
+fn number<T: FromStr>(env_var: &str, default: T) -> T {
+    env::optional(env_var).map_or(default, |val| {
+        val.parse()
+            .unwrap_or_else(|_| panic!("{} is not a number: {}", env_var, val))
+    })
+}
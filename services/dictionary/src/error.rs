@@ -0,0 +1,180 @@
+use crate::db::database::DbErr;
+use rpc::dictionary::WordNotFound;
+use rpc::status;
+use std::collections::HashMap;
+use std::time::Duration;
+use tonic::{Code, Status};
+
+/// `ErrorInfo` domain of the errors of this service
+pub const DOMAIN: &str = "dictionary";
+
+/// Error returned to the clients, mapped to its gRPC code with a `google.rpc.ErrorInfo`
+/// carrying the reason and the fields in the metadata, see `DictionaryErr::reason`
+#[derive(Debug, thiserror::Error)]
+pub enum DictionaryErr {
+    #[error("{kind} {id} not found")]
+    NotFound { kind: &'static str, id: String },
+    /// every source says the word does not exist, the details carry the suggestions
+    #[error("word {} not found", .0.word)]
+    WordNotFound(WordNotFound),
+    #[error("invalid {field}: {reason}")]
+    InvalidArgument { field: &'static str, reason: String },
+    #[error("range not satisfiable, the size is {size}")]
+    RangeNotSatisfiable { size: u64 },
+    #[error("WordNet is not loaded")]
+    WordNetNotLoaded,
+    /// looking the word up in the source failed, e.g. it is rate limiting,
+    /// `retry_after` is sent as a `google.rpc.RetryInfo`
+    #[error("source {name} failed: {reason}")]
+    SourceFailed {
+        name: String,
+        reason: String,
+        code: Code,
+        retry_after: Option<Duration>,
+    },
+    #[error("database is unavailable: {0}")]
+    DbUnavailable(DbErr),
+    #[error("database timed out: {0}")]
+    DbTimeout(DbErr),
+    #[error("{0}")]
+    Internal(DbErr),
+}
+
+impl DictionaryErr {
+    pub fn code(&self) -> Code {
+        match self {
+            DictionaryErr::NotFound { .. } | DictionaryErr::WordNotFound(_) => Code::NotFound,
+            DictionaryErr::InvalidArgument { .. } => Code::InvalidArgument,
+            DictionaryErr::RangeNotSatisfiable { .. } => Code::OutOfRange,
+            DictionaryErr::WordNetNotLoaded | DictionaryErr::DbUnavailable(_) => Code::Unavailable,
+            DictionaryErr::SourceFailed { code, .. } => *code,
+            DictionaryErr::DbTimeout(_) => Code::DeadlineExceeded,
+            DictionaryErr::Internal(_) => Code::Internal,
+        }
+    }
+
+    /// stable UPPER_SNAKE_CASE identifier of the error for the clients to match on
+    pub fn reason(&self) -> &'static str {
+        match self {
+            DictionaryErr::NotFound { .. } => "NOT_FOUND",
+            DictionaryErr::WordNotFound(_) => "WORD_NOT_FOUND",
+            DictionaryErr::InvalidArgument { .. } => "INVALID_ARGUMENT",
+            DictionaryErr::RangeNotSatisfiable { .. } => "RANGE_NOT_SATISFIABLE",
+            DictionaryErr::WordNetNotLoaded => "WORDNET_NOT_LOADED",
+            DictionaryErr::SourceFailed { code, .. } => match code {
+                Code::ResourceExhausted => "SOURCE_RATE_LIMITED",
+                Code::DeadlineExceeded => "SOURCE_TIMEOUT",
+                Code::Unavailable => "SOURCE_UNAVAILABLE",
+                _ => "SOURCE_FAILED",
+            },
+            DictionaryErr::DbUnavailable(_) => "DB_UNAVAILABLE",
+            DictionaryErr::DbTimeout(_) => "DB_TIMEOUT",
+            DictionaryErr::Internal(_) => "INTERNAL",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        let pairs: Vec<(&str, String)> = match self {
+            DictionaryErr::NotFound { kind, id } => {
+                vec![("kind", kind.to_string()), ("id", id.clone())]
+            }
+            DictionaryErr::WordNotFound(details) => vec![("word", details.word.clone())],
+            DictionaryErr::InvalidArgument { field, .. } => vec![("field", field.to_string())],
+            DictionaryErr::RangeNotSatisfiable { size } => vec![("size", size.to_string())],
+            DictionaryErr::SourceFailed { name, .. } => vec![("source", name.clone())],
+            _ => Vec::new(),
+        };
+        pairs
+            .into_iter()
+            .map(|(key, val)| (key.to_string(), val))
+            .collect()
+    }
+}
+
+impl From<DbErr> for DictionaryErr {
+    fn from(err: DbErr) -> Self {
+        match &err {
+            DbErr::ParseBsonErr(parse_err) => DictionaryErr::InvalidArgument {
+                field: "id",
+                reason: parse_err.to_string(),
+            },
+            _ if err.is_timeout() => DictionaryErr::DbTimeout(err),
+            _ if err.is_unreachable() => DictionaryErr::DbUnavailable(err),
+            _ => DictionaryErr::Internal(err),
+        }
+    }
+}
+
+impl From<DictionaryErr> for Status {
+    fn from(err: DictionaryErr) -> Self {
+        if matches!(err.code(), Code::Internal | Code::Unavailable) {
+            println!("{:?}", err);
+        }
+
+        let details = match &err {
+            DictionaryErr::WordNotFound(details) => {
+                vec![status::pack("dictionary.WordNotFound", details)]
+            }
+            DictionaryErr::SourceFailed {
+                retry_after: Some(delay),
+                ..
+            } => vec![status::retry_info(*delay)],
+            _ => Vec::new(),
+        };
+        status::with_error_info(
+            err.code(),
+            err.to_string(),
+            DOMAIN,
+            err.reason(),
+            err.metadata(),
+            details,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_ok() {
+        let err = DictionaryErr::WordNotFound(WordNotFound {
+            word: "catt".to_string(),
+            suggestions: vec!["cat".to_string()],
+            lemma: "catt".to_string(),
+        });
+
+        let status = Status::from(err);
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message(), "word catt not found");
+
+        let info = status::error_info(&status).unwrap();
+        assert_eq!(info.reason, "WORD_NOT_FOUND");
+        assert_eq!(info.domain, DOMAIN);
+        assert_eq!(info.metadata.get("word"), Some(&"catt".to_string()));
+
+        let details: WordNotFound = status::detail(&status, "dictionary.WordNotFound").unwrap();
+        assert_eq!(details.suggestions, vec!["cat".to_string()]);
+    }
+
+    #[test]
+    fn source_failed_code_ok() {
+        let err = DictionaryErr::SourceFailed {
+            name: "oxford".to_string(),
+            reason: "429".to_string(),
+            code: Code::ResourceExhausted,
+            retry_after: Some(Duration::from_secs(30)),
+        };
+
+        assert_eq!(err.code(), Code::ResourceExhausted);
+        assert_eq!(err.reason(), "SOURCE_RATE_LIMITED");
+        assert_eq!(
+            status::retry_delay(&Status::from(err)),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            DictionaryErr::from(DbErr::MissingDoc("job")).code(),
+            Code::Internal
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use crate::http::{self, HttpErr, HttpResponse};
+use chrono::{DateTime, Utc};
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tonic::Code;
+
+/// markers of the bot check pages served instead of the content, e.g. by Cloudflare
+const CHALLENGE_MARKERS: [&str; 5] = [
+    r#"id="challenge-form""#,
+    "cf-browser-verification",
+    "cf_chl_opt",
+    "/cdn-cgi/challenge-platform/",
+    "<title>Just a moment...</title>",
+];
+
+#[derive(Debug)]
+pub enum FetchErr {
+    HttpErr(HttpErr),
+    /// still challenged after the last attempt
+    Challenged,
+    /// still rate limited after the last attempt, with the last `Retry-After`
+    RateLimited(Option<Duration>),
+}
+
+impl FetchErr {
+    /// gRPC code of a lookup failing on it
+    pub fn code(&self) -> Code {
+        match self {
+            FetchErr::HttpErr(HttpErr::RequestErr(err)) if err.is_timeout() => {
+                Code::DeadlineExceeded
+            }
+            FetchErr::HttpErr(HttpErr::RequestErr(_)) | FetchErr::Challenged => Code::Unavailable,
+            FetchErr::HttpErr(HttpErr::Disallowed(_)) => Code::PermissionDenied,
+            FetchErr::RateLimited(_) => Code::ResourceExhausted,
+            FetchErr::HttpErr(_) => Code::Internal,
+        }
+    }
+
+    /// how long the source asked to wait, sent to the clients as a `RetryInfo`
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            FetchErr::RateLimited(retry_after) => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// How persistently a source is fetched
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// attempts including the first one
+    pub max_attempts: u32,
+    /// delay before the first retry, doubled with every next one
+    pub base_delay: Duration,
+    /// longer backoff or `Retry-After` means giving up
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// audio and images, a missing one does not fail the definition
+    pub const ASSETS: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(500),
+        max_delay: Duration::from_secs(10),
+    };
+}
+
+/// What a response turned out to be
+#[derive(Debug, PartialEq)]
+enum Class {
+    /// anything to hand to the caller, including 404 and other client errors
+    Done,
+    Challenge,
+    /// 429 with its `Retry-After`
+    RateLimited(Option<Duration>),
+    /// 5xx with its `Retry-After`
+    ServerErr(Option<Duration>),
+    /// timeout, connection reset, ...
+    NetworkErr,
+    /// e.g. disallowed by robots.txt, retrying does not help
+    Fatal,
+}
+
+#[derive(Debug, PartialEq)]
+enum Decision {
+    Accept,
+    Retry(Duration),
+    GiveUp,
+}
+
+/// GETs with retries of the transient failures, see `RetryPolicy`
+#[derive(Debug, Clone, Copy)]
+pub struct Fetcher {
+    policy: RetryPolicy,
+}
+
+impl Fetcher {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Fetcher { policy }
+    }
+
+    /// response of the last attempt when the server keeps failing, so the caller sees its status,
+    /// `FetchErr::RateLimited` when it keeps rate limiting
+    pub async fn get(&self, url: &str) -> Result<HttpResponse, FetchErr> {
+        let mut attempt = 1;
+        loop {
+            let res = http::client().get(url).await;
+            let class = classify(&res, SystemTime::now());
+
+            match decide(&self.policy, attempt, &class, jitter()) {
+                Decision::Retry(delay) => {
+                    println!("Retrying {url} in {:?} ({:?})", delay, class);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Decision::Accept | Decision::GiveUp => {
+                    return match (class, res) {
+                        (Class::Challenge, _) => Err(FetchErr::Challenged),
+                        (Class::RateLimited(retry_after), _) => {
+                            Err(FetchErr::RateLimited(retry_after))
+                        }
+                        (_, res) => res.map_err(FetchErr::HttpErr),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn classify(res: &Result<HttpResponse, HttpErr>, now: SystemTime) -> Class {
+    let res = match res {
+        Ok(res) => res,
+        Err(HttpErr::RequestErr(_)) => return Class::NetworkErr,
+        Err(_) => return Class::Fatal,
+    };
+
+    if is_challenge(res) {
+        return Class::Challenge;
+    }
+
+    let retry_after = res
+        .headers
+        .get(RETRY_AFTER)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| parse_retry_after(val, now));
+    match res.status {
+        StatusCode::TOO_MANY_REQUESTS => Class::RateLimited(retry_after),
+        status if status.is_server_error() => Class::ServerErr(retry_after),
+        _ => Class::Done,
+    }
+}
+
+fn is_challenge(res: &HttpResponse) -> bool {
+    if res
+        .headers
+        .get("cf-mitigated")
+        .is_some_and(|val| val == "challenge")
+    {
+        return true;
+    }
+
+    let is_html = res
+        .content_type()
+        .is_some_and(|val| val.starts_with("text/html"));
+    if !is_html {
+        return false;
+    }
+
+    let html = String::from_utf8_lossy(&res.bytes);
+    CHALLENGE_MARKERS.iter().any(|marker| html.contains(marker))
+}
+
+/// `Retry-After` is either seconds or an HTTP date
+fn parse_retry_after(val: &str, now: SystemTime) -> Option<Duration> {
+    if let Ok(secs) = val.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let at = DateTime::parse_from_rfc2822(val.trim()).ok()?;
+    let now: DateTime<Utc> = now.into();
+    Some(
+        (at.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// `jitter` in `[0, 1)` spreads the retries of parallel requests
+fn decide(policy: &RetryPolicy, attempt: u32, class: &Class, jitter: f64) -> Decision {
+    let retry_after = match class {
+        Class::Done => return Decision::Accept,
+        Class::Fatal => return Decision::GiveUp,
+        Class::RateLimited(retry_after) | Class::ServerErr(retry_after) => *retry_after,
+        Class::Challenge | Class::NetworkErr => None,
+    };
+    if attempt >= policy.max_attempts {
+        return Decision::GiveUp;
+    }
+
+    let backoff = policy
+        .base_delay
+        .saturating_mul(1 << (attempt - 1).min(16))
+        .min(policy.max_delay);
+    let backoff = backoff.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0);
+
+    match retry_after {
+        Some(delay) if delay > policy.max_delay => Decision::GiveUp,
+        Some(delay) => Decision::Retry(delay.max(backoff)),
+        None => Decision::Retry(backoff),
+    }
+}
+
+fn jitter() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    (nanos % 1000) as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_attempts: 4,
+        base_delay: Duration::from_secs(1),
+        max_delay: Duration::from_secs(5),
+    };
+
+    fn response(status: u16, headers: &[(&'static str, &'static str)], body: &str) -> HttpResponse {
+        let mut map = HeaderMap::new();
+        for (name, val) in headers {
+            map.insert(*name, HeaderValue::from_static(val));
+        }
+        HttpResponse {
+            status: StatusCode::from_u16(status).unwrap(),
+            url: "https://example.com".to_string(),
+            headers: map,
+            bytes: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn class(res: HttpResponse) -> Class {
+        classify(&Ok(res), UNIX_EPOCH + Duration::from_secs(784111777))
+    }
+
+    #[test]
+    fn classify_ok() {
+        let html = [(CONTENT_TYPE.as_str(), "text/html; charset=utf-8")];
+
+        assert_eq!(class(response(200, &html, "<h1>cat</h1>")), Class::Done);
+        assert_eq!(class(response(404, &html, "not found")), Class::Done);
+        assert_eq!(
+            class(response(
+                200,
+                &html,
+                r#"<form id="challenge-form" action="/x">"#
+            )),
+            Class::Challenge
+        );
+        assert_eq!(
+            class(response(503, &html, "<title>Just a moment...</title>")),
+            Class::Challenge
+        );
+        assert_eq!(
+            class(response(403, &[("cf-mitigated", "challenge")], "")),
+            Class::Challenge
+        );
+        // markers are looked for in html only
+        assert_eq!(
+            class(response(
+                200,
+                &[(CONTENT_TYPE.as_str(), "audio/mpeg")],
+                "cf_chl_opt"
+            )),
+            Class::Done
+        );
+        assert_eq!(
+            class(response(429, &[("retry-after", "3")], "")),
+            Class::RateLimited(Some(Duration::from_secs(3)))
+        );
+        assert_eq!(
+            class(response(
+                503,
+                &[("retry-after", "Sun, 06 Nov 1994 08:49:47 GMT")],
+                ""
+            )),
+            Class::ServerErr(Some(Duration::from_secs(10)))
+        );
+        assert_eq!(class(response(500, &[], "")), Class::ServerErr(None));
+        assert_eq!(
+            classify(
+                &Err(HttpErr::Disallowed("https://example.com".to_string())),
+                SystemTime::now()
+            ),
+            Class::Fatal
+        );
+    }
+
+    #[test]
+    fn decide_backoff() {
+        assert_eq!(
+            decide(&POLICY, 1, &Class::NetworkErr, 0.0),
+            Decision::Retry(Duration::from_millis(500))
+        );
+        assert_eq!(
+            decide(&POLICY, 2, &Class::Challenge, 0.999999),
+            Decision::Retry(Duration::from_secs(2).mul_f64(0.5 + 0.999999 / 2.0))
+        );
+        assert_eq!(
+            decide(&POLICY, 3, &Class::ServerErr(None), 1.0),
+            Decision::Retry(Duration::from_secs(4))
+        );
+        assert_eq!(
+            decide(&POLICY, 4, &Class::ServerErr(None), 0.0),
+            Decision::GiveUp
+        );
+    }
+
+    #[test]
+    fn decide_retry_after() {
+        assert_eq!(
+            decide(
+                &POLICY,
+                1,
+                &Class::RateLimited(Some(Duration::from_secs(3))),
+                0.0
+            ),
+            Decision::Retry(Duration::from_secs(3))
+        );
+        // backoff is longer
+        assert_eq!(
+            decide(&POLICY, 3, &Class::RateLimited(Some(Duration::ZERO)), 1.0),
+            Decision::Retry(Duration::from_secs(4))
+        );
+        assert_eq!(
+            decide(
+                &POLICY,
+                1,
+                &Class::RateLimited(Some(Duration::from_secs(60))),
+                0.0
+            ),
+            Decision::GiveUp
+        );
+    }
+
+    #[test]
+    fn decide_final() {
+        assert_eq!(decide(&POLICY, 1, &Class::Done, 0.0), Decision::Accept);
+        assert_eq!(decide(&POLICY, 1, &Class::Fatal, 0.0), Decision::GiveUp);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::completeness::Completeness;
+use crate::http::cassette::{self, Cassette, Mode};
+use crate::http::{HttpErr, HttpResponse};
+use crate::{oxford, vocabulary};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum FixtureErr {
+    #[error("could not read the cassette: {0:?}")]
+    HttpErr(HttpErr),
+    #[error("could not write the snapshot: {0}")]
+    IoErr(#[source] std::io::Error),
+    /// a snapshot is not valid json
+    #[error("invalid snapshot: {0}")]
+    InvalidSnapshot(#[source] serde_json::Error),
+}
+
+/// Result of re-parsing the recorded definition pages
+#[derive(Debug, Default)]
+pub struct FixtureReport {
+    pub checked: usize,
+    /// url and the differences from its snapshot
+    pub changed: Vec<(String, Vec<String>)>,
+    /// url and its empty sections
+    pub incomplete: Vec<(String, Vec<&'static str>)>,
+    /// snapshots written, new or updated
+    pub written: usize,
+}
+
+impl FixtureReport {
+    /// `true` when the parsers still produce what they did when the snapshots were taken
+    pub fn is_ok(&self) -> bool {
+        self.changed.is_empty() && self.incomplete.is_empty()
+    }
+}
+
+/// Parses the definition pages recorded in `cassette_dir` and diffs the output
+/// against `snapshot_dir/<recording>.json`, so a changed markup shows up before the scraped docs do.
+/// `update` writes the snapshots instead of reporting the differences.
+pub async fn check(
+    cassette_dir: &str,
+    snapshot_dir: &str,
+    update: bool,
+) -> Result<FixtureReport, FixtureErr> {
+    let cassette = Cassette::new(cassette_dir, Mode::Replay);
+    let mut report = FixtureReport::default();
+
+    for url in cassette.urls().await.map_err(FixtureErr::HttpErr)? {
+        let res = cassette.load(&url).await.map_err(FixtureErr::HttpErr)?;
+        let (parsed, completeness) = match parse(&url, &res) {
+            Some(parsed) => parsed,
+            None => continue,
+        };
+        report.checked += 1;
+
+        if !completeness.empty.is_empty() {
+            report
+                .incomplete
+                .push((url.clone(), completeness.empty.clone()));
+        }
+        let parsed = json!({ "parsed": parsed, "completeness": completeness });
+
+        let path = snapshot_path(snapshot_dir, &url);
+        let snapshot = match tokio::fs::read(&path).await {
+            Ok(bytes) => Some(serde_json::from_slice(&bytes).map_err(FixtureErr::InvalidSnapshot)?),
+            Err(_) => None,
+        };
+
+        let changes = match &snapshot {
+            Some(snapshot) => diff(snapshot, &parsed),
+            None => vec!["+ no snapshot".to_string()],
+        };
+        if changes.is_empty() {
+            continue;
+        }
+
+        if update {
+            write_snapshot(&path, &parsed).await?;
+            report.written += 1;
+        } else {
+            report.changed.push((url, changes));
+        }
+    }
+
+    Ok(report)
+}
+
+fn snapshot_path(snapshot_dir: &str, url: &str) -> PathBuf {
+    Path::new(snapshot_dir).join(format!("{}.json", cassette::name(url)))
+}
+
+async fn write_snapshot(path: &Path, parsed: &Value) -> Result<(), FixtureErr> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(FixtureErr::IoErr)?;
+    }
+    let mut json = serde_json::to_vec_pretty(parsed).map_err(FixtureErr::InvalidSnapshot)?;
+    json.push(b'\n');
+    tokio::fs::write(path, json)
+        .await
+        .map_err(FixtureErr::IoErr)
+}
+
+/// output of the parser of the page, `None` when the url is not a definition page, e.g. audio
+fn parse(url: &str, res: &HttpResponse) -> Option<(Value, Completeness)> {
+    let word = url.rsplit('/').next().unwrap_or_default();
+
+    if url.starts_with(oxford::DEFINITION_BASE_URL) {
+        let parsed = match res.status {
+            StatusCode::NOT_FOUND => {
+                let not_found = oxford::parse_not_found_page(&res.text());
+                let words: Vec<_> = not_found.similar_words.iter().map(|x| &x.word).collect();
+                return Some((json!({ "not_found": words }), Completeness::default()));
+            }
+            _ => oxford::scrape_html(&res.text()),
+        };
+        let completeness = parsed.completeness();
+        return Some((serde_json::to_value(parsed).ok()?, completeness));
+    }
+
+    if url.starts_with(vocabulary::DEFINITION_BASE_URL) {
+        return match vocabulary::scrape_html(&res.text(), &res.url, word) {
+            Ok(parsed) => {
+                let completeness = parsed.completeness();
+                Some((serde_json::to_value(parsed).ok()?, completeness))
+            }
+            Err(vocabulary::ScrapeErr::NotFound(not_found)) => Some((
+                json!({ "not_found": not_found.suggestions }),
+                Completeness::default(),
+            )),
+            Err(_) => None,
+        };
+    }
+
+    None
+}
+
+/// one line per differing value: `~ path: old -> new`, `- path` for removed and `+ path` for added
+fn diff(old: &Value, new: &Value) -> Vec<String> {
+    let mut changes = Vec::new();
+    diff_at("", old, new, &mut changes);
+    changes
+}
+
+fn diff_at(path: &str, old: &Value, new: &Value, changes: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_val) in old {
+                let key_path = format!("{path}.{key}");
+                match new.get(key) {
+                    Some(new_val) => diff_at(&key_path, old_val, new_val, changes),
+                    None => changes.push(format!("- {key_path}")),
+                }
+            }
+            for key in new.keys().filter(|key| !old.contains_key(*key)) {
+                changes.push(format!("+ {path}.{key}"));
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for (i, (old_val, new_val)) in old.iter().zip(new).enumerate() {
+                diff_at(&format!("{path}[{i}]"), old_val, new_val, changes);
+            }
+            for i in new.len()..old.len() {
+                changes.push(format!("- {path}[{i}]"));
+            }
+            for i in old.len()..new.len() {
+                changes.push(format!("+ {path}[{i}]"));
+            }
+        }
+        (old, new) if old != new => changes.push(format!("~ {path}: {old} -> {new}")),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_ok() {
+        let old = json!({"header": "cat", "defs": ["a", "b"], "note": "x"});
+        let new = json!({"header": "dog", "defs": ["a"], "origin": "y"});
+
+        assert!(diff(&old, &old).is_empty());
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                "~ .header: \"cat\" -> \"dog\"".to_string(),
+                "- .defs[1]".to_string(),
+                "- .note".to_string(),
+                "+ .origin".to_string(),
+            ]
+        );
+    }
+
+    /// set `UPDATE_SNAPSHOTS=1` to accept the current output after a parser change
+    #[tokio::test]
+    async fn fixtures_match_snapshots() {
+        let update = std::env::var("UPDATE_SNAPSHOTS").is_ok();
+        let report = check("./src/tests/cassettes", "./src/tests/snapshots", update)
+            .await
+            .unwrap();
+
+        assert!(report.checked >= 3);
+        assert!(report.changed.is_empty(), "{:#?}", report.changed);
+    }
+}
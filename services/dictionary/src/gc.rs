@@ -0,0 +1,215 @@
+use crate::db::database::DbErr;
+use crate::db::repository::Repository;
+use crate::sources::Sources;
+use chrono::{DateTime, Duration, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// audio younger than this is kept, the definition referring to it may be just being stored
+pub const GRACE_PERIOD_MINUTES: i64 = 60;
+const DELETE_BATCH_SIZE: usize = 1000;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct GcReport {
+    pub audio_removed: u64,
+    pub blobs_removed: u64,
+    pub bytes_reclaimed: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AudioRef {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    /// `None` for audio not moved to the blob store yet
+    #[serde(default)]
+    hash: Option<String>,
+    #[serde(default)]
+    size: i64,
+    #[serde(default)]
+    variants: Vec<BlobRef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlobRef {
+    hash: String,
+    size: i64,
+}
+
+impl AudioRef {
+    fn blobs(&self) -> impl Iterator<Item = (&String, i64)> {
+        self.hash
+            .iter()
+            .map(|hash| (hash, self.size))
+            .chain(self.variants.iter().map(|v| (&v.hash, v.size)))
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Plan {
+    audio: Vec<ObjectId>,
+    /// hashes and sizes of the blobs only the removed audio refers to
+    blobs: Vec<(String, i64)>,
+}
+
+/// removes the audio no stored definition refers to anymore, e.g. after a re-scrape,
+/// together with the blobs no other audio shares
+pub async fn collect_audio(repository: &Repository, sources: &Sources) -> Result<GcReport, DbErr> {
+    let plan = load_plan(repository, sources).await?;
+
+    for ids in plan.audio.chunks(DELETE_BATCH_SIZE) {
+        repository
+            .audio
+            .delete_many(doc! {"_id": {"$in": ids}}, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+    }
+
+    let mut report = GcReport {
+        audio_removed: plan.audio.len() as u64,
+        ..Default::default()
+    };
+    for (hash, size) in plan.blobs {
+        repository
+            .blobs
+            .delete(&hash)
+            .await
+            .map_err(DbErr::BlobErr)?;
+        report.blobs_removed += 1;
+        report.bytes_reclaimed += size as u64;
+    }
+
+    Ok(report)
+}
+
+/// ids of the audio `collect_audio` would remove
+pub async fn orphaned_audio(
+    repository: &Repository,
+    sources: &Sources,
+) -> Result<Vec<ObjectId>, DbErr> {
+    Ok(load_plan(repository, sources).await?.audio)
+}
+
+async fn load_plan(repository: &Repository, sources: &Sources) -> Result<Plan, DbErr> {
+    let mut referenced = HashSet::new();
+    for source in sources.iter() {
+        referenced.extend(source.referenced_audio().await?);
+    }
+
+    let audio: Vec<AudioRef> = repository
+        .audio
+        .clone_with_type::<AudioRef>()
+        .find(
+            doc! {},
+            FindOptions::builder()
+                .projection(doc! {"_id": 1, "hash": 1, "size": 1, "variants": 1})
+                .build(),
+        )
+        .await
+        .map_err(DbErr::QueryErr)?
+        .try_collect()
+        .await
+        .map_err(DbErr::QueryErr)?;
+
+    let cutoff = Utc::now() - Duration::minutes(GRACE_PERIOD_MINUTES);
+    Ok(plan(audio, &referenced, cutoff))
+}
+
+fn plan(audio: Vec<AudioRef>, referenced: &HashSet<ObjectId>, cutoff: DateTime<Utc>) -> Plan {
+    let (removed, kept): (Vec<_>, Vec<_>) = audio.into_iter().partition(|audio| {
+        !referenced.contains(&audio.id) && audio.id.timestamp().to_chrono() < cutoff
+    });
+
+    let kept_hashes: HashSet<_> = kept
+        .iter()
+        .flat_map(|audio| audio.blobs().map(|(hash, _)| hash))
+        .collect();
+    let mut blobs: Vec<(String, i64)> = Vec::new();
+    for (hash, size) in removed.iter().flat_map(|audio| audio.blobs()) {
+        if !kept_hashes.contains(hash) && !blobs.iter().any(|(h, _)| h == hash) {
+            blobs.push((hash.clone(), size));
+        }
+    }
+
+    Plan {
+        audio: removed.into_iter().map(|audio| audio.id).collect(),
+        blobs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audio(hash: &str, size: i64) -> AudioRef {
+        AudioRef {
+            id: ObjectId::new(),
+            hash: Some(hash.to_string()),
+            size,
+            variants: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn plan_ok() {
+        let referenced_audio = audio("a", 10);
+        let shared_hash = audio("a", 10);
+        let orphan = audio("b", 20);
+        let orphan_duplicate = audio("b", 20);
+        let legacy = AudioRef {
+            id: ObjectId::new(),
+            hash: None,
+            size: 0,
+            variants: Vec::new(),
+        };
+        let mut transcoded = audio("c", 30);
+        transcoded.variants.push(BlobRef {
+            hash: "c.opus".to_string(),
+            size: 3,
+        });
+
+        let referenced = HashSet::from([referenced_audio.id]);
+        let expected = Plan {
+            audio: vec![
+                shared_hash.id,
+                orphan.id,
+                orphan_duplicate.id,
+                legacy.id,
+                transcoded.id,
+            ],
+            blobs: vec![
+                ("b".to_string(), 20),
+                ("c".to_string(), 30),
+                ("c.opus".to_string(), 3),
+            ],
+        };
+
+        let plan = plan(
+            vec![
+                referenced_audio,
+                shared_hash,
+                orphan,
+                orphan_duplicate,
+                legacy,
+                transcoded,
+            ],
+            &referenced,
+            Utc::now() + Duration::minutes(1),
+        );
+        assert_eq!(plan, expected);
+    }
+
+    #[test]
+    fn plan_keeps_recent_audio() {
+        let recent = audio("a", 10);
+
+        let plan = plan(
+            vec![recent],
+            &HashSet::new(),
+            Utc::now() - Duration::minutes(GRACE_PERIOD_MINUTES),
+        );
+        assert_eq!(plan, Plan::default());
+    }
+}
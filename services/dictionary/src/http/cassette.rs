@@ -0,0 +1,189 @@
+use super::{HttpErr, HttpResponse};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// longer file names are cut and suffixed with a hash of the url
+const MAX_NAME_LEN: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// sends the requests and stores the responses
+    Record,
+    /// answers from the stored responses only, never touches the network
+    Replay,
+}
+
+/// Directory of recorded responses, a `<name>.json` with the status and headers per url
+/// and the body in the file it points to
+#[derive(Debug)]
+pub struct Cassette {
+    dir: PathBuf,
+    pub mode: Mode,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Recording {
+    url: String,
+    status: u16,
+    /// url the request ended up at after following redirects
+    final_url: String,
+    headers: Vec<(String, String)>,
+    /// relative to the cassette directory, may be shared with other fixtures
+    body: String,
+}
+
+impl Cassette {
+    pub fn new(dir: impl Into<PathBuf>, mode: Mode) -> Self {
+        Cassette {
+            dir: dir.into(),
+            mode,
+        }
+    }
+
+    pub async fn load(&self, url: &str) -> Result<HttpResponse, HttpErr> {
+        let name = name(url);
+        let json = match tokio::fs::read(self.dir.join(format!("{}.json", name))).await {
+            Ok(json) => json,
+            Err(_) => return Err(HttpErr::NotRecorded(url.to_string())),
+        };
+        let recording: Recording = serde_json::from_slice(&json).map_err(HttpErr::DecodeErr)?;
+        let bytes = tokio::fs::read(self.dir.join(&recording.body))
+            .await
+            .map_err(HttpErr::IoErr)?;
+
+        let mut headers = HeaderMap::new();
+        for (name, val) in &recording.headers {
+            if let (Ok(name), Ok(val)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(val),
+            ) {
+                headers.append(name, val);
+            }
+        }
+
+        Ok(HttpResponse {
+            status: StatusCode::from_u16(recording.status).unwrap_or(StatusCode::OK),
+            url: recording.final_url,
+            headers,
+            bytes,
+        })
+    }
+
+    /// urls of all the recorded responses, sorted
+    pub async fn urls(&self) -> Result<Vec<String>, HttpErr> {
+        let mut entries = tokio::fs::read_dir(&self.dir)
+            .await
+            .map_err(HttpErr::IoErr)?;
+        let mut urls = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(HttpErr::IoErr)? {
+            if entry.path().extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let json = tokio::fs::read(entry.path())
+                .await
+                .map_err(HttpErr::IoErr)?;
+            let recording: Recording = serde_json::from_slice(&json).map_err(HttpErr::DecodeErr)?;
+            urls.push(recording.url);
+        }
+        urls.sort();
+        Ok(urls)
+    }
+
+    pub async fn save(&self, url: &str, res: &HttpResponse) -> Result<(), HttpErr> {
+        let name = name(url);
+        let recording = Recording {
+            url: url.to_string(),
+            status: res.status.as_u16(),
+            final_url: res.url.clone(),
+            headers: res
+                .headers
+                .iter()
+                .filter_map(|(name, val)| Some((name.to_string(), val.to_str().ok()?.to_string())))
+                .collect(),
+            body: format!("{}.body", name),
+        };
+        let json = serde_json::to_vec_pretty(&recording).map_err(HttpErr::DecodeErr)?;
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(HttpErr::IoErr)?;
+        tokio::fs::write(self.dir.join(&recording.body), &res.bytes)
+            .await
+            .map_err(HttpErr::IoErr)?;
+        tokio::fs::write(self.dir.join(format!("{}.json", name)), json)
+            .await
+            .map_err(HttpErr::IoErr)
+    }
+}
+
+/// readable file name of the url without the scheme
+pub fn name(url: &str) -> String {
+    let readable: String = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect();
+
+    if readable.len() <= MAX_NAME_LEN {
+        return readable;
+    }
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    format!("{}_{}", &readable[..MAX_NAME_LEN], &hash[..12])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_ok() {
+        assert_eq!(
+            name("https://www.vocabulary.com/dictionary/cat"),
+            "www.vocabulary.com_dictionary_cat"
+        );
+
+        let long = name("https://corpus.vocabulary.com/api/1.0/examples.json?maxResults=24&query=cat&sartOffset=0&domain=F");
+        assert_eq!(long.len(), MAX_NAME_LEN + 13);
+        assert_ne!(
+            long,
+            name("https://corpus.vocabulary.com/api/1.0/examples.json?maxResults=24&query=dog&sartOffset=0&domain=F")
+        );
+    }
+
+    #[tokio::test]
+    async fn record_and_replay() {
+        let dir = std::env::temp_dir().join(format!("cassette_{}", std::process::id()));
+        let cassette = Cassette::new(&dir, Mode::Record);
+        let url = "https://example.com/cat?x=1";
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("text/html"));
+        let res = HttpResponse {
+            status: StatusCode::NOT_FOUND,
+            url: "https://example.com/cats".to_string(),
+            headers,
+            bytes: b"<h1>cat</h1>".to_vec(),
+        };
+
+        assert!(matches!(
+            cassette.load(url).await,
+            Err(HttpErr::NotRecorded(_))
+        ));
+        cassette.save(url, &res).await.unwrap();
+        assert_eq!(cassette.urls().await.unwrap(), vec![url.to_string()]);
+
+        let replayed = cassette.load(url).await.unwrap();
+        assert_eq!(replayed.status, res.status);
+        assert_eq!(replayed.url, res.url);
+        assert_eq!(replayed.content_type(), Some("text/html".to_string()));
+        assert_eq!(replayed.bytes, res.bytes);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
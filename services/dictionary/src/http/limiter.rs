@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket of a single host, tokens may go negative to queue up the waiting requests
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(burst: f64, now: Instant) -> Self {
+        TokenBucket {
+            tokens: burst,
+            updated: now,
+        }
+    }
+
+    /// takes a token, returns how long to wait before sending the request
+    fn take(&mut self, now: Instant, rate: f64, burst: f64) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.updated = now;
+        self.tokens -= 1.0;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+/// Requests per second allowed to each host
+#[derive(Debug)]
+pub struct RateLimiter {
+    default_rate: f64,
+    burst: f64,
+    /// rates of hosts which differ from the default one
+    host_rates: HashMap<String, f64>,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(default_rate: f64, burst: f64, host_rates: HashMap<String, f64>) -> Self {
+        RateLimiter {
+            default_rate: default_rate.max(f64::EPSILON),
+            burst: burst.max(1.0),
+            host_rates,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `crawl_delay` of the host's robots.txt lowers the rate when it is stricter
+    pub async fn acquire(&self, host: &str, crawl_delay: Option<Duration>) {
+        let wait = self.reserve(host, crawl_delay, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn reserve(&self, host: &str, crawl_delay: Option<Duration>, now: Instant) -> Duration {
+        let mut rate = self
+            .host_rates
+            .get(host)
+            .copied()
+            .unwrap_or(self.default_rate)
+            .max(f64::EPSILON);
+        let mut burst = self.burst;
+        if let Some(delay) = crawl_delay.filter(|x| !x.is_zero()) {
+            rate = rate.min(1.0 / delay.as_secs_f64());
+            burst = 1.0;
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets
+            .entry(host.to_string())
+            .or_insert_with(|| TokenBucket::new(burst, now))
+            .take(now, rate, burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserve_ok() {
+        let limiter = RateLimiter::new(2.0, 2.0, HashMap::from([("slow".to_string(), 0.5)]));
+        let now = Instant::now();
+
+        // burst, then one request every half a second
+        assert_eq!(limiter.reserve("a", None, now), Duration::ZERO);
+        assert_eq!(limiter.reserve("a", None, now), Duration::ZERO);
+        assert_eq!(limiter.reserve("a", None, now), Duration::from_millis(500));
+        assert_eq!(limiter.reserve("a", None, now), Duration::from_secs(1));
+        // refilled meanwhile
+        assert_eq!(
+            limiter.reserve("a", None, now + Duration::from_secs(1)),
+            Duration::from_millis(500)
+        );
+
+        // other hosts have their own buckets
+        assert_eq!(limiter.reserve("b", None, now), Duration::ZERO);
+        assert_eq!(limiter.reserve("slow", None, now), Duration::ZERO);
+        assert_eq!(limiter.reserve("slow", None, now), Duration::ZERO);
+        assert_eq!(limiter.reserve("slow", None, now), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn reserve_crawl_delay() {
+        let limiter = RateLimiter::new(10.0, 5.0, HashMap::new());
+        let now = Instant::now();
+        let delay = Some(Duration::from_secs(3));
+
+        assert_eq!(limiter.reserve("a", delay, now), Duration::ZERO);
+        assert_eq!(limiter.reserve("a", delay, now), Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::env::Env;
+use cassette::{Cassette, Mode};
+use common_rs::EnvStore;
+use limiter::RateLimiter;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, USER_AGENT};
+use reqwest::{Client, StatusCode, Url};
+use robots::Robots;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+pub mod cassette;
+pub mod limiter;
+pub mod robots;
+
+static HTTP: OnceLock<Http> = OnceLock::new();
+
+/// robots.txt of a host is fetched again after this
+const ROBOTS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// sent when no user agents are configured, identifies the scraper to the sites
+const DEFAULT_USER_AGENT: &str = concat!("dictionary-ms/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug)]
+pub enum HttpErr {
+    InvalidUrl(String),
+    /// the host's robots.txt does not allow the url
+    Disallowed(String),
+    RequestErr(reqwest::Error),
+    DecodeErr(serde_json::Error),
+    /// replaying and the cassette has no response of the url
+    NotRecorded(String),
+    IoErr(std::io::Error),
+}
+
+/// Response read to the end, so the connection goes back to the pool right away
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    /// url the request ended up at after following redirects
+    pub url: String,
+    pub headers: HeaderMap,
+    pub bytes: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn content_type(&self) -> Option<String> {
+        self.headers
+            .get(CONTENT_TYPE)
+            .and_then(|val| val.to_str().ok())
+            .map(|val| val.to_string())
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.bytes).to_string()
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, HttpErr> {
+        serde_json::from_slice(&self.bytes).map_err(HttpErr::DecodeErr)
+    }
+}
+
+/// HTTP layer shared by all scrapers: one connection pool, per-host rate limits,
+/// a cap on requests in flight and robots.txt awareness
+#[derive(Debug)]
+pub struct Http {
+    client: Client,
+    limiter: RateLimiter,
+    in_flight: Semaphore,
+    /// a host's robots.txt is fetched by one request at a time, the others wait for it
+    robots: Mutex<HashMap<String, Arc<RobotsEntry>>>,
+    /// rotated per request, `DEFAULT_USER_AGENT` when not configured
+    user_agents: Vec<String>,
+    next_user_agent: AtomicUsize,
+    robots_user_agent: String,
+    cassette: Option<Cassette>,
+}
+
+/// robots.txt of a host with when it was fetched, empty until the first fetch is done
+type RobotsEntry = tokio::sync::Mutex<Option<(Instant, Arc<Robots>)>>;
+
+/// the instance configured by the env, unless `record_into` or `replay_from` set another one
+pub fn client() -> &'static Http {
+    HTTP.get_or_init(|| Http::from_env(None))
+}
+
+/// records the responses of all requests into `dir`, has to be called before the first request
+pub fn record_into(dir: &str) -> Result<(), String> {
+    HTTP.set(Http::from_env(Some(Cassette::new(dir, Mode::Record))))
+        .map_err(|_| "The http client is in use already".to_string())
+}
+
+/// replays the responses recorded in `dir` instead of sending the requests,
+/// the tests share the first dir it is called with
+#[cfg(test)]
+pub fn replay_from(dir: &str) {
+    HTTP.get_or_init(|| Http::replay(dir));
+}
+
+impl Http {
+    fn from_env(cassette: Option<Cassette>) -> Self {
+        let env = Env::vars();
+        let client = Client::builder()
+            .timeout(Duration::from_secs(env.http_timeout_secs))
+            .pool_max_idle_per_host(env.http_max_concurrency)
+            .build()
+            .unwrap_or_else(|err| panic!("Could not create the http client. Err: {}", err));
+
+        Http {
+            client,
+            limiter: RateLimiter::new(env.http_rate_per_sec, env.http_burst, env.http_host_rates),
+            in_flight: Semaphore::new(env.http_max_concurrency.max(1)),
+            robots: Mutex::new(HashMap::new()),
+            user_agents: env.user_agents.unwrap_or_default(),
+            next_user_agent: AtomicUsize::new(0),
+            robots_user_agent: env.robots_user_agent.unwrap_or_default(),
+            cassette,
+        }
+    }
+
+    /// offline, does not read the env
+    #[cfg(test)]
+    fn replay(dir: &str) -> Self {
+        Http {
+            client: Client::new(),
+            limiter: RateLimiter::new(f64::MAX, f64::MAX, HashMap::new()),
+            in_flight: Semaphore::new(Semaphore::MAX_PERMITS),
+            robots: Mutex::new(HashMap::new()),
+            user_agents: Vec::new(),
+            next_user_agent: AtomicUsize::new(0),
+            robots_user_agent: String::new(),
+            cassette: Some(Cassette::new(dir, Mode::Replay)),
+        }
+    }
+
+    pub async fn get(&self, url: &str) -> Result<HttpResponse, HttpErr> {
+        let parsed = Url::parse(url).map_err(|_| HttpErr::InvalidUrl(url.to_string()))?;
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| HttpErr::InvalidUrl(url.to_string()))?
+            .to_string();
+
+        let robots = self.robots(&parsed, &host).await;
+        let path = match parsed.query() {
+            Some(query) => format!("{}?{}", parsed.path(), query),
+            None => parsed.path().to_string(),
+        };
+        if !robots.is_allowed(&path) {
+            return Err(HttpErr::Disallowed(url.to_string()));
+        }
+
+        self.send(parsed, &host, robots.crawl_delay).await
+    }
+
+    async fn send(
+        &self,
+        url: Url,
+        host: &str,
+        crawl_delay: Option<Duration>,
+    ) -> Result<HttpResponse, HttpErr> {
+        if let Some(cassette) = self.cassette.as_ref().filter(|x| x.mode == Mode::Replay) {
+            return cassette.load(url.as_str()).await;
+        }
+
+        self.limiter.acquire(host, crawl_delay).await;
+        let _permit = self.in_flight.acquire().await.expect("Never closed");
+
+        let res = self
+            .client
+            .get(url.clone())
+            .header(USER_AGENT, self.user_agent())
+            .send()
+            .await
+            .map_err(HttpErr::RequestErr)?;
+
+        let status = res.status();
+        let final_url = res.url().to_string();
+        let headers = res.headers().clone();
+        let bytes = res.bytes().await.map_err(HttpErr::RequestErr)?.to_vec();
+
+        let res = HttpResponse {
+            status,
+            url: final_url,
+            headers,
+            bytes,
+        };
+        if let Some(cassette) = &self.cassette {
+            cassette.save(url.as_str(), &res).await?;
+        }
+        Ok(res)
+    }
+
+    /// cached robots.txt of the host, a missing or unreachable one allows everything
+    async fn robots(&self, url: &Url, host: &str) -> Arc<Robots> {
+        let entry = self
+            .robots
+            .lock()
+            .unwrap()
+            .entry(host.to_string())
+            .or_default()
+            .clone();
+        // held while fetching so that the concurrent requests to the host wait for this fetch
+        let mut entry = entry.lock().await;
+        if let Some((fetched_at, robots)) = entry.as_ref() {
+            if fetched_at.elapsed() < ROBOTS_TTL {
+                return robots.clone();
+            }
+        }
+
+        let mut robots_url = url.clone();
+        robots_url.set_path("/robots.txt");
+        robots_url.set_query(None);
+        robots_url.set_fragment(None);
+
+        let robots = match self.send(robots_url, host, None).await {
+            Ok(res) if res.status.is_success() => {
+                Robots::parse(&res.text(), &self.robots_user_agent)
+            }
+            _ => Robots::allow_all(),
+        };
+        let robots = Arc::new(robots);
+
+        *entry = Some((Instant::now(), robots.clone()));
+        robots
+    }
+
+    fn user_agent(&self) -> String {
+        if self.user_agents.is_empty() {
+            return DEFAULT_USER_AGENT.to_string();
+        }
+        let i = self.next_user_agent.fetch_add(1, Ordering::Relaxed);
+        self.user_agents[i % self.user_agents.len()].clone()
+    }
+}
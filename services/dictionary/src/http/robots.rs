@@ -0,0 +1,160 @@
+use std::time::Duration;
+
+/// Rules of a robots.txt which apply to our user agent
+#[derive(Debug, Default, PartialEq)]
+pub struct Robots {
+    /// `(allowed, path pattern)`
+    rules: Vec<(bool, String)>,
+    pub crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// allows everything, used when the host has no robots.txt
+    pub fn allow_all() -> Self {
+        Robots::default()
+    }
+
+    /// takes the group of `agent`, the `*` group when there is none
+    pub fn parse(txt: &str, agent: &str) -> Self {
+        let agent = agent.to_lowercase();
+        let mut specific: Option<Robots> = None;
+        let mut wildcard: Option<Robots> = None;
+
+        // agents of the group being read and whether its rules are being read already
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+        let mut group = Robots::default();
+
+        let mut flush = |agents: &[String], group: Robots| {
+            if !agent.is_empty()
+                && agents
+                    .iter()
+                    .any(|x| x != "*" && agent.contains(x.as_str()))
+            {
+                specific.get_or_insert(group);
+            } else if agents.iter().any(|x| x == "*") {
+                wildcard.get_or_insert(group);
+            }
+        };
+
+        for line in txt.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+                None => continue,
+            };
+
+            match key.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        flush(&agents, std::mem::take(&mut group));
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_lowercase());
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    // an empty disallow allows everything
+                    if !value.is_empty() {
+                        group.rules.push((key == "allow", value.to_string()));
+                    }
+                }
+                "crawl-delay" => {
+                    in_rules = true;
+                    group.crawl_delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|x| x.is_finite() && *x >= 0.0)
+                        .map(Duration::from_secs_f64);
+                }
+                _ => {}
+            }
+        }
+        flush(&agents, group);
+
+        specific.or(wildcard).unwrap_or_default()
+    }
+
+    /// the longest matching rule wins, allow wins a tie
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| matches(pattern, path))
+            .max_by_key(|(allowed, pattern)| (pattern.len(), *allowed))
+            .is_none_or(|(allowed, _)| *allowed)
+    }
+}
+
+/// robots.txt path pattern with `*` wildcards and the `$` end anchor
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match path.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let parts: Vec<_> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        let is_last = i == parts.len() - 1;
+        if is_last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "
+        # comment
+        User-agent: *
+        Disallow: /search
+        Disallow: /*.json$
+        Allow: /search/help
+        Crawl-delay: 2
+
+        User-agent: BadBot
+        User-agent: dictionary-bot
+        Disallow: /
+    ";
+
+    #[test]
+    fn parse_wildcard_group() {
+        let robots = Robots::parse(ROBOTS, "Mozilla/5.0");
+
+        assert!(robots.is_allowed("/definition/english/cat"));
+        assert!(!robots.is_allowed("/search?q=cat"));
+        assert!(robots.is_allowed("/search/help"));
+        assert!(!robots.is_allowed("/api/words.json"));
+        assert!(robots.is_allowed("/api/words.json?x=1"));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn parse_specific_group() {
+        let robots = Robots::parse(ROBOTS, "dictionary-bot/1.0");
+
+        assert!(!robots.is_allowed("/definition/english/cat"));
+        assert_eq!(robots.crawl_delay, None);
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(Robots::parse("", "dictionary-bot"), Robots::allow_all());
+        assert!(Robots::allow_all().is_allowed("/anything"));
+    }
+}
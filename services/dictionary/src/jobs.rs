@@ -0,0 +1,534 @@
+use crate::db::database::{is_duplicate_key, DbErr};
+use crate::env::Env;
+use crate::error::DictionaryErr;
+use crate::models::job::{JobDoc, JobStatus, SourceProgress, Stage};
+use crate::sources::{Lookup, Progress};
+use crate::DictionaryService;
+use chrono::Duration;
+use common_rs::EnvStore;
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use mongodb::bson::{self, doc, oid::ObjectId, DateTime};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use mongodb::{Collection, Database};
+use rpc::dictionary::{SourceStage, WatchWordEvent};
+use std::collections::HashMap;
+use tonic::Status;
+
+/// attempts of a job before it is moved to the dead-letter queue
+const MAX_ATTEMPTS: i32 = 5;
+const BACKOFF_BASE_SECS: i64 = 30;
+/// a running job whose lease is not renewed by then is handed to another worker
+const LEASE_SECS: i64 = 5 * 60;
+/// the worker renews the lease of its job this often while it runs
+const HEARTBEAT_SECS: u64 = 60;
+const IDLE_POLL_MILLIS: u64 = 1000;
+const WATCH_POLL_MILLIS: u64 = 500;
+
+/// Durable queue of words to scrape, stored in the `jobs` collection
+#[derive(Debug, Clone)]
+pub struct JobQueue {
+    jobs: Collection<JobDoc>,
+}
+
+impl JobQueue {
+    pub fn new(db: &Database) -> Self {
+        JobQueue {
+            jobs: db.collection("jobs"),
+        }
+    }
+
+    /// queues a lookup of the word in the given sources, returns the pending job of the word when there is one already
+    pub async fn enqueue(&self, word: &str, sources: &[&str]) -> Result<JobDoc, DbErr> {
+        match self.upsert(word, sources).await {
+            // a concurrent enqueue of the word inserted it first, the unique index rejected this one
+            Err(DbErr::QueryErr(err)) if is_duplicate_key(&err) => self.upsert(word, sources).await,
+            res => res,
+        }
+    }
+
+    async fn upsert(&self, word: &str, sources: &[&str]) -> Result<JobDoc, DbErr> {
+        let now = DateTime::now();
+        let progress: HashMap<_, _> = sources
+            .iter()
+            .map(|source| (source.to_string(), SourceProgress::new(Stage::Queued)))
+            .collect();
+        let filter = doc! {
+            "word": word,
+            "status": {"$in": [status(JobStatus::Queued), status(JobStatus::Running)]},
+        };
+        let update = doc! {
+            "$setOnInsert": {
+                "word": word,
+                "status": status(JobStatus::Queued),
+                "attempts": 0,
+                "sources": bson::to_bson(&progress).map_err(DbErr::SerializeBsonErr)?,
+                "last_error": null,
+                "run_at": now,
+                "locked_until": null,
+                "updated_at": now,
+            },
+        };
+
+        self.jobs
+            .find_one_and_update(
+                filter,
+                update,
+                FindOneAndUpdateOptions::builder()
+                    .upsert(true)
+                    .return_document(ReturnDocument::After)
+                    .build(),
+            )
+            .await
+            .map_err(DbErr::QueryErr)?
+            .ok_or(DbErr::MissingDoc("upserted job"))
+    }
+
+    pub async fn get(&self, id: &ObjectId) -> Result<Option<JobDoc>, DbErr> {
+        self.jobs
+            .find_one(doc! {"_id": id}, None)
+            .await
+            .map_err(DbErr::QueryErr)
+    }
+
+    /// takes the next due job, or a running one whose worker did not finish it in time
+    async fn claim(&self) -> Result<Option<JobDoc>, DbErr> {
+        let now = DateTime::now();
+        let filter = doc! {
+            "$or": [
+                {"status": status(JobStatus::Queued), "run_at": {"$lte": now}},
+                {"status": status(JobStatus::Running), "locked_until": {"$lte": now}},
+            ],
+        };
+        let update = doc! {
+            "$set": {
+                "status": status(JobStatus::Running),
+                "locked_until": after(Duration::seconds(LEASE_SECS)),
+                "updated_at": now,
+            },
+            "$inc": {"attempts": 1},
+        };
+
+        self.jobs
+            .find_one_and_update(
+                filter,
+                update,
+                FindOneAndUpdateOptions::builder()
+                    .sort(doc! {"run_at": 1})
+                    .return_document(ReturnDocument::After)
+                    .build(),
+            )
+            .await
+            .map_err(DbErr::QueryErr)
+    }
+
+    /// extends the lease of a running job, unless another worker took it over since
+    async fn renew(&self, job: &JobDoc) -> Result<(), DbErr> {
+        let filter = doc! {
+            "_id": job.id,
+            "status": status(JobStatus::Running),
+            "attempts": job.attempts,
+        };
+        let update = doc! {
+            "$set": {"locked_until": after(Duration::seconds(LEASE_SECS))},
+        };
+
+        self.jobs
+            .update_one(filter, update, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+        Ok(())
+    }
+
+    async fn set_progress(
+        &self,
+        id: &ObjectId,
+        source: &str,
+        progress: &SourceProgress,
+    ) -> Result<(), DbErr> {
+        let update = doc! {
+            "$set": {
+                format!("sources.{}", source): bson::to_bson(progress).map_err(DbErr::SerializeBsonErr)?,
+                "updated_at": DateTime::now(),
+            },
+        };
+
+        self.jobs
+            .update_one(doc! {"_id": id}, update, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+        Ok(())
+    }
+
+    async fn complete(&self, job: &JobDoc) -> Result<(), DbErr> {
+        self.finish(job, JobStatus::Done, None, DateTime::now())
+            .await
+    }
+
+    /// schedules a retry with an exponential backoff, gives up after `MAX_ATTEMPTS`
+    async fn fail(&self, job: &JobDoc, err: String) -> Result<(), DbErr> {
+        match backoff(job.attempts) {
+            Some(delay) => {
+                self.finish(job, JobStatus::Queued, Some(err), after(delay))
+                    .await
+            }
+            None => {
+                self.finish(job, JobStatus::Dead, Some(err), DateTime::now())
+                    .await
+            }
+        }
+    }
+
+    /// fails with `DbErr::LeaseLost` when another worker took the job over since, see `renew`
+    async fn finish(
+        &self,
+        job: &JobDoc,
+        job_status: JobStatus,
+        err: Option<String>,
+        run_at: DateTime,
+    ) -> Result<(), DbErr> {
+        let update = doc! {
+            "$set": {
+                "status": status(job_status),
+                "last_error": err,
+                "run_at": run_at,
+                "locked_until": null,
+                "updated_at": DateTime::now(),
+            },
+        };
+
+        // claiming bumps the attempts
+        let filter = doc! {"_id": job.id, "attempts": job.attempts};
+        let res = self
+            .jobs
+            .update_one(filter, update, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+        if res.matched_count == 0 {
+            return Err(DbErr::LeaseLost);
+        }
+        Ok(())
+    }
+
+    /// moves the dead-letter jobs back to the queue, returns the number of requeued jobs
+    pub async fn requeue_dead(&self) -> Result<u64, DbErr> {
+        let update = doc! {
+            "$set": {
+                "status": status(JobStatus::Queued),
+                "attempts": 0,
+                "run_at": DateTime::now(),
+                "updated_at": DateTime::now(),
+            },
+        };
+
+        self.jobs
+            .update_many(doc! {"status": status(JobStatus::Dead)}, update, None)
+            .await
+            .map(|res| res.modified_count)
+            .map_err(DbErr::QueryErr)
+    }
+}
+
+/// starts `JOB_WORKERS` workers taking jobs off the queue
+pub fn spawn_workers(service: &DictionaryService) {
+    for _ in 0..Env::vars().job_workers {
+        let service = service.clone();
+        tokio::spawn(async move {
+            loop {
+                // the jobs are in the db, they wait until it is back
+                if !service.db_health.is_up() {
+                    idle().await;
+                    continue;
+                }
+                match service.jobs.claim().await {
+                    Ok(Some(job)) => process(&service, job).await,
+                    Ok(None) => idle().await,
+                    Err(err) => {
+                        println!("Could not claim a job: {:?}", err);
+                        idle().await;
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn idle() {
+    tokio::time::sleep(std::time::Duration::from_millis(IDLE_POLL_MILLIS)).await;
+}
+
+async fn process(service: &DictionaryService, job: JobDoc) {
+    let id = match job.id {
+        Some(id) => id,
+        None => return,
+    };
+    println!("Processing {} (attempt {})", job.word, job.attempts);
+
+    let heartbeat = tokio::spawn({
+        let jobs = service.jobs.clone();
+        let job = job.clone();
+        async move {
+            let period = std::time::Duration::from_secs(HEARTBEAT_SECS);
+            let mut interval =
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                if let Err(err) = jobs.renew(&job).await {
+                    println!("Could not renew the lease of {}: {:?}", job.word, err);
+                }
+            }
+        }
+    });
+    let res = run(service, &id, &job.word).await;
+    heartbeat.abort();
+
+    let err = match res {
+        Ok(failed) if failed.is_empty() => None,
+        Ok(failed) => Some(format!("Failed sources: {}", failed.join(", "))),
+        Err(err) => Some(format!("{:?}", err)),
+    };
+
+    let res = match err {
+        None => service.jobs.complete(&job).await,
+        Some(err) => {
+            println!("Job of {} failed: {}", job.word, err);
+            service.jobs.fail(&job, err).await
+        }
+    };
+    if let Err(err) = res {
+        println!("Could not update the job of {}: {:?}", job.word, err);
+    }
+}
+
+/// looks the word up in the sources which do not have it yet, returns the ones which failed
+async fn run(service: &DictionaryService, id: &ObjectId, word: &str) -> Result<Vec<String>, DbErr> {
+    let definition = service
+        .repository
+        .get_definition(word)
+        .await?
+        .unwrap_or_else(|| crate::models::definition::DefinitionDoc {
+            word: word.to_string(),
+            ..Default::default()
+        });
+    let ttl = Duration::seconds(Env::vars().negative_cache_ttl_secs);
+
+    // the progress is written by a single task so the updates of a source keep their order
+    let (tx, mut rx) = mpsc::unbounded::<(&'static str, SourceProgress)>();
+    let jobs = service.jobs.clone();
+    let job_id = *id;
+    let writer = tokio::spawn(async move {
+        while let Some((source, progress)) = rx.next().await {
+            if let Err(err) = jobs.set_progress(&job_id, source, &progress).await {
+                println!("Could not report the progress of {}: {:?}", source, err);
+            }
+        }
+    });
+
+    let lookups = service.sources.iter().map(|source| {
+        let tx = tx.clone();
+        let pending =
+            definition.needs_lookup(source.name(), ttl) || definition.has_failed(source.name());
+        let found = definition.sources.get(source.name()).cloned().flatten();
+        async move {
+            let name = source.name();
+            let lookup = match found {
+                Some(id) if !pending => Lookup::Found(id),
+                _ => {
+                    let reporter = tx.clone();
+                    let progress = Progress::new(move |stage| {
+                        _ = reporter.unbounded_send((name, SourceProgress::new(stage)));
+                    });
+                    source.lookup(&service.repository, word, &progress).await
+                }
+            };
+            _ = tx.unbounded_send((name, outcome(&lookup)));
+            (name, lookup)
+        }
+    });
+    let lookups = futures::future::join_all(lookups).await;
+    drop(tx);
+    _ = writer.await;
+
+    let failed = lookups
+        .iter()
+        .filter(|(_, lookup)| matches!(lookup, Lookup::Failed { .. }))
+        .map(|(name, _)| name.to_string())
+        .collect();
+    service.store_lookups(definition, lookups).await?;
+
+    Ok(failed)
+}
+
+fn outcome(lookup: &Lookup) -> SourceProgress {
+    match lookup {
+        Lookup::Found(id) => SourceProgress {
+            stage: Stage::Done,
+            definition: Some(*id),
+            error: None,
+        },
+        Lookup::NotFound { .. } => SourceProgress::new(Stage::Done),
+        Lookup::Failed { reason, .. } => SourceProgress {
+            stage: Stage::Failed,
+            definition: None,
+            error: Some(reason.clone()),
+        },
+    }
+}
+
+/// queues the word and streams the progress of its sources until the job is finished,
+/// ends with the error when the job can not be queued or read
+pub fn watch(
+    service: DictionaryService,
+    word: String,
+) -> mpsc::Receiver<Result<WatchWordEvent, Status>> {
+    let (mut tx, rx) = mpsc::channel(16);
+
+    tokio::spawn(async move {
+        let names: Vec<_> = service.sources.iter().map(|source| source.name()).collect();
+        let job = match service.jobs.enqueue(&word, &names).await {
+            Ok(job) => job,
+            Err(err) => {
+                _ = tx.send(Err(DictionaryErr::from(err).into())).await;
+                return;
+            }
+        };
+        let id = match job.id {
+            Some(id) => id,
+            None => {
+                let err = DictionaryErr::from(DbErr::MissingDoc("job id"));
+                _ = tx.send(Err(err.into())).await;
+                return;
+            }
+        };
+
+        let mut seen: HashMap<String, SourceProgress> = HashMap::new();
+        let mut job = Some(job);
+        loop {
+            let current = match job.take() {
+                Some(job) => job,
+                None => match service.jobs.get(&id).await {
+                    Ok(Some(job)) => job,
+                    Ok(None) => return,
+                    Err(err) => {
+                        _ = tx.send(Err(DictionaryErr::from(err).into())).await;
+                        return;
+                    }
+                },
+            };
+
+            for name in &names {
+                let progress = match current.sources.get(*name) {
+                    Some(progress) if seen.get(*name) != Some(progress) => progress,
+                    _ => continue,
+                };
+                seen.insert(name.to_string(), progress.clone());
+
+                let event = event(&service, &word, name, progress).await;
+                if tx.send(Ok(event)).await.is_err() {
+                    // the client went away
+                    return;
+                }
+            }
+
+            if matches!(current.status, JobStatus::Done | JobStatus::Dead) {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(WATCH_POLL_MILLIS)).await;
+        }
+    });
+
+    rx
+}
+
+async fn event(
+    service: &DictionaryService,
+    word: &str,
+    source: &str,
+    progress: &SourceProgress,
+) -> WatchWordEvent {
+    let definition = match (&progress.definition, service.sources.get(source)) {
+        (Some(id), Some(source)) => source.get_definition(id).await.unwrap_or_else(|err| {
+            println!("Could not get the definition of {}: {:?}", word, err);
+            None
+        }),
+        _ => None,
+    };
+
+    WatchWordEvent {
+        word: word.to_string(),
+        source: source.to_string(),
+        stage: source_stage(progress.stage) as i32,
+        definition,
+        error: progress.error.clone(),
+    }
+}
+
+fn source_stage(stage: Stage) -> SourceStage {
+    match stage {
+        Stage::Queued => SourceStage::Queued,
+        Stage::Scraping => SourceStage::Scraping,
+        Stage::DownloadingAudio => SourceStage::DownloadingAudio,
+        Stage::Done => SourceStage::Done,
+        Stage::Failed => SourceStage::Failed,
+    }
+}
+
+fn status(status: JobStatus) -> bson::Bson {
+    bson::to_bson(&status).unwrap_or_default()
+}
+
+fn after(delay: Duration) -> DateTime {
+    DateTime::from_chrono(chrono::Utc::now() + delay)
+}
+
+/// delay before the next attempt, `None` when the job is out of attempts
+fn backoff(attempts: i32) -> Option<Duration> {
+    if attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    let exp = attempts.clamp(1, MAX_ATTEMPTS) - 1;
+    Some(Duration::seconds(BACKOFF_BASE_SECS << exp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::mem_db;
+
+    #[test]
+    fn backoff_ok() {
+        assert_eq!(backoff(1), Some(Duration::seconds(30)));
+        assert_eq!(backoff(2), Some(Duration::seconds(60)));
+        assert_eq!(backoff(4), Some(Duration::seconds(240)));
+        assert_eq!(backoff(MAX_ATTEMPTS), None);
+    }
+
+    #[tokio::test]
+    async fn finish_lost_lease() {
+        let db = mem_db::start("jobs_test").await;
+        let jobs = JobQueue::new(&db);
+        jobs.enqueue("cat", &["oxford"]).await.unwrap();
+        let job = jobs.claim().await.unwrap().expect("should be due");
+
+        // claimed by another worker since, which bumped the attempts
+        let mut stale = job.clone();
+        stale.attempts -= 1;
+        assert!(matches!(jobs.complete(&stale).await, Err(DbErr::LeaseLost)));
+
+        jobs.complete(&job).await.unwrap();
+        let done = jobs.get(&job.id.unwrap()).await.unwrap().unwrap();
+        assert_eq!(done.status, JobStatus::Done);
+    }
+
+    #[test]
+    fn status_ok() {
+        assert_eq!(
+            status(JobStatus::Queued),
+            bson::Bson::String("queued".to_string())
+        );
+        assert_eq!(
+            status(JobStatus::Dead),
+            bson::Bson::String("dead".to_string())
+        );
+    }
+}
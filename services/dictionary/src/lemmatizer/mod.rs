@@ -0,0 +1,192 @@
+use crate::db::database::DbErr;
+use crate::models::inflection::InflectionDoc;
+use mongodb::bson::doc;
+use mongodb::options::ReplaceOptions;
+use mongodb::{Collection, Database};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub use normalize::normalize;
+
+mod normalize;
+
+/// irregular forms, one "form lemma" per line
+const EXCEPTIONS: &str = include_str!("exceptions.txt");
+
+/// Maps inflected forms to the headword their definitions are stored under, e.g. "cats" => "cat",
+/// from the bundled irregular forms, the inflections the sources list and the regular suffixes
+#[derive(Debug, Clone)]
+pub struct Lemmatizer {
+    exceptions: Arc<HashMap<String, String>>,
+    inflections: Collection<InflectionDoc>,
+}
+
+impl Lemmatizer {
+    pub fn new(db: &Database) -> Self {
+        Lemmatizer {
+            exceptions: Arc::new(parse_exceptions(EXCEPTIONS)),
+            inflections: db.collection("inflections"),
+        }
+    }
+
+    /// lemma of a normalized word, the word itself when it is a headword already or nothing matches
+    pub async fn lemma(&self, word: &str) -> String {
+        if word.is_empty() || self.is_headword(word).await {
+            return word.to_string();
+        }
+        if let Some(lemma) = self.exceptions.get(word) {
+            return lemma.clone();
+        }
+        if let Some(lemma) = self.learned(word).await {
+            return lemma;
+        }
+        // a regular form is trusted only when the guessed lemma is a known headword
+        for candidate in candidates(word) {
+            if self.is_headword(&candidate).await {
+                return candidate;
+            }
+        }
+        word.to_string()
+    }
+
+    /// lemma of an irregular form, the word itself otherwise, without querying the db
+    pub fn irregular_lemma(&self, word: &str) -> String {
+        self.exceptions
+            .get(word)
+            .cloned()
+            .unwrap_or_else(|| word.to_string())
+    }
+
+    /// remembers the inflected forms of a scraped headword,
+    /// a form which is a headword of its own keeps pointing to itself
+    pub async fn learn(&self, lemma: &str, forms: &[String]) -> Result<(), DbErr> {
+        let lemma = normalize(lemma);
+        if lemma.is_empty() {
+            return Ok(());
+        }
+        self.save(&lemma, &lemma).await?;
+
+        for form in forms.iter().map(|x| normalize(x)) {
+            if form.is_empty() || form == lemma || self.is_headword(&form).await {
+                continue;
+            }
+            self.save(&form, &lemma).await?;
+        }
+        Ok(())
+    }
+
+    async fn save(&self, form: &str, lemma: &str) -> Result<(), DbErr> {
+        let doc = InflectionDoc {
+            id: None,
+            form: form.to_string(),
+            lemma: lemma.to_string(),
+        };
+        self.inflections
+            .replace_one(
+                doc! {"form": form},
+                doc,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(DbErr::QueryErr)?;
+        Ok(())
+    }
+
+    async fn is_headword(&self, word: &str) -> bool {
+        self.inflections
+            .find_one(doc! {"form": word, "lemma": word}, None)
+            .await
+            .ok()
+            .flatten()
+            .is_some()
+    }
+
+    async fn learned(&self, form: &str) -> Option<String> {
+        self.inflections
+            .find_one(doc! {"form": form}, None)
+            .await
+            .ok()
+            .flatten()
+            .map(|x| x.lemma)
+            .filter(|lemma| lemma != form)
+    }
+}
+
+fn parse_exceptions(txt: &str) -> HashMap<String, String> {
+    txt.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(' '))
+        .map(|(form, lemma)| (form.trim().to_string(), lemma.trim().to_string()))
+        .collect()
+}
+
+/// possible lemmas of a regularly inflected word, the likelier first
+fn candidates(word: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+    let mut push = |candidate: String| {
+        if candidate.chars().count() > 1 && !candidates.contains(&candidate) {
+            candidates.push(candidate);
+        }
+    };
+
+    for (suffix, replacement) in [("ies", "y"), ("ied", "y"), ("ier", "y"), ("iest", "y")] {
+        if let Some(stem) = word.strip_suffix(suffix) {
+            push(format!("{stem}{replacement}"));
+        }
+    }
+
+    if !word.ends_with("ss") {
+        if let Some(stem) = word.strip_suffix('s') {
+            push(stem.to_string());
+        }
+    }
+    if let Some(stem) = word.strip_suffix("es") {
+        push(stem.to_string());
+    }
+
+    for suffix in ["ed", "ing", "er", "est"] {
+        if let Some(stem) = word.strip_suffix(suffix) {
+            push(stem.to_string());
+            push(format!("{stem}e"));
+            // stopped => stop, bigger => big
+            let mut chars = stem.chars().rev();
+            if let (Some(last), Some(prev)) = (chars.next(), chars.next()) {
+                if last == prev && !"aeiou".contains(last) {
+                    push(stem[..stem.len() - last.len_utf8()].to_string());
+                }
+            }
+        }
+    }
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_exceptions_ok() {
+        let exceptions = parse_exceptions(EXCEPTIONS);
+        assert_eq!(exceptions.get("mice"), Some(&"mouse".to_string()));
+        assert_eq!(exceptions.get("took"), Some(&"take".to_string()));
+        assert!(!exceptions.contains_key("left"));
+        assert!(exceptions.keys().all(|x| !x.starts_with('#')));
+    }
+
+    #[test]
+    fn candidates_ok() {
+        assert_eq!(candidates("cats"), vec!["cat"]);
+        assert_eq!(candidates("flies"), vec!["fly", "flie", "fli"]);
+        assert!(candidates("boxes").contains(&"box".to_string()));
+        assert!(candidates("liked").contains(&"like".to_string()));
+        assert!(candidates("stopped").contains(&"stop".to_string()));
+        assert!(candidates("running").contains(&"run".to_string()));
+        assert!(candidates("making").contains(&"make".to_string()));
+        assert!(candidates("bigger").contains(&"big".to_string()));
+        assert!(candidates("happiest").contains(&"happy".to_string()));
+        assert!(candidates("glass").is_empty());
+        assert!(candidates("cat").is_empty());
+    }
+}
@@ -0,0 +1,40 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Form of a queried word definitions are stored under: NFC, lowercase, single spaces
+/// and no punctuation around it, e.g. " Cat’s! " => "cat's"
+pub fn normalize(word: &str) -> String {
+    let word: String = word
+        .nfc()
+        .map(|c| match c {
+            // typographic apostrophes as used in the headwords of oxford
+            '\u{2019}' | '\u{2018}' | '\u{02bc}' => '\'',
+            c => c,
+        })
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    word.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_ok() {
+        assert_eq!(normalize("Cats"), "cats");
+        assert_eq!(normalize("  cat \n"), "cat");
+        assert_eq!(normalize("\"cat!?\""), "cat");
+        assert_eq!(normalize("Mother-in-Law"), "mother-in-law");
+        assert_eq!(normalize("o’clock"), "o'clock");
+        assert_eq!(normalize("ice   cream"), "ice cream");
+        // decomposed é
+        assert_eq!(normalize("Cafe\u{301}"), "caf\u{e9}");
+        assert_eq!(normalize("ÅNGSTRÖM"), "ångström");
+        assert_eq!(normalize(" ... "), "");
+    }
+}
@@ -1,35 +1,83 @@
 use crate::env::Env;
 use common_rs::EnvStore;
 use db::database::get_database_client;
+use db::health::{self, DbHealth};
 use db::repository::Repository;
+use error::DictionaryErr;
+use futures::{Stream, StreamExt, TryStreamExt};
+use jobs::JobQueue;
+use mongodb::Database;
+use refresher::Refresher;
 use rpc::dictionary::{
     dictionary_server::{Dictionary, DictionaryServer},
-    GetAudioRequest, GetAudioResponse, GetWordDefinitionsRequest, GetWordDefinitionsResponse,
-    InvalidateWordRequest, InvalidateWordResponse,
+    AudioChunk, EnqueueWordRequest, EnqueueWordResponse, GetAudioRequest, GetAudioResponse,
+    GetImageRequest, GetImageResponse, GetWordDefinitionsBatchRequest, GetWordDefinitionsRequest,
+    GetWordDefinitionsResponse, GetWordRelationsRequest, GetWordRelationsResponse,
+    InvalidateWordRequest, InvalidateWordResponse, StreamAudioRequest, VerifyRequest,
+    VerifyResponse, WatchWordEvent, WatchWordRequest, WordDefinitionsResult,
 };
+use service::WordDefinitions;
+use sources::Sources;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
 use tonic::{transport::Server, Request, Response, Status};
+use transcoder::Transcoder;
+use wordnet::WordNet;
 
-mod cloudflare_bypasser;
+mod archive;
+mod blob;
+mod completeness;
 mod db;
 mod dtos;
 mod env;
+mod error;
+mod fetcher;
+mod fixtures;
+mod gc;
+mod http;
+mod jobs;
+mod lemmatizer;
 mod models;
 mod oxford;
+mod refresher;
 mod service;
+mod sources;
+mod transcoder;
 mod utils;
+mod verify;
 mod vocabulary;
+mod wiktionary;
+mod wordnet;
 
 #[cfg(test)]
 mod tests;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DictionaryService {
     pub repository: Repository,
+    pub sources: Sources,
+    pub wordnet: Option<Arc<WordNet>>,
+    pub refresher: Refresher,
+    pub jobs: JobQueue,
+    pub db_health: DbHealth,
 }
 
 impl DictionaryService {
-    pub fn new(repository: Repository) -> DictionaryService {
-        DictionaryService { repository }
+    pub fn new(
+        repository: Repository,
+        sources: Sources,
+        wordnet: Option<WordNet>,
+        jobs: JobQueue,
+    ) -> DictionaryService {
+        DictionaryService {
+            repository,
+            sources,
+            wordnet: wordnet.map(Arc::new),
+            refresher: Refresher::default(),
+            jobs,
+            db_health: DbHealth::default(),
+        }
     }
 }
 
@@ -46,54 +94,186 @@ impl Dictionary for DictionaryService {
         let response = self.get_word_definitions(word).await;
 
         match response {
-            Ok(val) => Ok(Response::new(val)),
-            Err(err) => {
-                println!("{:?}", err);
-                let err = format!("{:?}", err);
-                return Err(Status::new(tonic::Code::Internal, err));
+            Ok(WordDefinitions::Found(val)) => Ok(Response::new(val)),
+            Ok(WordDefinitions::NotFound(details)) => {
+                Err(DictionaryErr::WordNotFound(details).into())
             }
+            Err(err) => Err(DictionaryErr::from(err).into()),
         }
     }
 
+    type GetWordDefinitionsBatchStream =
+        Pin<Box<dyn Stream<Item = Result<WordDefinitionsResult, Status>> + Send>>;
+
+    async fn get_word_definitions_batch(
+        &self,
+        request: Request<GetWordDefinitionsBatchRequest>,
+    ) -> Result<Response<Self::GetWordDefinitionsBatchStream>, Status> {
+        let words = service::batch_words(request.into_inner().words);
+        if words.len() > service::MAX_BATCH_SIZE {
+            return Err(DictionaryErr::InvalidArgument {
+                field: "words",
+                reason: format!(
+                    "at most {} words can be requested at once",
+                    service::MAX_BATCH_SIZE
+                ),
+            }
+            .into());
+        }
+
+        println!("Requesting {} words", words.len());
+
+        let service = self.clone();
+        let results = futures::stream::iter(words)
+            .map(move |word| {
+                let service = service.clone();
+                async move { Ok(service.lookup_word(word).await) }
+            })
+            .buffer_unordered(Env::vars().batch_concurrency.max(1));
+
+        Ok(Response::new(Box::pin(results)))
+    }
+
+    async fn enqueue_word(
+        &self,
+        request: Request<EnqueueWordRequest>,
+    ) -> Result<Response<EnqueueWordResponse>, Status> {
+        let word = self.resolve(&request.into_inner().word).await;
+        let sources: Vec<_> = self.sources.iter().map(|source| source.name()).collect();
+
+        let job = self
+            .jobs
+            .enqueue(&word, &sources)
+            .await
+            .map_err(DictionaryErr::from)?;
+
+        Ok(Response::new(EnqueueWordResponse {
+            job_id: job.id.map(|id| id.to_hex()).unwrap_or_default(),
+        }))
+    }
+
+    type WatchWordStream = Pin<Box<dyn Stream<Item = Result<WatchWordEvent, Status>> + Send>>;
+
+    async fn watch_word(
+        &self,
+        request: Request<WatchWordRequest>,
+    ) -> Result<Response<Self::WatchWordStream>, Status> {
+        let word = self.resolve(&request.into_inner().word).await;
+
+        println!("Watching {word}");
+
+        let events = jobs::watch(self.clone(), word);
+        Ok(Response::new(Box::pin(events)))
+    }
+
     async fn invalidate_word(
         &self,
         request: Request<InvalidateWordRequest>,
     ) -> Result<Response<InvalidateWordResponse>, Status> {
-        let word = request.into_inner().word;
+        let request = request.into_inner();
 
-        match self.invalidate_word(word).await {
-            Ok(_) => Ok(Response::new(InvalidateWordResponse { success: true })),
-            Err(_) => Ok(Response::new(InvalidateWordResponse { success: false })),
-        }
+        let refreshed = self.invalidate_word(request.word, request.sources).await?;
+
+        Ok(Response::new(InvalidateWordResponse {
+            success: true,
+            refreshed,
+        }))
     }
 
     async fn get_audio(
         &self,
         request: Request<GetAudioRequest>,
     ) -> Result<Response<GetAudioResponse>, Status> {
+        let request = request.into_inner();
+        let format = request.format();
+        let id = request.id;
+
+        let audio = self.get_audio(id, format).await?;
+        Ok(Response::new(audio))
+    }
+
+    type StreamAudioStream = Pin<Box<dyn Stream<Item = Result<AudioChunk, Status>> + Send>>;
+
+    async fn stream_audio(
+        &self,
+        request: Request<StreamAudioRequest>,
+    ) -> Result<Response<Self::StreamAudioStream>, Status> {
+        let request = request.into_inner();
+
+        let format = request.format();
+        let chunks = self
+            .stream_audio(request.id, request.range, format, request.if_none_match)
+            .await?;
+
+        let chunks = chunks.map_err(Status::from);
+        Ok(Response::new(Box::pin(chunks)))
+    }
+
+    async fn get_image(
+        &self,
+        request: Request<GetImageRequest>,
+    ) -> Result<Response<GetImageResponse>, Status> {
         let id = request.into_inner().id;
 
-        let audio = self.get_audio(id.clone()).await.map_err(Status::internal)?;
+        let image = self.get_image(id).await?;
+        Ok(Response::new(image))
+    }
 
-        match audio {
-            Some(a) => Ok(Response::new(a)),
-            None => Err(Status::not_found(format!("Not found: {}", id))),
-        }
+    async fn get_word_relations(
+        &self,
+        request: Request<GetWordRelationsRequest>,
+    ) -> Result<Response<GetWordRelationsResponse>, Status> {
+        let word = request.into_inner().word;
+
+        let relations = self.get_word_relations(&word)?;
+        Ok(Response::new(relations))
+    }
+
+    async fn verify(
+        &self,
+        request: Request<VerifyRequest>,
+    ) -> Result<Response<VerifyResponse>, Status> {
+        let repair = request.into_inner().repair;
+
+        println!("Verifying{}", if repair { " and repairing" } else { "" });
+
+        let report = self.verify(repair).await.map_err(DictionaryErr::from)?;
+        Ok(Response::new(report))
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 {
+        return run_command(&args[1..]).await;
+    }
+
     let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
 
+    // serving once the db answers and is migrated, see `health::monitor`
     health_reporter
-        .set_serving::<DictionaryServer<DictionaryService>>()
+        .set_not_serving::<DictionaryServer<DictionaryService>>()
         .await;
 
-    let db = get_database_client(Env::vars().db_connection_uri, "dictionary").await;
-    let repository = Repository::new(db);
+    let db = get_database_client(Env::vars().db_connection_uri, "dictionary").await?;
+    let sources = Sources::new(&db, Env::vars().dictionary_sources);
+    let repository = new_repository(db.clone());
+    let blobs = repository.blobs.clone();
+    let wordnet = Env::vars().wordnet_dir.map(|dir| {
+        WordNet::load(Path::new(&dir))
+            .unwrap_or_else(|err| panic!("Could not load WordNet from {}. Err: {:?}", dir, err))
+    });
 
-    let service = DictionaryService::new(repository);
+    let jobs = JobQueue::new(&db);
+    let service = DictionaryService::new(repository, sources, wordnet, jobs);
+    jobs::spawn_workers(&service);
+    tokio::spawn(health::monitor::<DictionaryServer<DictionaryService>>(
+        db,
+        blobs,
+        service.db_health.clone(),
+        health_reporter,
+    ));
 
     let addr = "0.0.0.0:80".parse()?;
     Server::builder()
@@ -104,3 +284,152 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// maintenance commands, e.g. `dictionary-ms import-wiktionary ./kaikki.org-dictionary-English.jsonl`
+/// or `dictionary-ms gc-audio` to remove the audio no definition refers to,
+/// `dictionary-ms transcode-audio` converts the audio stored before transcoding,
+/// `dictionary-ms requeue-dead-jobs` retries the scrape jobs which ran out of attempts,
+/// `dictionary-ms record-cassettes ./src/tests/cassettes cat` records the responses the scrapers get for test fixtures,
+/// `dictionary-ms reparse [source]...` rebuilds the source docs from the archived pages after a parser change,
+/// `dictionary-ms check-fixtures ./src/tests/cassettes ./src/tests/snapshots` re-parses them and diffs the output
+async fn run_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    // offline, does not need the db
+    if let [command, cassette_dir, snapshot_dir, rest @ ..] = args {
+        if command == "check-fixtures" {
+            return check_fixtures(
+                cassette_dir,
+                snapshot_dir,
+                rest.iter().any(|x| x == "--update"),
+            )
+            .await;
+        }
+    }
+
+    let db = get_database_client(Env::vars().db_connection_uri, "dictionary").await?;
+
+    match args {
+        [command, path, rest @ ..] if command == "import-wiktionary" => {
+            let lang_code = rest.first().map_or("en", |x| x.as_str());
+            println!("Importing {path} ({lang_code})");
+            let progress = wiktionary::Importer::new(&db, lang_code)
+                .import(Path::new(path))
+                .await
+                .map_err(|err| err.to_string())?;
+            println!(
+                "Import finished. Imported: {}, skipped: {}",
+                progress.imported, progress.skipped
+            );
+            Ok(())
+        }
+        [command] if command == "gc-audio" => {
+            let sources = Sources::new(&db, None);
+            let repository = new_repository(db.clone());
+            let report = gc::collect_audio(&repository, &sources)
+                .await
+                .map_err(|err| format!("{:?}", err))?;
+            println!(
+                "Removed {} audio docs and {} blobs, reclaimed {} bytes",
+                report.audio_removed, report.blobs_removed, report.bytes_reclaimed
+            );
+            Ok(())
+        }
+        [command] if command == "transcode-audio" => {
+            let updated = new_repository(db)
+                .transcode_missing_audio()
+                .await
+                .map_err(|err| format!("{:?}", err))?;
+            println!("Transcoding finished. Updated: {}", updated);
+            Ok(())
+        }
+        [command, dir, words @ ..] if command == "record-cassettes" && !words.is_empty() => {
+            http::record_into(dir)?;
+            let progress = sources::Progress::default();
+            for word in words {
+                if let Err(err) = oxford::scrape(word, &progress).await {
+                    println!("Oxford could not scrape {word}: {:?}", err);
+                }
+                if let Err(err) = vocabulary::scrape(word, &progress).await {
+                    println!("Vocabulary could not scrape {word}: {:?}", err);
+                }
+            }
+            println!("Recorded {} words into {dir}", words.len());
+            Ok(())
+        }
+        [command, names @ ..] if command == "reparse" => {
+            let enabled = (!names.is_empty()).then(|| names.to_vec());
+            let sources = Sources::new(&db, enabled);
+            let repository = new_repository(db.clone());
+            for source in sources.iter() {
+                let report = source
+                    .reparse_all(&repository)
+                    .await
+                    .map_err(|err| format!("{:?}", err))?;
+                println!(
+                    "Reparsed {}: {}, skipped: {}, failed: {}",
+                    source.name(),
+                    report.reparsed,
+                    report.skipped,
+                    report.failed
+                );
+            }
+            Ok(())
+        }
+        [command] if command == "requeue-dead-jobs" => {
+            let requeued = JobQueue::new(&db)
+                .requeue_dead()
+                .await
+                .map_err(|err| format!("{:?}", err))?;
+            println!("Requeued {} jobs", requeued);
+            Ok(())
+        }
+        _ => Err(
+            "Usage: dictionary-ms import-wiktionary <path-to-jsonl> [lang-code]\n       \
+                  dictionary-ms gc-audio\n       \
+                  dictionary-ms transcode-audio\n       \
+                  dictionary-ms requeue-dead-jobs\n       \
+                  dictionary-ms reparse [source]...\n       \
+                  dictionary-ms record-cassettes <dir> <word>...\n       \
+                  dictionary-ms check-fixtures <cassette-dir> <snapshot-dir> [--update]"
+                .into(),
+        ),
+    }
+}
+
+async fn check_fixtures(
+    cassette_dir: &str,
+    snapshot_dir: &str,
+    update: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = fixtures::check(cassette_dir, snapshot_dir, update)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    for (url, empty) in &report.incomplete {
+        println!("Incomplete {url}, empty: {}", empty.join(", "));
+    }
+    for (url, changes) in &report.changed {
+        println!("Changed {url}");
+        for change in changes {
+            println!("    {change}");
+        }
+    }
+    println!(
+        "Checked {} pages, changed: {}, incomplete: {}, snapshots written: {}",
+        report.checked,
+        report.changed.len(),
+        report.incomplete.len(),
+        report.written
+    );
+
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err("The parsers output differs from the snapshots or is incomplete".into())
+    }
+}
+
+fn new_repository(db: Database) -> Repository {
+    let blobs = blob::from_env(&db);
+    let transcoder = Transcoder::new(Env::vars().ffmpeg_path);
+    Repository::new(db, blobs, transcoder)
+}
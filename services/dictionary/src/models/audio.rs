@@ -3,10 +3,34 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
 pub struct AudioDoc {
-    // TODO experiment with local storage systems
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub word: String,
     pub content_type: String, // video/mp4 , audio/mpeg
-    pub bytes: Vec<u8>,
+    /// key of the bytes in the `BlobStore`
+    pub hash: String,
+    pub size: i64,
+    /// the audio transcoded to the canonical formats, empty when transcoding failed
+    #[serde(default)]
+    pub variants: Vec<AudioVariant>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct AudioVariant {
+    /// `transcoder::Format::name`
+    pub format: String,
+    pub content_type: String,
+    pub hash: String,
+    pub size: i64,
+}
+
+impl AudioDoc {
+    /// content type, hash and size of the variant in the given format, of the original when there is none
+    pub fn blob(&self, format: Option<&str>) -> (&str, &str, i64) {
+        format
+            .and_then(|format| self.variants.iter().find(|v| v.format == format))
+            .map_or((&self.content_type, &self.hash, self.size), |v| {
+                (&v.content_type, &v.hash, v.size)
+            })
+    }
 }
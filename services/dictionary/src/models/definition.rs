@@ -1,9 +1,138 @@
+use chrono::{DateTime, Duration, Utc};
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct DefinitionDoc {
     pub word: String,
-    pub vocabulary_id: Option<ObjectId>,
-    pub oxford_id: Option<ObjectId>,
+    /// ids of the source docs keyed by the source name
+    #[serde(default)]
+    pub sources: HashMap<String, Option<ObjectId>>,
+    /// sources which could not provide the word, retried once the miss expires
+    #[serde(default)]
+    pub misses: HashMap<String, Miss>,
+    /// similar words suggested by the sources that do not know the word
+    #[serde(default)]
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Miss {
+    pub reason: MissReason,
+    /// error message when the lookup failed
+    pub detail: Option<String>,
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MissReason {
+    /// the source does not know the word
+    NotFound,
+    /// scraping or storing failed, e.g. network or parse error
+    Failed,
+}
+
+impl Miss {
+    pub fn is_expired(&self, ttl: Duration) -> bool {
+        self.checked_at + ttl < Utc::now()
+    }
+}
+
+impl DefinitionDoc {
+    /// `true` when the source has to be looked up (again)
+    pub fn needs_lookup(&self, source: &str, ttl: Duration) -> bool {
+        if let Some(Some(_)) = self.sources.get(source) {
+            return false;
+        }
+        match self.misses.get(source) {
+            Some(miss) => miss.is_expired(ttl),
+            None => true,
+        }
+    }
+
+    /// `true` when the last lookup of the source failed, e.g. on a network error
+    pub fn has_failed(&self, source: &str) -> bool {
+        matches!(
+            self.misses.get(source),
+            Some(Miss {
+                reason: MissReason::Failed,
+                ..
+            })
+        )
+    }
+
+    /// `true` when none of the given sources has the word and all of them said it does not exist
+    pub fn is_true_miss<'a>(&self, sources: impl IntoIterator<Item = &'a str>) -> bool {
+        let mut sources = sources.into_iter().peekable();
+        sources.peek().is_some()
+            && sources.all(|source| {
+                !matches!(self.sources.get(source), Some(Some(_)))
+                    && matches!(
+                        self.misses.get(source),
+                        Some(Miss {
+                            reason: MissReason::NotFound,
+                            ..
+                        })
+                    )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn miss(reason: MissReason, age: Duration) -> Miss {
+        Miss {
+            reason,
+            detail: None,
+            checked_at: Utc::now() - age,
+        }
+    }
+
+    #[test]
+    fn needs_lookup_ok() {
+        let ttl = Duration::hours(1);
+        let mut doc = DefinitionDoc::default();
+        doc.sources
+            .insert("oxford".to_string(), Some(ObjectId::new()));
+        // legacy docs stored failed sources as `None`
+        doc.sources.insert("vocabulary".to_string(), None);
+        doc.misses.insert(
+            "wiktionary".to_string(),
+            miss(MissReason::NotFound, Duration::minutes(5)),
+        );
+        doc.misses.insert(
+            "other".to_string(),
+            miss(MissReason::Failed, Duration::hours(2)),
+        );
+
+        assert!(!doc.needs_lookup("oxford", ttl));
+        assert!(doc.needs_lookup("vocabulary", ttl));
+        assert!(!doc.needs_lookup("wiktionary", ttl));
+        assert!(doc.needs_lookup("other", ttl));
+        assert!(doc.needs_lookup("unknown", ttl));
+    }
+
+    #[test]
+    fn is_true_miss_ok() {
+        let mut doc = DefinitionDoc::default();
+        assert!(!doc.is_true_miss([]));
+
+        doc.misses.insert(
+            "oxford".to_string(),
+            miss(MissReason::NotFound, Duration::zero()),
+        );
+        doc.misses.insert(
+            "vocabulary".to_string(),
+            miss(MissReason::Failed, Duration::zero()),
+        );
+
+        assert!(doc.is_true_miss(["oxford"]));
+        assert!(!doc.is_true_miss(["oxford", "vocabulary"]));
+        assert!(!doc.is_true_miss(["oxford", "wiktionary"]));
+    }
 }
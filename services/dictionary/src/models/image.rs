@@ -0,0 +1,12 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct ImageDoc {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// sha256 of the bytes, the same picture is stored only once
+    pub hash: String,
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
@@ -0,0 +1,12 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+/// Word form and the headword its definitions are stored under,
+/// a headword has a doc of its own with `form == lemma`
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct InflectionDoc {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub form: String,
+    pub lemma: String,
+}
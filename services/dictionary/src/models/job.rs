@@ -0,0 +1,61 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Queued lookup of a word in all enabled sources
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct JobDoc {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub word: String,
+    pub status: JobStatus,
+    pub attempts: i32,
+    /// progress of the lookup keyed by the source name
+    #[serde(default)]
+    pub sources: HashMap<String, SourceProgress>,
+    pub last_error: Option<String>,
+    /// the job is not picked up sooner, used for the retry backoff
+    pub run_at: DateTime,
+    /// a running job is picked up again after this, e.g. when its worker died
+    pub locked_until: Option<DateTime>,
+    pub updated_at: DateTime,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    /// out of attempts, stays in the collection as the dead-letter queue
+    Dead,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SourceProgress {
+    pub stage: Stage,
+    /// id of the source doc once the source is done and has the word
+    pub definition: Option<ObjectId>,
+    /// error message when the source failed
+    pub error: Option<String>,
+}
+
+impl SourceProgress {
+    pub fn new(stage: Stage) -> Self {
+        SourceProgress {
+            stage,
+            definition: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Queued,
+    Scraping,
+    DownloadingAudio,
+    Done,
+    Failed,
+}
@@ -0,0 +1,14 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// Migration which was applied or is being applied
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MigrationDoc {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub version: i32,
+    pub name: String,
+    pub started_at: DateTime,
+    /// `None` while it is running
+    pub applied_at: Option<DateTime>,
+}
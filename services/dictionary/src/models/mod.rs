@@ -1,5 +1,11 @@
 pub mod audio;
 pub mod definition;
+pub mod image;
+pub mod inflection;
+pub mod job;
+pub mod migration;
 pub mod oxford;
+pub mod raw_page;
 pub mod shared;
 pub mod vocabulary;
+pub mod wiktionary;
@@ -1,7 +1,7 @@
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 
-use super::shared::{Pronunciation, PronunciationDoc};
+use super::shared::PronunciationDoc;
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct DefinitionDoc {
@@ -20,7 +20,21 @@ pub struct DefinitionDoc {
     pub word_origin: String,
     pub idioms: Vec<Idiom>,
     pub phrasal_verbs: Vec<WordRef>,
-    pub veb_forms: Vec<VebForm>,
+    /// read from `veb_forms` too, the docs may be served before the `rename_veb_forms` migration ran
+    #[serde(alias = "veb_forms", default)]
+    pub verb_forms: Vec<VerbForm>,
+    /// refs of all the entries of the headword in the dictionary's order, this one included
+    #[serde(default)]
+    pub homographs: Vec<String>,
+    /// sections which came out empty when scraped, the markup may have changed
+    #[serde(default)]
+    pub incomplete: Vec<String>,
+    /// when the page was scraped, `None` for docs stored before it was tracked
+    #[serde(default)]
+    pub scraped_at: Option<DateTime>,
+    /// `DictionarySource::VERSION` of the scraper which stored the doc
+    #[serde(default)]
+    pub source_version: Option<i32>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -62,8 +76,9 @@ pub struct Idiom {
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
-pub struct VebForm {
+pub struct VerbForm {
+    pub form: String,
     pub note: String,
     pub word: String,
-    pub pronunciations: Vec<Pronunciation>,
+    pub pronunciations: Vec<PronunciationDoc>,
 }
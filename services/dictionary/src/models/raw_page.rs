@@ -0,0 +1,22 @@
+use mongodb::bson::{oid::ObjectId, Binary, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// Page a scraper fetched, kept so the definitions can be re-parsed without the network
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RawPageDoc {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub source: String,
+    /// ref of the source doc built from the page, e.g. `oxford_ref`
+    pub word_ref: String,
+    /// which of the pages of the definition it is, e.g. "definition" or "examples"
+    pub kind: String,
+    pub url: String,
+    /// url the request ended up at after following redirects
+    pub final_url: String,
+    pub status: i32,
+    pub content_type: Option<String>,
+    /// gzipped body
+    pub body: Binary,
+    pub fetched_at: DateTime,
+}
@@ -14,6 +14,12 @@ pub struct Audio {
     pub bytes: Vec<u8>,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct Image {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct PronunciationDoc {
     pub variant: PronunciationVariant,
@@ -1,5 +1,5 @@
 use super::shared::PronunciationDoc;
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -15,6 +15,15 @@ pub struct DefinitionDoc {
     pub long_description: String,
     pub definitions: Vec<SubDefinition>,
     pub examples: Vec<Example>,
+    /// sections which came out empty when scraped, the markup may have changed
+    #[serde(default)]
+    pub incomplete: Vec<String>,
+    /// when the page was scraped, `None` for docs stored before it was tracked
+    #[serde(default)]
+    pub scraped_at: Option<DateTime>,
+    /// `DictionarySource::VERSION` of the scraper which stored the doc
+    #[serde(default)]
+    pub source_version: Option<i32>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -28,7 +37,7 @@ pub struct Example {
 pub struct SubDefinition {
     pub variant: WordVariant,
     pub description: String,
-    pub image: Option<Image>,
+    pub image_id: Option<ObjectId>,
     pub short_examples: Vec<String>,
     pub synonyms: Vec<String>,
 }
@@ -42,9 +51,3 @@ pub enum WordVariant {
     Adverb,
     Other(String),
 }
-
-#[derive(Debug, Deserialize, Serialize, Clone, Default)]
-pub struct Image {
-    pub bytes: Vec<u8>,
-    pub format: String,
-}
@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct DefinitionDoc {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub word: String,
+    /// keyed by the byte offset of the entry in the dump, so re-importing the same line is idempotent
+    pub entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct Entry {
+    pub pos: String,
+    pub etymology: String,
+    pub senses: Vec<Sense>,
+    pub sounds: Vec<Sound>,
+    pub forms: Vec<Form>,
+    pub translations: Vec<Translation>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct Sense {
+    pub glosses: Vec<String>,
+    pub examples: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct Sound {
+    pub ipa: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct Form {
+    pub form: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct Translation {
+    pub lang: String,
+    pub code: String,
+    pub word: String,
+    pub sense: String,
+}
+
+/// Progress of a dump import, used to resume it after interruption
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ImportDoc {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub offset: u64,
+    pub imported: u64,
+    pub skipped: u64,
+    pub done: bool,
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub updated_at: DateTime<Utc>,
+}
@@ -1,35 +1,96 @@
+use crate::archive::{RawPage, DEFINITION_PAGE};
+use crate::completeness::Completeness;
+use crate::fetcher::{FetchErr, Fetcher, RetryPolicy};
+use crate::models::job::Stage;
 use crate::models::oxford::DefinitionGroup;
 use crate::models::oxford::Idiom;
 use crate::models::oxford::SubDefinition;
-use crate::models::oxford::VebForm;
 use crate::models::oxford::WordRef;
 use crate::models::shared::Audio;
 use crate::models::shared::Pronunciation;
 use crate::models::shared::PronunciationVariant;
+use crate::sources::Progress;
 use crate::utils::AttrUtils;
 use crate::utils::Css;
 use crate::utils::CssUtils;
 use reqwest::StatusCode;
 use scraper::{Element, ElementRef, Html};
+use serde::Serialize;
+use std::time::Duration;
+use tonic::Code;
+
+pub static DEFINITION_BASE_URL: &str =
+    "https://www.oxfordlearnersdictionaries.com/definition/english";
+
+const FETCH_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 4,
+    base_delay: Duration::from_secs(1),
+    max_delay: Duration::from_secs(30),
+};
+
+/// more entries of a headword are not followed
+const MAX_HOMOGRAPHS: usize = 10;
+
+/// the entry the word leads to and its homographs, e.g. "bear" the noun and "bear" the verb,
+/// ordered as in the dictionary
+pub async fn scrape(word: &str, progress: &Progress) -> Result<Vec<Definition>, ScrapeErr> {
+    let first = scrape_entry(&get_word_url(word), progress).await?;
+
+    let mut entries = Vec::new();
+    for oxford_ref in homograph_refs(&first.oxford_ref, &first.similar_results) {
+        match scrape_entry(&get_word_url(&oxford_ref), progress).await {
+            Ok(entry) => entries.push(entry),
+            Err(err) => println!("Could not scrape the homograph {oxford_ref}: {:?}", err),
+        }
+    }
+    entries.push(first);
+    entries.sort_by_key(|x| split_homograph(&x.oxford_ref).map_or(0, |(_, n)| n));
 
-static DEFINITION_BASE_URL: &str = "https://www.oxfordlearnersdictionaries.com/definition/english";
+    Ok(entries)
+}
 
-pub async fn scrape(word: &str) -> Result<Definition, ScrapeErr> {
-    let response = reqwest::Client::new()
-        .get(get_word_url(word))
-        .send()
-        .await
-        .map_err(ScrapeErr::GetRequestErr)?;
+/// refs of the other entries of the same headword among the other results of an entry,
+/// e.g. "bear_2" and "bear_3" of "bear_1"
+fn homograph_refs(oxford_ref: &str, similar_results: &[WordRef]) -> Vec<String> {
+    let base = match split_homograph(oxford_ref) {
+        Some((base, _)) => base,
+        None => return Vec::new(),
+    };
 
-    let status_code = response.status();
+    let mut refs: Vec<String> = similar_results
+        .iter()
+        .map(|x| x.oxford_ref.as_str())
+        .filter(|x| *x != oxford_ref && split_homograph(x).is_some_and(|(b, _)| b == base))
+        .map(|x| x.to_string())
+        .collect();
+    refs.sort_by_key(|x| split_homograph(x).map_or(0, |(_, n)| n));
+    refs.dedup();
+    refs.truncate(MAX_HOMOGRAPHS);
+    refs
+}
 
-    let html = response.text().await.map_err(ScrapeErr::GetRequestErr)?;
+/// "bear_2" => ("bear", 2)
+fn split_homograph(oxford_ref: &str) -> Option<(&str, u32)> {
+    let (base, n) = oxford_ref.rsplit_once('_')?;
+    Some((base, n.parse().ok()?))
+}
 
-    match status_code {
+async fn scrape_entry(url: &str, progress: &Progress) -> Result<Definition, ScrapeErr> {
+    let response = Fetcher::new(FETCH_POLICY)
+        .get(url)
+        .await
+        .map_err(ScrapeErr::FetchErr)?;
+
+    let html = response.text();
+
+    match response.status {
         StatusCode::OK => {
             let scraped = scrape_html(&html);
+            progress.report(Stage::DownloadingAudio);
             let pros = load_audio(&scraped.pronunciations).await;
-            Ok(scraped.into_definition(pros))
+            let verb_forms = load_verb_forms_audio(&scraped.verb_forms).await;
+            let page = RawPage::new(DEFINITION_PAGE, url, &response);
+            Ok(scraped.into_definition(pros, verb_forms, vec![page]))
         }
         StatusCode::NOT_FOUND => Err(ScrapeErr::NotFound(parse_not_found_page(&html))),
         other => Err(ScrapeErr::UnexpectedHtmlStatusCode(other)),
@@ -37,7 +98,7 @@ pub async fn scrape(word: &str) -> Result<Definition, ScrapeErr> {
 }
 
 // gotta do it separately to any async operations since html is not Send
-fn scrape_html(html: &str) -> Scraped {
+pub fn scrape_html(html: &str) -> Scraped {
     let html = Html::parse_document(html);
     Scraped {
         oxford_ref: get_id(&html),
@@ -52,8 +113,7 @@ fn scrape_html(html: &str) -> Scraped {
         word_origin: get_word_origin(&html),
         idioms: get_idioms(&html),
         phrasal_verbs: get_phrasal_verbs(&html),
-        // TODO
-        veb_forms: Vec::new(),
+        verb_forms: get_verb_forms(&html),
     }
 }
 
@@ -206,39 +266,91 @@ fn get_phrasal_verbs(html: &Html) -> Vec<WordRef> {
 
 fn get_pronunciations(html: &Html) -> Vec<ScrapedPronunciation> {
     html.select(&Css(".webtop >.phonetics > div").into())
+        .map(parse_pronunciation)
+        .collect()
+}
+
+fn parse_pronunciation(el: ElementRef) -> ScrapedPronunciation {
+    let variant = match el.value().attr("geo") {
+        Some("br") => PronunciationVariant::Uk,
+        Some("n_am") => PronunciationVariant::Usa,
+        Some(_other) => PronunciationVariant::Other,
+        None => PronunciationVariant::Other,
+    };
+
+    let ipa_str = el
+        .select(&Css("span.phon").into())
+        .next()
+        .map_or_else(|| "", |el| el.text().next().unwrap_or_default())
+        .trim()
+        .to_string();
+
+    let audio_src = el
+        .select(&Css("div[data-src-mp3]").into())
+        .next()
+        .map_or_else(
+            || "",
+            |el| el.value().attr("data-src-mp3").unwrap_or_default(),
+        )
+        .to_string();
+
+    ScrapedPronunciation {
+        variant,
+        ipa_str,
+        audio_src,
+    }
+}
+
+fn get_verb_forms(html: &Html) -> Vec<ScrapedVerbForm> {
+    html.select(&Css(".verb_forms_table tr.verb_form").into())
         .map(|el| {
-            let variant = match el.value().attr("geo") {
-                Some("br") => PronunciationVariant::Uk,
-                Some("n_am") => PronunciationVariant::Usa,
-                Some(_other) => PronunciationVariant::Other,
-                None => PronunciationVariant::Other,
-            };
-
-            let ipa_str = el
-                .select(&Css("span.phon").into())
-                .next()
-                .map_or_else(|| "", |el| el.text().next().unwrap_or_default())
-                .trim()
-                .to_string();
+            let form = el.value().attr("form").unwrap_or_default().to_string();
+            let note = el.select(&Css(".vf_prefix").into()).join_text();
 
-            let audio_src = el
-                .select(&Css("div[data-src-mp3]").into())
+            // the word is the only text directly in the cell, next to the prefix span
+            let word = el
+                .select(&Css("td.verb_form").into())
                 .next()
                 .map_or_else(
-                    || "",
-                    |el| el.value().attr("data-src-mp3").unwrap_or_default(),
+                    || "".to_string(),
+                    |td| {
+                        td.children()
+                            .filter_map(|node| node.value().as_text().map(|t| t.to_string()))
+                            .collect::<Vec<String>>()
+                            .join("")
+                    },
                 )
+                .trim()
                 .to_string();
 
-            ScrapedPronunciation {
-                variant,
-                ipa_str,
-                audio_src,
+            let pronunciations = el
+                .select(&Css(".verb_phons .phonetics > div").into())
+                .map(parse_pronunciation)
+                .collect();
+
+            ScrapedVerbForm {
+                form,
+                note,
+                word,
+                pronunciations,
             }
         })
         .collect()
 }
 
+/// "(comparative huskier, superlative huskiest)" => huskier, huskiest
+fn parse_inflections(inflections: &str) -> Vec<String> {
+    inflections
+        .split(',')
+        .filter_map(|part| part.split_whitespace().last())
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_string()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
 fn get_inflections(html: &Html) -> String {
     html.select(&Css(".inflections").into()).next().map_or_else(
         || "".to_string(),
@@ -259,6 +371,10 @@ fn get_definitions(html: &Html) -> Vec<DefinitionGroup> {
     if elements.is_empty() {
         elements = html.select(&Css(".senses_multiple").into()).collect();
     }
+    // entries with one sense, e.g. many homographs, the idioms have their own
+    if elements.is_empty() {
+        elements = html.select(&Css(".entry > .sense_single").into()).collect();
+    }
 
     elements
         .iter()
@@ -363,11 +479,17 @@ fn get_similar_results(html: &Html) -> Vec<WordRef> {
         .collect()
 }
 
-fn parse_not_found_page(html: &str) -> NotFound {
-    let _html = Html::parse_document(html);
-    NotFound {
-        similar_words: Vec::new(), // TODO
-    }
+pub fn parse_not_found_page(html: &str) -> NotFound {
+    let html = Html::parse_document(html);
+    let similar_words = html
+        .select(&Css(".result-list li a").into())
+        .map(|mut el| WordRef {
+            oxford_ref: el.href_last_part(),
+            word: el.join_text().trim().to_string(),
+        })
+        .collect();
+
+    NotFound { similar_words }
 }
 
 async fn load_audio(scraped: &[ScrapedPronunciation]) -> Vec<Pronunciation> {
@@ -383,39 +505,31 @@ async fn load_audio(scraped: &[ScrapedPronunciation]) -> Vec<Pronunciation> {
     futures::future::join_all(pronunciations).await
 }
 
-async fn get_audio(audio_url: &str) -> Option<Audio> {
-    let response = reqwest::get(audio_url).await;
-
-    match response {
-        Ok(response) => {
-            let status_code = response.status();
-            match status_code {
-                StatusCode::OK => {
-                    let content_type = match response.headers().get(reqwest::header::CONTENT_TYPE) {
-                        Some(val) => match val.to_str() {
-                            Ok(val) => Some(val.to_string()),
-                            Err(_) => None,
-                        },
-                        None => None,
-                    };
+async fn load_verb_forms_audio(scraped: &[ScrapedVerbForm]) -> Vec<VerbForm> {
+    let forms = scraped.iter().map(|f| async move {
+        VerbForm {
+            form: f.form.clone(),
+            note: f.note.clone(),
+            word: f.word.clone(),
+            pronunciations: load_audio(&f.pronunciations).await,
+        }
+    });
 
-                    let bytes = response.bytes().await;
+    futures::future::join_all(forms).await
+}
 
-                    match (content_type, bytes) {
-                        (Some(content_type), Ok(bytes)) => {
-                            let audio = Audio {
-                                content_type,
-                                bytes: bytes.into_iter().collect(),
-                            };
-                            Some(audio)
-                        }
-                        _ => None,
-                    }
-                }
-                _ => None,
-            }
-        }
-        Err(_) => None,
+async fn get_audio(audio_url: &str) -> Option<Audio> {
+    let response = Fetcher::new(RetryPolicy::ASSETS)
+        .get(audio_url)
+        .await
+        .ok()?;
+
+    match response.status {
+        StatusCode::OK => Some(Audio {
+            content_type: response.content_type()?,
+            bytes: response.bytes,
+        }),
+        _ => None,
     }
 }
 
@@ -436,9 +550,27 @@ pub struct Definition {
     pub word_origin: String,
     pub idioms: Vec<Idiom>,
     pub phrasal_verbs: Vec<WordRef>,
-    pub veb_forms: Vec<VebForm>,
+    pub verb_forms: Vec<VerbForm>,
+    pub completeness: Completeness,
+    /// pages the definition was parsed from, empty when re-parsed from them
+    pub pages: Vec<RawPage>,
 }
 
+impl Definition {
+    /// words listed as the inflections of the headword and its verb forms
+    pub fn inflected_forms(&self) -> Vec<String> {
+        let mut forms = parse_inflections(&self.inflections);
+        forms.extend(
+            self.verb_forms
+                .iter()
+                .filter(|x| x.form != "root")
+                .map(|x| x.word.clone()),
+        );
+        forms
+    }
+}
+
+#[derive(Serialize)]
 pub struct Scraped {
     pub oxford_ref: String,
     pub header: String,
@@ -452,11 +584,68 @@ pub struct Scraped {
     pub word_origin: String,
     pub idioms: Vec<Idiom>,
     pub phrasal_verbs: Vec<WordRef>,
-    pub veb_forms: Vec<VebForm>,
+    pub verb_forms: Vec<ScrapedVerbForm>,
 }
 
 impl Scraped {
-    fn into_definition(self, pros: Vec<Pronunciation>) -> Definition {
+    pub fn completeness(&self) -> Completeness {
+        let senses: Vec<_> = self
+            .definitions
+            .iter()
+            .flat_map(|g| &g.definitions)
+            .collect();
+        Completeness::default()
+            .required("oxford_ref", !self.oxford_ref.is_empty())
+            .required("header", !self.header.is_empty())
+            .required("definitions", !senses.is_empty())
+            .expected(
+                "definitions.description",
+                senses.iter().all(|d| !d.description.is_empty()),
+            )
+            .expected("word_variant", !self.word_variant.is_empty())
+            .expected("pronunciations", !self.pronunciations.is_empty())
+            .expected(
+                "pronunciations.ipa",
+                self.pronunciations.iter().all(|p| !p.ipa_str.is_empty()),
+            )
+            .expected(
+                "pronunciations.audio",
+                self.pronunciations.iter().all(|p| !p.audio_src.is_empty()),
+            )
+    }
+
+    /// definition with no audio downloaded, e.g. when re-parsing an archived page
+    pub fn into_definition_without_audio(self) -> Definition {
+        let without_audio = |pros: &[ScrapedPronunciation]| -> Vec<Pronunciation> {
+            pros.iter()
+                .map(|p| Pronunciation {
+                    variant: p.variant.clone(),
+                    ipa_str: p.ipa_str.clone(),
+                    audio: None,
+                })
+                .collect()
+        };
+        let pros = without_audio(&self.pronunciations);
+        let verb_forms = self
+            .verb_forms
+            .iter()
+            .map(|f| VerbForm {
+                form: f.form.clone(),
+                note: f.note.clone(),
+                word: f.word.clone(),
+                pronunciations: without_audio(&f.pronunciations),
+            })
+            .collect();
+        self.into_definition(pros, verb_forms, Vec::new())
+    }
+
+    fn into_definition(
+        self,
+        pros: Vec<Pronunciation>,
+        verb_forms: Vec<VerbForm>,
+        pages: Vec<RawPage>,
+    ) -> Definition {
+        let completeness = self.completeness();
         Definition {
             oxford_ref: self.oxford_ref,
             header: self.header,
@@ -470,7 +659,9 @@ impl Scraped {
             word_origin: self.word_origin,
             idioms: self.idioms,
             phrasal_verbs: self.phrasal_verbs,
-            veb_forms: self.veb_forms,
+            verb_forms,
+            completeness,
+            pages,
         }
     }
 }
@@ -480,19 +671,56 @@ pub struct NotFound {
     pub similar_words: Vec<WordRef>,
 }
 
+#[derive(Serialize)]
 pub struct ScrapedPronunciation {
     pub variant: PronunciationVariant,
     pub ipa_str: String,
     pub audio_src: String,
 }
 
+#[derive(Serialize)]
+pub struct ScrapedVerbForm {
+    pub form: String,
+    pub note: String,
+    pub word: String,
+    pub pronunciations: Vec<ScrapedPronunciation>,
+}
+
+pub struct VerbForm {
+    /// root, thirdps, past, pastpart, prespart
+    pub form: String,
+    pub note: String,
+    pub word: String,
+    pub pronunciations: Vec<Pronunciation>,
+}
+
 #[derive(Debug)]
 pub enum ScrapeErr {
-    GetRequestErr(reqwest::Error),
+    FetchErr(FetchErr),
     UnexpectedHtmlStatusCode(StatusCode),
     NotFound(NotFound),
 }
 
+impl ScrapeErr {
+    pub fn code(&self) -> Code {
+        match self {
+            ScrapeErr::FetchErr(err) => err.code(),
+            ScrapeErr::UnexpectedHtmlStatusCode(status) if status.is_server_error() => {
+                Code::Unavailable
+            }
+            ScrapeErr::UnexpectedHtmlStatusCode(_) => Code::Internal,
+            ScrapeErr::NotFound(_) => Code::NotFound,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ScrapeErr::FetchErr(err) => err.retry_after(),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -530,6 +758,46 @@ mod tests {
         assert_eq!(pros.len(), 2);
     }
 
+    #[test]
+    fn get_verb_forms_ok() {
+        let html = parse_html(TestHtml::Oxford(OxfordHtml::Take1));
+        let forms = get_verb_forms(&html);
+
+        assert_eq!(forms.len(), 5);
+        assert_eq!(&forms[0].form, "root");
+        assert_eq!(&forms[0].note, "present simple I / you / we / they");
+        assert_eq!(&forms[0].word, "take");
+        assert_eq!(&forms[1].form, "thirdps");
+        assert_eq!(&forms[1].word, "takes");
+        assert_eq!(&forms[2].form, "past");
+        assert_eq!(&forms[2].word, "took");
+        assert_eq!(&forms[3].form, "pastpart");
+        assert_eq!(&forms[3].note, "past participle");
+        assert_eq!(&forms[3].word, "taken");
+        assert_eq!(&forms[4].form, "prespart");
+        assert_eq!(&forms[4].note, "-ing form");
+        assert_eq!(&forms[4].word, "taking");
+
+        let pros = &forms[2].pronunciations;
+        assert_eq!(pros.len(), 2);
+        assert_eq!(pros[0].variant, PronunciationVariant::Uk);
+        assert_eq!(&pros[0].ipa_str, "/tʊk/");
+        assert_eq!(
+            &pros[0].audio_src,
+            "https://www.oxfordlearnersdictionaries.com/media/english/uk_pron/t/too/took_/took__gb_1.mp3"
+        );
+        assert_eq!(pros[1].variant, PronunciationVariant::Usa);
+
+        let html = parse_html(TestHtml::Oxford(OxfordHtml::Fling1));
+        let forms = get_verb_forms(&html);
+        assert_eq!(forms.len(), 5);
+        assert_eq!(&forms[3].word, "flung");
+        assert_eq!(&forms[3].pronunciations[1].ipa_str, "/flʌŋ/");
+
+        let html = parse_html(TestHtml::Oxford(OxfordHtml::Cat1));
+        assert_eq!(get_verb_forms(&html).len(), 0);
+    }
+
     #[test]
     fn get_similar_results_ok() {
         let html = parse_html(TestHtml::Oxford(OxfordHtml::Cat1));
@@ -544,6 +812,49 @@ mod tests {
         assert_eq!("the Cheshire Cat", results[17].word);
     }
 
+    #[test]
+    fn homograph_refs_ok() {
+        let word_ref = |oxford_ref: &str| WordRef {
+            oxford_ref: oxford_ref.to_string(),
+            word: String::new(),
+        };
+        let similar_results = vec![
+            word_ref("bear_3"),
+            word_ref("bear_1"),
+            word_ref("bearable"),
+            word_ref("polar-bear_1"),
+            word_ref("bear_2"),
+        ];
+
+        assert_eq!(
+            homograph_refs("bear_1", &similar_results),
+            vec!["bear_2", "bear_3"]
+        );
+        assert_eq!(
+            homograph_refs("bear_2", &similar_results),
+            vec!["bear_1", "bear_3"]
+        );
+        assert!(homograph_refs("bearable", &similar_results).is_empty());
+        assert_eq!(split_homograph("a_priori"), None);
+    }
+
+    #[test]
+    fn parse_not_found_page_ok() {
+        let html = r#"
+            <div id="didyoumean">Did you mean:</div>
+            <ul class="result-list">
+                <li><a href="https://www.oxfordlearnersdictionaries.com/definition/english/cat_1">cat</a></li>
+                <li><a href="https://www.oxfordlearnersdictionaries.com/definition/english/catty">catty</a></li>
+            </ul>
+        "#;
+
+        let not_found = parse_not_found_page(html);
+        assert_eq!(not_found.similar_words.len(), 2);
+        assert_eq!(&not_found.similar_words[0].oxford_ref, "cat_1");
+        assert_eq!(&not_found.similar_words[0].word, "cat");
+        assert_eq!(&not_found.similar_words[1].word, "catty");
+    }
+
     #[test]
     fn get_word_variant_ok() {
         let html = parse_html(TestHtml::Oxford(OxfordHtml::Cat1));
@@ -631,6 +942,8 @@ mod tests {
         let html = parse_html(TestHtml::Oxford(OxfordHtml::Husky1));
         let inflections = get_inflections(&html);
         assert_eq!(&inflections, "(comparative huskier, superlative huskiest)");
+        assert_eq!(parse_inflections(&inflections), vec!["huskier", "huskiest"]);
+        assert!(parse_inflections("").is_empty());
     }
 
     #[test]
@@ -725,4 +1038,35 @@ mod tests {
             "She flung the letter down onto the table."
         );
     }
+
+    /// replays `src/tests/cassettes`, only the uk audio of the headword is recorded
+    #[tokio::test]
+    async fn scrape_replay() {
+        crate::http::replay_from("./src/tests/cassettes");
+        let progress = Progress::default();
+
+        let entries = scrape("cat", &progress).await.expect("should be recorded");
+        let refs: Vec<_> = entries.iter().map(|x| x.oxford_ref.as_str()).collect();
+        assert_eq!(refs, vec!["cat_1", "cat_2"]);
+        assert_eq!(entries[1].header, "Cat");
+        assert_eq!(
+            entries[1].pages[0].url,
+            format!("{DEFINITION_BASE_URL}/cat_2")
+        );
+
+        let definition = &entries[0];
+        assert_eq!(
+            definition.pages[0].final_url,
+            format!("{DEFINITION_BASE_URL}/cat_1")
+        );
+        assert_eq!(definition.header, "cat");
+        assert!(!definition.definitions.is_empty());
+        let audio: Vec<_> = definition
+            .pronunciations
+            .iter()
+            .filter_map(|p| p.audio.as_ref())
+            .collect();
+        assert_eq!(audio.len(), 1);
+        assert_eq!(audio[0].content_type, "audio/mpeg");
+    }
 }
@@ -0,0 +1,47 @@
+use crate::db::repository::Repository;
+use crate::sources::{Lookup, Progress, Source};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// Re-scrapes stale source docs in the background, at most one refresh of a word per source at a time
+#[derive(Debug, Clone, Default)]
+pub struct Refresher {
+    running: Arc<Mutex<HashSet<(&'static str, String)>>>,
+}
+
+impl Refresher {
+    pub fn spawn(&self, repository: Repository, source: Arc<dyn Source>, word: String) {
+        let key = (source.name(), word.clone());
+        if !self.running.lock().unwrap().insert(key.clone()) {
+            return;
+        }
+
+        let running = self.running.clone();
+        tokio::spawn(async move {
+            refresh(&repository, source.as_ref(), &word).await;
+            running.lock().unwrap().remove(&key);
+        });
+    }
+}
+
+/// scrapes the word again and points its definition to the new doc,
+/// the old doc is kept when the source does not provide a new one
+pub async fn refresh(repository: &Repository, source: &dyn Source, word: &str) -> Lookup {
+    let lookup = source.refresh(repository, word, &Progress::default()).await;
+
+    match &lookup {
+        Lookup::Found(id) => {
+            if let Err(err) = repository.set_source(word, source.name(), id).await {
+                println!("Could not refresh {word} from {}: {:?}", source.name(), err);
+                return Lookup::failed(err);
+            }
+        }
+        _ => println!(
+            "Could not refresh {word} from {}: {:?}",
+            source.name(),
+            lookup
+        ),
+    }
+
+    lookup
+}
@@ -1,106 +1,604 @@
 use crate::db::database::DbErr;
 use crate::dtos::GetWordDefinitionsResponseBuilder;
+use crate::env::Env;
+use crate::error::DictionaryErr;
+use crate::lemmatizer::normalize;
 use crate::models::audio::AudioDoc;
-use crate::models::definition::DefinitionDoc;
-use crate::{oxford, vocabulary, DictionaryService};
+use crate::models::definition::{DefinitionDoc, Miss, MissReason};
+use crate::refresher;
+use crate::sources::{Lookup, Progress, Source};
+use crate::transcoder::Format;
+use crate::verify;
+use crate::DictionaryService;
+use chrono::{Duration, Utc};
+use common_rs::db::with_retry;
+use common_rs::EnvStore;
+use futures::stream::BoxStream;
+use futures::StreamExt;
 use mongodb::bson::oid::ObjectId;
-use rpc::dictionary::{GetAudioResponse, GetWordDefinitionsResponse};
+use rpc::dictionary::{
+    word_definitions_result, AudioChunk, AudioFormat, AudioInfo, ByteRange, DanglingId,
+    GetAudioResponse, GetImageResponse, GetWordDefinitionsResponse, GetWordRelationsResponse,
+    LookupError, SourceDefinition, SourceDocRef, VerifyResponse, WordDefinitionsResult,
+    WordNotFound,
+};
+
+const AUDIO_CHUNK_SIZE: usize = 64 * 1024;
+pub const MAX_BATCH_SIZE: usize = 1000;
+
+pub enum WordDefinitions {
+    Found(GetWordDefinitionsResponse),
+    /// every enabled source says the word does not exist
+    NotFound(WordNotFound),
+}
 
 impl DictionaryService {
-    pub async fn get_word_definitions(
+    /// the headword the definitions of a query are stored under, e.g. "cat" for " Cats ".
+    /// Only the irregular forms are resolved while the db is down
+    pub async fn resolve(&self, query: &str) -> String {
+        let word = normalize(query);
+        if !self.db_health.is_up() {
+            return self.repository.lemmatizer.irregular_lemma(&word);
+        }
+        self.repository.lemmatizer.lemma(&word).await
+    }
+
+    /// `query` is reported back as is along with the lemma it resolved to.
+    /// The definitions are scraped without being stored while the db is unreachable
+    pub async fn get_word_definitions(&self, query: String) -> Result<WordDefinitions, DbErr> {
+        let word = self.resolve(&query).await;
+        if !self.db_health.is_up() {
+            return self.scrape_unstored(query, word).await;
+        }
+        let definition = match with_retry(|| self.repository.get_definition(&word)).await {
+            Err(err) if err.is_unreachable() => {
+                println!("Serving {word} unstored: {err}");
+                return self.scrape_unstored(query, word).await;
+            }
+            definition => definition?,
+        };
+
+        let definition = definition.unwrap_or_else(|| DefinitionDoc {
+            word: word.to_string(),
+            ..Default::default()
+        });
+        let definition = self.complete_definition(definition).await?;
+
+        if definition.is_true_miss(self.sources.iter().map(|source| source.name())) {
+            return Ok(WordDefinitions::NotFound(WordNotFound {
+                word: query,
+                suggestions: definition.suggestions,
+                lemma: word,
+            }));
+        }
+
+        let max_age = Duration::seconds(Env::vars().definition_max_age_secs);
+        let mut definitions = Vec::new();
+        for source in self.sources.iter() {
+            if let Some(Some(id)) = definition.sources.get(source.name()) {
+                // served as is, the refreshed one is returned next time
+                if source.is_stale(id, max_age).await {
+                    self.refresher
+                        .spawn(self.repository.clone(), source.clone(), word.clone());
+                }
+                let mut entries = with_retry(|| source.get_definitions(id)).await?;
+                if entries.is_empty() {
+                    entries = self.relink(&word, source.as_ref(), id).await?;
+                }
+                definitions.extend(entries);
+            }
+        }
+
+        let response = GetWordDefinitionsResponseBuilder::new(&query, definitions)
+            .lemma(&word)
+            .suggestions(definition.suggestions)
+            .build();
+        Ok(WordDefinitions::Found(response))
+    }
+
+    /// definitions scraped from every source right now, nothing is stored,
+    /// fails as unreachable when no source found the word and not all of them said it does not exist
+    async fn scrape_unstored(&self, query: String, word: String) -> Result<WordDefinitions, DbErr> {
+        let progress = Progress::default();
+        let scrapes = self
+            .sources
+            .iter()
+            .map(|source| source.scrape_unstored(&word, &progress));
+        let results = futures::future::join_all(scrapes).await;
+
+        let mut definitions = Vec::new();
+        let mut suggestions: Vec<String> = Vec::new();
+        let mut true_miss = true;
+        for result in results {
+            match result {
+                // scraped but the source can not serve it unstored
+                Ok(entries) if entries.is_empty() => true_miss = false,
+                Ok(entries) => definitions.extend(entries),
+                Err(Lookup::NotFound { suggestions: found }) => {
+                    for suggestion in found {
+                        if !suggestions.contains(&suggestion) {
+                            suggestions.push(suggestion);
+                        }
+                    }
+                }
+                Err(_) => true_miss = false,
+            }
+        }
+
+        if definitions.is_empty() && true_miss {
+            return Ok(WordDefinitions::NotFound(WordNotFound {
+                word: query,
+                suggestions,
+                lemma: word,
+            }));
+        }
+        if definitions.is_empty() {
+            return Err(DbErr::Unreachable);
+        }
+
+        let response = GetWordDefinitionsResponseBuilder::new(&query, definitions)
+            .lemma(&word)
+            .suggestions(suggestions)
+            .build();
+        Ok(WordDefinitions::Found(response))
+    }
+
+    /// looks the word up again in the source the stored id of which points to no doc,
+    /// the id is dropped when the lookup fails, so it is retried on the next request
+    async fn relink(
+        &self,
+        word: &str,
+        source: &dyn Source,
+        dangling: &ObjectId,
+    ) -> Result<Vec<SourceDefinition>, DbErr> {
+        println!("Dangling {} id {dangling} of {word}", source.name());
+
+        match source
+            .lookup(&self.repository, word, &Progress::default())
+            .await
+        {
+            Lookup::Found(id) => {
+                self.repository.set_source(word, source.name(), &id).await?;
+                source.get_definitions(&id).await
+            }
+            _ => {
+                self.repository.unset_source(word, source.name()).await?;
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// resolves a word of a batch, a failure is reported in the result instead of failing the whole batch
+    pub async fn lookup_word(&self, word: String) -> WordDefinitionsResult {
+        let result = match self.get_word_definitions(word.clone()).await {
+            Ok(WordDefinitions::Found(val)) => word_definitions_result::Result::Found(val),
+            Ok(WordDefinitions::NotFound(details)) => {
+                word_definitions_result::Result::NotFound(details)
+            }
+            Err(err) => {
+                let err = DictionaryErr::from(err);
+                println!("{:?}", err);
+                word_definitions_result::Result::Error(LookupError {
+                    code: err.code() as i32,
+                    message: err.to_string(),
+                })
+            }
+        };
+
+        WordDefinitionsResult {
+            word,
+            result: Some(result),
+        }
+    }
+
+    /// drops the definition when no `sources` are given, so it is resolved again on the next request.
+    /// Re-scrapes given sources right away otherwise and returns the ones which were refreshed,
+    /// fails with the first failure when every one of them failed
+    pub async fn invalidate_word(
         &self,
         word: String,
-    ) -> Result<GetWordDefinitionsResponse, DbErr> {
-        let definition = self.repository.get_definition(&word).await?;
+        sources: Vec<String>,
+    ) -> Result<Vec<String>, DictionaryErr> {
+        let word = self.resolve(&word).await;
+        if sources.is_empty() {
+            self.repository.delete_definition(word.as_str()).await?;
+            return Ok(Vec::new());
+        }
+
+        let sources = sources
+            .iter()
+            .map(|name| {
+                self.sources
+                    .get(name)
+                    .ok_or_else(|| DictionaryErr::InvalidArgument {
+                        field: "sources",
+                        reason: format!("unknown dictionary source {name}"),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let refreshed = sources.into_iter().map(|source| async {
+            let lookup = refresher::refresh(&self.repository, source.as_ref(), &word).await;
+            (source.name(), lookup)
+        });
+        let lookups = futures::future::join_all(refreshed).await;
+
+        let all_failed = lookups
+            .iter()
+            .all(|(_, lookup)| matches!(lookup, Lookup::Failed { .. }));
+        if let Some((
+            name,
+            Lookup::Failed {
+                reason,
+                code,
+                retry_after,
+            },
+        )) = lookups.first()
+        {
+            if all_failed {
+                return Err(DictionaryErr::SourceFailed {
+                    name: name.to_string(),
+                    reason: reason.clone(),
+                    code: *code,
+                    retry_after: *retry_after,
+                });
+            }
+        }
+
+        Ok(lookups
+            .into_iter()
+            .filter(|(_, lookup)| matches!(lookup, Lookup::Found(_)))
+            .map(|(name, _)| name.to_string())
+            .collect())
+    }
+
+    pub async fn get_audio(
+        &self,
+        id: String,
+        format: AudioFormat,
+    ) -> Result<GetAudioResponse, DictionaryErr> {
+        let (audio, content_type, bytes) = self.load_audio(id, format).await?;
+
+        Ok(GetAudioResponse {
+            word: audio.word,
+            content_type,
+            bytes,
+        })
+    }
+
+    /// the first chunk carries the `AudioInfo`, the rest is read from the blob store
+    /// a chunk at a time while the stream is polled. Only the info is sent when the hash
+    /// is one of `if_none_match`, "*" matching any
+    pub async fn stream_audio(
+        &self,
+        id: String,
+        range: Option<ByteRange>,
+        format: AudioFormat,
+        if_none_match: Vec<String>,
+    ) -> Result<BoxStream<'static, Result<AudioChunk, DictionaryErr>>, DictionaryErr> {
+        let not_found = || DictionaryErr::NotFound {
+            kind: "audio",
+            id: id.clone(),
+        };
+        let audio = self
+            .repository
+            .get_audio(id.clone())
+            .await?
+            .ok_or_else(not_found)?;
+        let (content_type, hash, size) = audio.blob(format_name(format));
+        let (content_type, hash, size) = (content_type.to_string(), hash.to_string(), size as u64);
+        let served = match format_name(format) {
+            Some(name) if audio.variants.iter().any(|v| v.format == name) => format,
+            _ => AudioFormat::Original,
+        };
+
+        if if_none_match
+            .iter()
+            .any(|etag| etag == "*" || *etag == hash)
+        {
+            let info = AudioInfo {
+                content_type,
+                hash,
+                size,
+                format: served.into(),
+                not_modified: true,
+                ..Default::default()
+            };
+            let chunk = AudioChunk {
+                info: Some(info),
+                data: Vec::new(),
+            };
+            return Ok(futures::stream::once(async { Ok(chunk) }).boxed());
+        }
+
+        let (start, length) = match range {
+            Some(range) => {
+                resolve_range(&range, size).ok_or(DictionaryErr::RangeNotSatisfiable { size })?
+            }
+            None => (0, size),
+        };
+        let end = start + length;
 
-        let _definition = match definition {
-            Some(val) => val,
-            None => self.create_definition(&word).await?,
+        // read before answering so that a missing blob is a not found rather than a broken stream
+        let first_length = length.min(AUDIO_CHUNK_SIZE as u64);
+        let data = self
+            .repository
+            .get_blob_range(&hash, start, first_length)
+            .await?
+            .ok_or_else(not_found)?;
+        let first = AudioChunk {
+            info: Some(AudioInfo {
+                content_type,
+                hash: hash.clone(),
+                size,
+                start,
+                length,
+                format: served.into(),
+                not_modified: false,
+            }),
+            data,
         };
 
-        let voc_definition = self.repository.get_voc_definition(&word).await;
-        let oxford_definition = self.repository.get_ox_definition(&word).await;
-        let response =
-            GetWordDefinitionsResponseBuilder::new(&word, voc_definition, oxford_definition)
-                .build();
-        Ok(response)
+        let repository = self.repository.clone();
+        let rest = futures::stream::try_unfold(start + first_length, move |offset| {
+            let repository = repository.clone();
+            let hash = hash.clone();
+            async move {
+                if offset >= end {
+                    return Ok(None);
+                }
+                let length = (end - offset).min(AUDIO_CHUNK_SIZE as u64);
+                let data = repository
+                    .get_blob_range(&hash, offset, length)
+                    .await?
+                    .unwrap_or_default();
+                // the blob is shorter than its doc says
+                if data.is_empty() {
+                    return Ok(None);
+                }
+                let next = offset + data.len() as u64;
+                Ok(Some((AudioChunk { info: None, data }, next)))
+            }
+        });
+
+        Ok(futures::stream::once(async { Ok(first) })
+            .chain(rest)
+            .boxed())
     }
 
-    pub async fn invalidate_word(&self, word: String) -> Result<(), DbErr> {
-        self.repository.delete_definition(word.as_str()).await
+    /// the audio doc with the content type and the bytes of the format
+    async fn load_audio(
+        &self,
+        id: String,
+        format: AudioFormat,
+    ) -> Result<(AudioDoc, String, Vec<u8>), DictionaryErr> {
+        let not_found = || DictionaryErr::NotFound {
+            kind: "audio",
+            id: id.clone(),
+        };
+        let audio = self
+            .repository
+            .get_audio(id.clone())
+            .await?
+            .ok_or_else(not_found)?;
+
+        let (content_type, hash, _) = audio.blob(format_name(format));
+        let content_type = content_type.to_string();
+        let bytes = self
+            .repository
+            .get_blob(hash)
+            .await?
+            .ok_or_else(not_found)?;
+        Ok((audio, content_type, bytes))
     }
 
-    pub async fn get_audio(&self, id: String) -> Result<Option<GetAudioResponse>, DbErr> {
-        self.repository
-            .get_audio(id)
-            .await
-            .map(|o| o.map(|audio| audio.to_response()))
+    pub async fn get_image(&self, id: String) -> Result<GetImageResponse, DictionaryErr> {
+        let image = self.repository.get_image(id.clone()).await?;
+
+        image
+            .map(|image| GetImageResponse {
+                content_type: image.content_type,
+                bytes: image.bytes,
+            })
+            .ok_or(DictionaryErr::NotFound { kind: "image", id })
     }
 
-    async fn create_definition(&self, word: &str) -> Result<DefinitionDoc, DbErr> {
-        let (vocabulary_id, oxford_id) = tokio::join!(
-            self.get_voc_definition_id(word),
-            self.get_ox_definition_id(word)
-        );
+    pub fn get_word_relations(
+        &self,
+        word: &str,
+    ) -> Result<GetWordRelationsResponse, DictionaryErr> {
+        let wordnet = self
+            .wordnet
+            .as_ref()
+            .ok_or(DictionaryErr::WordNetNotLoaded)?;
+        let senses = wordnet
+            .senses(word)
+            .ok_or_else(|| DictionaryErr::NotFound {
+                kind: "word",
+                id: word.to_string(),
+            })?;
 
-        let definition = DefinitionDoc {
+        Ok(GetWordRelationsResponse {
             word: word.to_string(),
-            vocabulary_id,
-            oxford_id,
-        };
+            senses: senses.into_iter().map(|s| s.into()).collect(),
+        })
+    }
 
-        self.repository.replace_definition(&definition).await?;
+    /// checks the stored definitions refer to existing docs and the docs and audio to have an owner,
+    /// see `verify::verify`
+    pub async fn verify(&self, repair: bool) -> Result<VerifyResponse, DbErr> {
+        let report = verify::verify(&self.repository, &self.sources, repair).await?;
 
-        Ok(definition)
+        Ok(VerifyResponse {
+            dangling_ids: report
+                .dangling
+                .into_iter()
+                .map(|(word, source, id)| DanglingId {
+                    word,
+                    source,
+                    id: id.to_hex(),
+                })
+                .collect(),
+            orphaned_docs: report
+                .orphaned_docs
+                .into_iter()
+                .map(|(source, id)| SourceDocRef {
+                    source,
+                    id: id.to_hex(),
+                })
+                .collect(),
+            orphaned_audio: report.orphaned_audio.iter().map(|id| id.to_hex()).collect(),
+            repaired: report.repaired,
+        })
     }
 
-    /// tries to get from a db, if not found => scrape and store
-    async fn get_voc_definition_id(&self, word: &str) -> Option<ObjectId> {
-        match self.repository.get_voc_definition_id(word).await {
-            Some(id) => Some(id),
-            None => self.create_voc_definition(word).await,
+    /// resolves the enabled sources the definition has not been looked up in yet
+    async fn complete_definition(&self, definition: DefinitionDoc) -> Result<DefinitionDoc, DbErr> {
+        let word = definition.word.clone();
+        let ttl = Duration::seconds(Env::vars().negative_cache_ttl_secs);
+        let missing: Vec<_> = self
+            .sources
+            .iter()
+            .filter(|source| definition.needs_lookup(source.name(), ttl))
+            .map(|source| async {
+                let lookup = source
+                    .lookup(&self.repository, &word, &Progress::default())
+                    .await;
+                (source.name(), lookup)
+            })
+            .collect();
+
+        if missing.is_empty() {
+            return Ok(definition);
         }
+
+        let lookups = futures::future::join_all(missing).await;
+        self.store_lookups(definition, lookups).await
     }
 
-    /// tries to get from a db, if not found => scrape and store
-    async fn get_ox_definition_id(&self, word: &str) -> Option<ObjectId> {
-        match self.repository.get_ox_definition_id(word).await {
-            Some(id) => Some(id),
-            None => self.create_ox_definition(word).await,
+    /// records the outcome of the lookups in the definition and stores it
+    pub async fn store_lookups(
+        &self,
+        mut definition: DefinitionDoc,
+        lookups: Vec<(&str, Lookup)>,
+    ) -> Result<DefinitionDoc, DbErr> {
+        for (name, lookup) in lookups {
+            let miss = match lookup {
+                Lookup::Found(id) => {
+                    definition.sources.insert(name.to_string(), Some(id));
+                    definition.misses.remove(name);
+                    continue;
+                }
+                Lookup::NotFound { suggestions } => {
+                    for suggestion in suggestions {
+                        if !definition.suggestions.contains(&suggestion) {
+                            definition.suggestions.push(suggestion);
+                        }
+                    }
+                    Miss {
+                        reason: MissReason::NotFound,
+                        detail: None,
+                        checked_at: Utc::now(),
+                    }
+                }
+                Lookup::Failed { reason, .. } => Miss {
+                    reason: MissReason::Failed,
+                    detail: Some(reason),
+                    checked_at: Utc::now(),
+                },
+            };
+            definition.sources.remove(name);
+            definition.misses.insert(name.to_string(), miss);
         }
+
+        with_retry(|| self.repository.replace_definition(&definition)).await?;
+
+        Ok(definition)
     }
+}
 
-    async fn create_voc_definition(&self, word: &str) -> Option<ObjectId> {
-        match vocabulary::scrape(word).await {
-            Ok(scraped) => self
-                .repository
-                .save_voc_definition(scraped, word.to_string())
-                .await
-                .ok()
-                .flatten(),
-            Err(_) => None,
+/// trimmed unique words of a batch in the requested order
+pub fn batch_words(words: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    let mut normalized: Vec<String> = Vec::new();
+    for word in words {
+        let key = normalize(&word);
+        if !key.is_empty() && !normalized.contains(&key) {
+            unique.push(word.trim().to_string());
+            normalized.push(key);
         }
     }
+    unique
+}
 
-    async fn create_ox_definition(&self, word: &str) -> Option<ObjectId> {
-        match oxford::scrape(word).await {
-            Ok(scraped) => self
-                .repository
-                .save_ox_definition(scraped, word.to_string())
-                .await
-                .ok()
-                .flatten(),
-            Err(_) => None,
-        }
+fn format_name(format: AudioFormat) -> Option<&'static str> {
+    match format {
+        AudioFormat::Original => None,
+        AudioFormat::Opus => Some(Format::Opus.name()),
+        AudioFormat::Mp3 => Some(Format::Mp3.name()),
     }
 }
 
-impl AudioDoc {
-    fn to_response(&self) -> GetAudioResponse {
-        GetAudioResponse {
-            word: self.word.clone(),
-            content_type: self.content_type.clone(),
-            bytes: self.bytes.clone(),
+/// start and length of the requested bytes, `None` when none of them exist
+fn resolve_range(range: &ByteRange, size: u64) -> Option<(u64, u64)> {
+    match (range.first, range.last) {
+        (Some(first), last) => {
+            if first >= size {
+                return None;
+            }
+            let last = last.map_or(size - 1, |last| last.min(size - 1));
+            if last < first {
+                return None;
+            }
+            Some((first, last - first + 1))
         }
+        (None, Some(suffix)) => {
+            if suffix == 0 || size == 0 {
+                return None;
+            }
+            let length = suffix.min(size);
+            Some((size - length, length))
+        }
+        (None, None) => Some((0, size)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(first: Option<u64>, last: Option<u64>) -> ByteRange {
+        ByteRange { first, last }
+    }
+
+    #[test]
+    fn resolve_range_ok() {
+        assert_eq!(resolve_range(&range(Some(0), Some(9)), 100), Some((0, 10)));
+        assert_eq!(
+            resolve_range(&range(Some(90), Some(200)), 100),
+            Some((90, 10))
+        );
+        assert_eq!(resolve_range(&range(Some(10), None), 100), Some((10, 90)));
+        assert_eq!(resolve_range(&range(None, Some(30)), 100), Some((70, 30)));
+        assert_eq!(resolve_range(&range(None, Some(300)), 100), Some((0, 100)));
+        assert_eq!(resolve_range(&range(None, None), 100), Some((0, 100)));
+    }
+
+    #[test]
+    fn batch_words_ok() {
+        let words = vec![" cat", "dog", "", "cat ", "  ", "Cat"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(batch_words(words), vec!["cat", "dog"]);
+    }
+
+    #[test]
+    fn resolve_range_not_satisfiable() {
+        assert_eq!(resolve_range(&range(Some(100), None), 100), None);
+        assert_eq!(resolve_range(&range(Some(10), Some(5)), 100), None);
+        assert_eq!(resolve_range(&range(None, Some(0)), 100), None);
+        assert_eq!(resolve_range(&range(None, Some(10)), 0), None);
     }
 }
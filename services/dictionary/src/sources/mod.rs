@@ -0,0 +1,574 @@
+use crate::completeness::Completeness;
+use crate::db::database::{find_id, DbErr};
+use crate::db::repository::Repository;
+use crate::env::Env;
+use crate::error::DictionaryErr;
+use crate::models::job::Stage;
+use crate::models::shared::{Pronunciation, PronunciationDoc};
+use chrono::{Duration, Utc};
+use common_rs::EnvStore;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use mongodb::{options::FindOneOptions, Collection, Database};
+use rpc::dictionary::{source_definition::Definition, SourceDefinition};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tonic::Code;
+
+pub mod oxford;
+pub mod vocabulary;
+pub mod wiktionary;
+
+/// Third party dictionary which definitions are scraped and stored in its own collection.
+/// Implement it and register it in `Sources::new` to add a new source.
+#[tonic::async_trait]
+pub trait DictionarySource: Send + Sync {
+    type Scraped: Send;
+    type Doc: Serialize + DeserializeOwned + Send + Sync + Unpin;
+    type Err: Debug + Send;
+
+    /// unique name used in the configuration and as a key in `DefinitionDoc::sources`
+    fn name(&self) -> &'static str;
+
+    fn collection(&self) -> &Collection<Self::Doc>;
+
+    /// filter to find an already stored definition of a searched word
+    fn lookup_filter(&self, word: &str) -> Document {
+        doc! {"searched_word": word}
+    }
+
+    async fn scrape(&self, word: &str, progress: &Progress) -> Result<Self::Scraped, Self::Err>;
+
+    /// stores scraped definition and returns the id of the stored doc
+    async fn persist(
+        &self,
+        repository: &Repository,
+        scraped: Self::Scraped,
+        searched_word: &str,
+    ) -> Result<Option<ObjectId>, DbErr>;
+
+    fn into_response(doc: Self::Doc) -> Definition;
+
+    fn id(doc: &Self::Doc) -> Option<ObjectId>;
+
+    /// docs of the scraped definition as `persist` would store them but without the audio and
+    /// the images, served while the db is down. Nothing by default
+    fn unstored(_scraped: Self::Scraped, _searched_word: &str) -> Vec<Self::Doc> {
+        Vec::new()
+    }
+
+    /// bump when the scraper changes so the stored docs get re-scraped
+    const VERSION: i32 = 1;
+
+    /// `false` when the stored docs can not get stale, e.g. imported ones
+    fn refreshable(&self) -> bool {
+        true
+    }
+
+    /// paths of the fields with ids of the audio the docs refer to, e.g. "pronunciations.audio_id"
+    fn audio_paths(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// suggested words when the error means the source does not know the word, `None` otherwise
+    fn suggestions(_err: &Self::Err) -> Option<Vec<String>> {
+        None
+    }
+
+    /// gRPC code of a lookup failing on the scrape error, see `Lookup::Failed`
+    fn failure_code(_err: &Self::Err) -> Code {
+        Code::Internal
+    }
+
+    /// how long the source asked to wait after failing on the scrape error
+    fn retry_after(_err: &Self::Err) -> Option<std::time::Duration> {
+        None
+    }
+
+    /// which sections of the scraped page came out empty, nothing is checked by default
+    fn completeness(_scraped: &Self::Scraped) -> Completeness {
+        Completeness::default()
+    }
+
+    /// headwords of the scraped page and their inflected forms, see `Lemmatizer::learn`
+    fn inflections(_scraped: &Self::Scraped) -> Vec<(String, Vec<String>)> {
+        Vec::new()
+    }
+
+    /// docs returned for the stored one in the response, in order,
+    /// e.g. all the homographs of an entry
+    async fn entries(&self, doc: Self::Doc) -> Result<Vec<Self::Doc>, DbErr> {
+        Ok(vec![doc])
+    }
+
+    /// rebuilds the stored doc of `word_ref` from its archived pages with the current parser,
+    /// `false` when skipped, e.g. when the source does not archive pages
+    async fn reparse(&self, _repository: &Repository, _word_ref: &str) -> Result<bool, DbErr> {
+        Ok(false)
+    }
+}
+
+/// Result of looking up a word in a source
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lookup {
+    Found(ObjectId),
+    NotFound {
+        suggestions: Vec<String>,
+    },
+    /// `code` tells the clients whether and when to retry, e.g. `ResourceExhausted` when rate limited
+    Failed {
+        reason: String,
+        code: Code,
+        retry_after: Option<std::time::Duration>,
+    },
+}
+
+impl Lookup {
+    /// failed on storing the scraped definition
+    pub fn failed(err: DbErr) -> Lookup {
+        let reason = err.to_string();
+        Lookup::Failed {
+            code: DictionaryErr::from(err).code(),
+            reason,
+            retry_after: None,
+        }
+    }
+}
+
+/// Result of re-parsing all the archived pages of a source
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReparseReport {
+    pub reparsed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// Reports the stages of a lookup, e.g. to the job waiting for it
+#[derive(Default)]
+pub struct Progress(Option<Box<dyn Fn(Stage) + Send + Sync>>);
+
+impl Progress {
+    pub fn new(report: impl Fn(Stage) + Send + Sync + 'static) -> Self {
+        Progress(Some(Box::new(report)))
+    }
+
+    pub fn report(&self, stage: Stage) {
+        if let Some(report) = &self.0 {
+            report(stage);
+        }
+    }
+}
+
+/// Type erased `DictionarySource` so sources with different docs can live in one registry
+#[tonic::async_trait]
+pub trait Source: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// see `DictionarySource::refreshable`
+    fn refreshable(&self) -> bool;
+
+    /// tries to get from a db, if not found => scrape and store
+    async fn lookup(&self, repository: &Repository, word: &str, progress: &Progress) -> Lookup;
+
+    /// scrapes and stores even when the word is stored already
+    async fn refresh(&self, repository: &Repository, word: &str, progress: &Progress) -> Lookup;
+
+    /// scrapes without touching the db, see `DictionarySource::unstored`.
+    /// Fails with the `Lookup` the scrape would be recorded as, never `Lookup::Found`
+    async fn scrape_unstored(
+        &self,
+        word: &str,
+        progress: &Progress,
+    ) -> Result<Vec<SourceDefinition>, Lookup>;
+
+    /// `true` when the stored doc is older than `max_age` or was stored by an older scraper
+    async fn is_stale(&self, id: &ObjectId, max_age: Duration) -> bool;
+
+    async fn get_definition(&self, id: &ObjectId) -> Result<Option<SourceDefinition>, DbErr>;
+
+    /// the definition and the ones returned along with it, see `DictionarySource::entries`
+    async fn get_definitions(&self, id: &ObjectId) -> Result<Vec<SourceDefinition>, DbErr>;
+
+    /// ids of the audio referred by any stored doc
+    async fn referenced_audio(&self) -> Result<HashSet<ObjectId>, DbErr>;
+
+    /// rebuilds the stored docs from the archived pages, see `DictionarySource::reparse`
+    async fn reparse_all(&self, repository: &Repository) -> Result<ReparseReport, DbErr>;
+
+    /// which of the ids belong to a stored doc
+    async fn existing(&self, ids: &[ObjectId]) -> Result<HashSet<ObjectId>, DbErr>;
+
+    /// ids of the stored docs which are neither `referenced` nor returned along with a referenced one
+    async fn unreachable(&self, referenced: &HashSet<ObjectId>) -> Result<Vec<ObjectId>, DbErr>;
+
+    async fn delete(&self, ids: &[ObjectId]) -> Result<u64, DbErr>;
+}
+
+#[tonic::async_trait]
+impl<T: DictionarySource> Source for T {
+    fn name(&self) -> &'static str {
+        DictionarySource::name(self)
+    }
+
+    fn refreshable(&self) -> bool {
+        DictionarySource::refreshable(self)
+    }
+
+    async fn lookup(&self, repository: &Repository, word: &str, progress: &Progress) -> Lookup {
+        if let Some(id) = find_id(self.collection(), self.lookup_filter(word)).await {
+            return Lookup::Found(id);
+        }
+
+        self.refresh(repository, word, progress).await
+    }
+
+    async fn refresh(&self, repository: &Repository, word: &str, progress: &Progress) -> Lookup {
+        progress.report(Stage::Scraping);
+        match self.scrape(word, progress).await {
+            Ok(scraped)
+                if !T::completeness(&scraped).is_acceptable(Env::vars().min_completeness) =>
+            {
+                let completeness = T::completeness(&scraped);
+                println!(
+                    "Rejected incomplete {word} from {}, empty: {:?}, required: {:?}",
+                    self.name(),
+                    completeness.empty,
+                    completeness.missing_required
+                );
+                Lookup::Failed {
+                    reason: format!(
+                        "Incomplete scrape, empty: {}",
+                        completeness.empty.join(", ")
+                    ),
+                    code: Code::Internal,
+                    retry_after: None,
+                }
+            }
+            Ok(scraped) => {
+                for (lemma, forms) in T::inflections(&scraped) {
+                    if let Err(err) = repository.lemmatizer.learn(&lemma, &forms).await {
+                        println!("Could not store the inflections of {lemma}: {:?}", err);
+                    }
+                }
+                match self.persist(repository, scraped, word).await {
+                    Ok(Some(id)) => Lookup::Found(id),
+                    Ok(None) => Lookup::Failed {
+                        reason: "Stored doc has no id".to_string(),
+                        code: Code::Internal,
+                        retry_after: None,
+                    },
+                    Err(err) => Lookup::failed(err),
+                }
+            }
+            Err(err) => match T::suggestions(&err) {
+                Some(suggestions) => Lookup::NotFound { suggestions },
+                None => {
+                    println!("Could not scrape {word} from {}: {:?}", self.name(), err);
+                    Lookup::Failed {
+                        reason: format!("{:?}", err),
+                        code: T::failure_code(&err),
+                        retry_after: T::retry_after(&err),
+                    }
+                }
+            },
+        }
+    }
+
+    async fn scrape_unstored(
+        &self,
+        word: &str,
+        progress: &Progress,
+    ) -> Result<Vec<SourceDefinition>, Lookup> {
+        // served even when incomplete, it is scraped again once the db is back
+        match self.scrape(word, progress).await {
+            Ok(scraped) => Ok(T::unstored(scraped, word)
+                .into_iter()
+                .map(|doc| SourceDefinition {
+                    source: self.name().to_string(),
+                    definition: Some(T::into_response(doc)),
+                })
+                .collect()),
+            Err(err) => Err(match T::suggestions(&err) {
+                Some(suggestions) => Lookup::NotFound { suggestions },
+                None => Lookup::Failed {
+                    reason: format!("{:?}", err),
+                    code: T::failure_code(&err),
+                    retry_after: T::retry_after(&err),
+                },
+            }),
+        }
+    }
+
+    async fn is_stale(&self, id: &ObjectId, max_age: Duration) -> bool {
+        if !DictionarySource::refreshable(self) {
+            return false;
+        }
+
+        let freshness = self
+            .collection()
+            .clone_with_type::<Freshness>()
+            .find_one(
+                doc! {"_id": id},
+                FindOneOptions::builder()
+                    .projection(doc! {"scraped_at": 1, "source_version": 1})
+                    .build(),
+            )
+            .await;
+
+        match freshness {
+            Ok(Some(freshness)) => freshness.is_stale(T::VERSION, max_age),
+            // missing doc is handled by the lookup, failed query is not a reason to scrape
+            _ => false,
+        }
+    }
+
+    async fn get_definition(&self, id: &ObjectId) -> Result<Option<SourceDefinition>, DbErr> {
+        let doc = self
+            .collection()
+            .find_one(doc! {"_id": id}, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+
+        Ok(doc.map(|doc| SourceDefinition {
+            source: self.name().to_string(),
+            definition: Some(T::into_response(doc)),
+        }))
+    }
+
+    async fn get_definitions(&self, id: &ObjectId) -> Result<Vec<SourceDefinition>, DbErr> {
+        let doc = self
+            .collection()
+            .find_one(doc! {"_id": id}, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+        let entries = match doc {
+            Some(doc) => self.entries(doc).await?,
+            None => return Ok(Vec::new()),
+        };
+
+        Ok(entries
+            .into_iter()
+            .map(|doc| SourceDefinition {
+                source: self.name().to_string(),
+                definition: Some(T::into_response(doc)),
+            })
+            .collect())
+    }
+
+    async fn referenced_audio(&self) -> Result<HashSet<ObjectId>, DbErr> {
+        let mut ids = HashSet::new();
+        for path in self.audio_paths() {
+            let values = self
+                .collection()
+                .distinct(*path, None, None)
+                .await
+                .map_err(DbErr::QueryErr)?;
+            ids.extend(values.iter().filter_map(|val| val.as_object_id()));
+        }
+        Ok(ids)
+    }
+
+    async fn reparse_all(&self, repository: &Repository) -> Result<ReparseReport, DbErr> {
+        let mut report = ReparseReport::default();
+        for word_ref in repository.archive.refs(self.name()).await? {
+            match self.reparse(repository, &word_ref).await {
+                Ok(true) => report.reparsed += 1,
+                Ok(false) => report.skipped += 1,
+                Err(err) => {
+                    println!("Could not reparse {word_ref} of {}: {:?}", self.name(), err);
+                    report.failed += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    async fn existing(&self, ids: &[ObjectId]) -> Result<HashSet<ObjectId>, DbErr> {
+        let values = self
+            .collection()
+            .distinct("_id", doc! {"_id": {"$in": ids}}, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+        Ok(values.iter().filter_map(|val| val.as_object_id()).collect())
+    }
+
+    async fn unreachable(&self, referenced: &HashSet<ObjectId>) -> Result<Vec<ObjectId>, DbErr> {
+        let mut reachable = referenced.clone();
+        let mut all = Vec::new();
+
+        let mut cursor = self
+            .collection()
+            .find(doc! {}, None)
+            .await
+            .map_err(DbErr::QueryErr)?;
+        while let Some(doc) = cursor.try_next().await.map_err(DbErr::QueryErr)? {
+            let id = match T::id(&doc) {
+                Some(id) => id,
+                None => continue,
+            };
+            all.push(id);
+            if referenced.contains(&id) {
+                let entries = self.entries(doc).await?;
+                reachable.extend(entries.iter().filter_map(T::id));
+            }
+        }
+
+        Ok(all
+            .into_iter()
+            .filter(|id| !reachable.contains(id))
+            .collect())
+    }
+
+    async fn delete(&self, ids: &[ObjectId]) -> Result<u64, DbErr> {
+        self.collection()
+            .delete_many(doc! {"_id": {"$in": ids}}, None)
+            .await
+            .map(|res| res.deleted_count)
+            .map_err(DbErr::QueryErr)
+    }
+}
+
+/// docs of re-parsed pronunciations referring to the audio already stored for them,
+/// matched by the variant and the ipa, or by the position when the ipa changed
+pub fn keep_audio(stored: &[PronunciationDoc], pros: Vec<Pronunciation>) -> Vec<PronunciationDoc> {
+    pros.into_iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let audio_id = stored
+                .iter()
+                .find(|s| s.variant == p.variant && s.ipa_str == p.ipa_str)
+                .or_else(|| stored.get(i).filter(|s| s.variant == p.variant))
+                .and_then(|s| s.audio_id);
+
+            PronunciationDoc {
+                variant: p.variant,
+                ipa_str: p.ipa_str,
+                audio_id,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct Freshness {
+    scraped_at: Option<DateTime>,
+    source_version: Option<i32>,
+}
+
+impl Freshness {
+    fn is_stale(&self, version: i32, max_age: Duration) -> bool {
+        match (self.scraped_at, self.source_version) {
+            (Some(scraped_at), Some(source_version)) => {
+                source_version < version || scraped_at.to_chrono() + max_age < Utc::now()
+            }
+            // stored before it was tracked
+            _ => true,
+        }
+    }
+}
+
+/// Registry of the enabled dictionary sources
+#[derive(Clone)]
+pub struct Sources(Vec<Arc<dyn Source>>);
+
+impl Sources {
+    /// all known sources in the order they are returned to the client,
+    /// filtered by `enabled` names when provided
+    pub fn new(db: &Database, enabled: Option<Vec<String>>) -> Self {
+        let all: Vec<Arc<dyn Source>> = vec![
+            Arc::new(vocabulary::VocabularySource::new(db)),
+            Arc::new(oxford::OxfordSource::new(db)),
+            Arc::new(wiktionary::WiktionarySource::new(db)),
+        ];
+
+        let sources = match enabled {
+            Some(names) => {
+                if let Some(unknown) = names
+                    .iter()
+                    .find(|name| !all.iter().any(|s| s.name() == name.as_str()))
+                {
+                    panic!("Unknown dictionary source: {}", unknown);
+                }
+
+                all.into_iter()
+                    .filter(|s| names.iter().any(|name| name == s.name()))
+                    .collect()
+            }
+            None => all,
+        };
+
+        Sources(sources)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn Source>> {
+        self.0.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Source>> {
+        self.0.iter().find(|s| s.name() == name)
+    }
+}
+
+impl Debug for Sources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.iter().map(|s| s.name()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::shared::PronunciationVariant;
+
+    #[test]
+    fn freshness_is_stale_ok() {
+        let max_age = Duration::days(30);
+        let freshness = |age: Duration, version: Option<i32>| Freshness {
+            scraped_at: Some(DateTime::from_chrono(Utc::now() - age)),
+            source_version: version,
+        };
+
+        assert!(!freshness(Duration::days(1), Some(1)).is_stale(1, max_age));
+        assert!(freshness(Duration::days(31), Some(1)).is_stale(1, max_age));
+        assert!(freshness(Duration::days(1), Some(1)).is_stale(2, max_age));
+        assert!(freshness(Duration::days(1), None).is_stale(1, max_age));
+    }
+
+    #[test]
+    fn keep_audio_ok() {
+        let (uk, us) = (ObjectId::new(), ObjectId::new());
+        let stored = vec![
+            PronunciationDoc {
+                variant: PronunciationVariant::Uk,
+                ipa_str: "/kæt/".to_string(),
+                audio_id: Some(uk),
+            },
+            PronunciationDoc {
+                variant: PronunciationVariant::Usa,
+                ipa_str: "/kat/".to_string(),
+                audio_id: Some(us),
+            },
+        ];
+        let pro = |variant: PronunciationVariant, ipa_str: &str| Pronunciation {
+            variant,
+            ipa_str: ipa_str.to_string(),
+            audio: None,
+        };
+
+        let kept = keep_audio(
+            &stored,
+            vec![
+                pro(PronunciationVariant::Usa, "/kat/"),
+                // the ipa is parsed differently now
+                pro(PronunciationVariant::Usa, "kæt"),
+                pro(PronunciationVariant::Other, "kæt"),
+            ],
+        );
+        let ids: Vec<_> = kept.iter().map(|x| x.audio_id).collect();
+        assert_eq!(ids, vec![Some(us), Some(us), None]);
+    }
+}
@@ -0,0 +1,361 @@
+use super::{keep_audio, DictionarySource, Progress};
+use crate::archive::DEFINITION_PAGE;
+use crate::completeness::Completeness;
+use crate::db::database::{upsert, DbErr};
+use crate::db::repository::Repository;
+use crate::env::Env;
+use crate::models::oxford::{DefinitionDoc, VerbForm};
+use crate::models::shared::PronunciationDoc;
+use crate::oxford;
+use common_rs::EnvStore;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use mongodb::{Collection, Database};
+use rpc::dictionary::source_definition::Definition;
+use tonic::Code;
+
+pub struct OxfordSource {
+    definitions: Collection<DefinitionDoc>,
+}
+
+impl OxfordSource {
+    pub fn new(db: &Database) -> Self {
+        OxfordSource {
+            definitions: db.collection("oxford_definitions"),
+        }
+    }
+
+    async fn persist_entry(
+        &self,
+        repository: &Repository,
+        mut def: oxford::Definition,
+        searched_word: &str,
+        homographs: &[String],
+    ) -> Result<Option<ObjectId>, DbErr> {
+        if let Err(err) = repository
+            .archive
+            .save(self.name(), &def.oxford_ref, &def.pages)
+            .await
+        {
+            println!("Could not archive {}: {:?}", def.oxford_ref, err);
+        }
+
+        let pros = repository
+            .save_audio(&def.oxford_ref, std::mem::take(&mut def.pronunciations))
+            .await;
+        let verb_forms = std::mem::take(&mut def.verb_forms)
+            .into_iter()
+            .map(|f| async {
+                VerbForm {
+                    pronunciations: repository.save_audio(&f.word, f.pronunciations).await,
+                    form: f.form,
+                    note: f.note,
+                    word: f.word,
+                }
+            });
+        let verb_forms = futures::future::join_all(verb_forms).await;
+        let def = into_doc(
+            def,
+            pros,
+            verb_forms,
+            homographs.to_vec(),
+            searched_word,
+            DateTime::now(),
+        );
+
+        let filter = doc! {"oxford_ref" : &def.oxford_ref};
+        upsert(&self.definitions, filter, def).await
+    }
+}
+
+#[tonic::async_trait]
+impl DictionarySource for OxfordSource {
+    type Scraped = Vec<oxford::Definition>;
+    type Doc = DefinitionDoc;
+    type Err = oxford::ScrapeErr;
+
+    fn name(&self) -> &'static str {
+        "oxford"
+    }
+
+    fn collection(&self) -> &Collection<DefinitionDoc> {
+        &self.definitions
+    }
+
+    async fn scrape(
+        &self,
+        word: &str,
+        progress: &Progress,
+    ) -> Result<Vec<oxford::Definition>, oxford::ScrapeErr> {
+        oxford::scrape(word, progress).await
+    }
+
+    async fn persist(
+        &self,
+        repository: &Repository,
+        entries: Vec<oxford::Definition>,
+        searched_word: &str,
+    ) -> Result<Option<ObjectId>, DbErr> {
+        let homographs: Vec<String> = entries.iter().map(|x| x.oxford_ref.clone()).collect();
+
+        // the word points to the first entry, the others are returned along with it
+        let mut first_id = None;
+        for def in entries {
+            let id = self
+                .persist_entry(repository, def, searched_word, &homographs)
+                .await?;
+            first_id = first_id.or(id);
+        }
+        Ok(first_id)
+    }
+
+    async fn entries(&self, doc: DefinitionDoc) -> Result<Vec<DefinitionDoc>, DbErr> {
+        if doc.homographs.len() < 2 {
+            return Ok(vec![doc]);
+        }
+
+        let mut entries: Vec<DefinitionDoc> = self
+            .definitions
+            .find(doc! {"oxford_ref": {"$in": &doc.homographs}}, None)
+            .await
+            .map_err(DbErr::QueryErr)?
+            .try_collect()
+            .await
+            .map_err(DbErr::QueryErr)?;
+        if !entries.iter().any(|x| x.oxford_ref == doc.oxford_ref) {
+            entries.push(doc.clone());
+        }
+        entries.sort_by_key(|x| doc.homographs.iter().position(|r| *r == x.oxford_ref));
+        Ok(entries)
+    }
+
+    async fn reparse(&self, repository: &Repository, oxford_ref: &str) -> Result<bool, DbErr> {
+        let pages = repository.archive.get(self.name(), oxford_ref).await?;
+        let page = match pages.iter().find(|x| x.kind == DEFINITION_PAGE) {
+            Some(page) => page,
+            None => return Ok(false),
+        };
+
+        let mut def = oxford::scrape_html(&page.text()).into_definition_without_audio();
+        if !def.completeness.is_acceptable(Env::vars().min_completeness) {
+            println!(
+                "Skipped incomplete {oxford_ref}, empty: {:?}",
+                def.completeness.empty
+            );
+            return Ok(false);
+        }
+
+        let filter = doc! {"oxford_ref": oxford_ref};
+        let stored = self
+            .definitions
+            .find_one(filter.clone(), None)
+            .await
+            .map_err(DbErr::QueryErr)?
+            .unwrap_or_default();
+
+        let pros = keep_audio(
+            &stored.pronunciations,
+            std::mem::take(&mut def.pronunciations),
+        );
+        let verb_forms = std::mem::take(&mut def.verb_forms)
+            .into_iter()
+            .map(|f| {
+                let stored_pros = stored
+                    .verb_forms
+                    .iter()
+                    .find(|x| x.form == f.form && x.word == f.word)
+                    .map_or(&[][..], |x| &x.pronunciations);
+                VerbForm {
+                    pronunciations: keep_audio(stored_pros, f.pronunciations),
+                    form: f.form,
+                    note: f.note,
+                    word: f.word,
+                }
+            })
+            .collect();
+        let searched_word = match stored.searched_word.as_str() {
+            "" => oxford_ref,
+            searched_word => searched_word,
+        };
+        let def = into_doc(
+            def,
+            pros,
+            verb_forms,
+            stored.homographs.clone(),
+            searched_word,
+            page.fetched_at,
+        );
+
+        upsert(&self.definitions, filter, def).await?;
+        Ok(true)
+    }
+
+    fn audio_paths(&self) -> &'static [&'static str] {
+        &[
+            "pronunciations.audio_id",
+            "verb_forms.pronunciations.audio_id",
+        ]
+    }
+
+    fn into_response(doc: DefinitionDoc) -> Definition {
+        Definition::Oxford(doc.into_response())
+    }
+
+    fn id(doc: &DefinitionDoc) -> Option<ObjectId> {
+        doc.id
+    }
+
+    fn unstored(entries: Vec<oxford::Definition>, searched_word: &str) -> Vec<DefinitionDoc> {
+        let homographs: Vec<String> = entries.iter().map(|x| x.oxford_ref.clone()).collect();
+
+        entries
+            .into_iter()
+            .map(|mut def| {
+                let pros = keep_audio(&[], std::mem::take(&mut def.pronunciations));
+                let verb_forms = std::mem::take(&mut def.verb_forms)
+                    .into_iter()
+                    .map(|f| VerbForm {
+                        pronunciations: keep_audio(&[], f.pronunciations),
+                        form: f.form,
+                        note: f.note,
+                        word: f.word,
+                    })
+                    .collect();
+                into_doc(
+                    def,
+                    pros,
+                    verb_forms,
+                    homographs.clone(),
+                    searched_word,
+                    DateTime::now(),
+                )
+            })
+            .collect()
+    }
+
+    fn failure_code(err: &oxford::ScrapeErr) -> Code {
+        err.code()
+    }
+
+    fn retry_after(err: &oxford::ScrapeErr) -> Option<std::time::Duration> {
+        err.retry_after()
+    }
+
+    fn suggestions(err: &oxford::ScrapeErr) -> Option<Vec<String>> {
+        match err {
+            oxford::ScrapeErr::NotFound(not_found) => Some(
+                not_found
+                    .similar_words
+                    .iter()
+                    .map(|x| x.word.clone())
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// every homograph is a headword of its own
+    fn inflections(entries: &Vec<oxford::Definition>) -> Vec<(String, Vec<String>)> {
+        entries
+            .iter()
+            .map(|x| (x.header.clone(), x.inflected_forms()))
+            .collect()
+    }
+
+    /// of the first entry, a missing part in a rarer homograph does not reject the word
+    fn completeness(entries: &Vec<oxford::Definition>) -> Completeness {
+        entries
+            .first()
+            .map(|x| x.completeness.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// `def` with its pronunciations and verb forms taken out, stored separately
+fn into_doc(
+    def: oxford::Definition,
+    pronunciations: Vec<PronunciationDoc>,
+    verb_forms: Vec<VerbForm>,
+    homographs: Vec<String>,
+    searched_word: &str,
+    scraped_at: DateTime,
+) -> DefinitionDoc {
+    DefinitionDoc {
+        id: None,
+        oxford_ref: def.oxford_ref,
+        header: def.header,
+        searched_word: searched_word.to_string(),
+        inflections: def.inflections,
+        note: def.note,
+        word_variant: def.word_variant,
+        similar_results: def.similar_results,
+        pronunciations,
+        definitions: def.definitions,
+        see_also: def.see_also,
+        word_origin: def.word_origin,
+        idioms: def.idioms,
+        phrasal_verbs: def.phrasal_verbs,
+        verb_forms,
+        homographs,
+        incomplete: def
+            .completeness
+            .empty
+            .iter()
+            .map(|x| x.to_string())
+            .collect(),
+        scraped_at: Some(scraped_at),
+        source_version: Some(OxfordSource::VERSION),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob::fs::FsStore;
+    use crate::tests::mem_db;
+    use crate::transcoder::Transcoder;
+    use std::sync::Arc;
+
+    /// both homographs of the replayed "cat" are stored under the searched word
+    #[tokio::test]
+    async fn persist_homographs_ok() {
+        crate::http::replay_from("./src/tests/cassettes");
+        let db_name = format!("oxford_test_{}", std::process::id());
+        let db = mem_db::start(&db_name).await;
+        let blob_dir = std::env::temp_dir().join(&db_name);
+        let repository = Repository::new(
+            db.clone(),
+            Arc::new(FsStore::new(&blob_dir)),
+            Transcoder::new("ffmpeg".to_string()),
+        );
+        let source = OxfordSource::new(&db);
+
+        let entries = source.scrape("cat", &Progress::default()).await.unwrap();
+        let id = source.persist(&repository, entries, "cat").await.unwrap();
+
+        // upserted by the ref, the second homograph does not overwrite the first one
+        let stored: Vec<DefinitionDoc> = source
+            .definitions
+            .find(doc! {"searched_word": "cat"}, None)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        let refs: Vec<_> = stored
+            .iter()
+            .map(|x| (x.oxford_ref.as_str(), x.header.as_str()))
+            .collect();
+        assert_eq!(refs, vec![("cat_1", "cat"), ("cat_2", "Cat")]);
+        assert_eq!(stored[0].id, id);
+
+        // in the order of the homographs whichever one the word points to
+        for doc in stored {
+            let entries = source.entries(doc).await.unwrap();
+            let refs: Vec<_> = entries.iter().map(|x| x.oxford_ref.as_str()).collect();
+            assert_eq!(refs, vec!["cat_1", "cat_2"]);
+        }
+
+        _ = std::fs::remove_dir_all(blob_dir);
+    }
+}
@@ -0,0 +1,249 @@
+use super::{keep_audio, DictionarySource, Progress};
+use crate::archive::{DEFINITION_PAGE, EXAMPLES_PAGE};
+use crate::completeness::Completeness;
+use crate::db::database::{upsert, DbErr};
+use crate::db::repository::Repository;
+use crate::env::Env;
+use crate::models::shared::PronunciationDoc;
+use crate::models::vocabulary::{DefinitionDoc, SubDefinition};
+use crate::vocabulary;
+use common_rs::EnvStore;
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use mongodb::{Collection, Database};
+use rpc::dictionary::source_definition::Definition;
+use tonic::Code;
+
+pub struct VocabularySource {
+    definitions: Collection<DefinitionDoc>,
+}
+
+impl VocabularySource {
+    pub fn new(db: &Database) -> Self {
+        VocabularySource {
+            definitions: db.collection("vocabulary_definitions"),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl DictionarySource for VocabularySource {
+    type Scraped = vocabulary::Definition;
+    type Doc = DefinitionDoc;
+    type Err = vocabulary::ScrapeErr;
+
+    fn name(&self) -> &'static str {
+        "vocabulary"
+    }
+
+    fn collection(&self) -> &Collection<DefinitionDoc> {
+        &self.definitions
+    }
+
+    async fn scrape(
+        &self,
+        word: &str,
+        progress: &Progress,
+    ) -> Result<vocabulary::Definition, vocabulary::ScrapeErr> {
+        vocabulary::scrape(word, progress).await
+    }
+
+    async fn persist(
+        &self,
+        repository: &Repository,
+        mut def: vocabulary::Definition,
+        searched_word: &str,
+    ) -> Result<Option<ObjectId>, DbErr> {
+        if let Err(err) = repository
+            .archive
+            .save(self.name(), &def.voc_ref, &def.pages)
+            .await
+        {
+            println!("Could not archive {}: {:?}", def.voc_ref, err);
+        }
+
+        let pros = repository
+            .save_audio(&def.voc_ref, std::mem::take(&mut def.pronunciations))
+            .await;
+        let definitions = std::mem::take(&mut def.definitions)
+            .into_iter()
+            .map(|d| async {
+                let image_id = match d.image {
+                    Some(image) => repository.save_image(image).await.ok().flatten(),
+                    None => None,
+                };
+                SubDefinition {
+                    variant: d.variant,
+                    description: d.description,
+                    image_id,
+                    short_examples: d.short_examples,
+                    synonyms: d.synonyms,
+                }
+            });
+        let definitions = futures::future::join_all(definitions).await;
+        let def = into_doc(def, pros, definitions, searched_word, DateTime::now());
+
+        let filter = doc! {"voc_ref" : &def.voc_ref};
+        upsert(&self.definitions, filter, def).await
+    }
+
+    async fn reparse(&self, repository: &Repository, voc_ref: &str) -> Result<bool, DbErr> {
+        let pages = repository.archive.get(self.name(), voc_ref).await?;
+        let page = match pages.iter().find(|x| x.kind == DEFINITION_PAGE) {
+            Some(page) => page,
+            None => return Ok(false),
+        };
+
+        let filter = doc! {"voc_ref": voc_ref};
+        let stored = self
+            .definitions
+            .find_one(filter.clone(), None)
+            .await
+            .map_err(DbErr::QueryErr)?
+            .unwrap_or_default();
+
+        let scraped = match vocabulary::scrape_html(&page.text(), &page.final_url, voc_ref) {
+            Ok(scraped) => scraped,
+            Err(err) => {
+                println!("Could not reparse {voc_ref}: {:?}", err);
+                return Ok(false);
+            }
+        };
+        let examples = pages
+            .iter()
+            .find(|x| x.kind == EXAMPLES_PAGE)
+            .and_then(|x| vocabulary::parse_examples(&x.bytes).ok())
+            .unwrap_or_else(|| stored.examples.clone());
+
+        let mut def = scraped.into_definition_without_assets(examples);
+        if !def.completeness.is_acceptable(Env::vars().min_completeness) {
+            println!(
+                "Skipped incomplete {voc_ref}, empty: {:?}",
+                def.completeness.empty
+            );
+            return Ok(false);
+        }
+
+        let pros = keep_audio(
+            &stored.pronunciations,
+            std::mem::take(&mut def.pronunciations),
+        );
+        let definitions = std::mem::take(&mut def.definitions)
+            .into_iter()
+            .enumerate()
+            .map(|(i, d)| {
+                // images are not fetched again, the stored one of the same sense is kept
+                let image_id = stored
+                    .definitions
+                    .iter()
+                    .find(|x| x.description == d.description)
+                    .or_else(|| stored.definitions.get(i))
+                    .and_then(|x| x.image_id);
+                SubDefinition {
+                    variant: d.variant,
+                    description: d.description,
+                    image_id,
+                    short_examples: d.short_examples,
+                    synonyms: d.synonyms,
+                }
+            })
+            .collect();
+        let searched_word = match stored.searched_word.as_str() {
+            "" => voc_ref,
+            searched_word => searched_word,
+        };
+        let def = into_doc(def, pros, definitions, searched_word, page.fetched_at);
+
+        upsert(&self.definitions, filter, def).await?;
+        Ok(true)
+    }
+
+    fn audio_paths(&self) -> &'static [&'static str] {
+        &["pronunciations.audio_id"]
+    }
+
+    fn into_response(doc: DefinitionDoc) -> Definition {
+        Definition::Vocabulary(doc.into_response())
+    }
+
+    fn id(doc: &DefinitionDoc) -> Option<ObjectId> {
+        doc.id
+    }
+
+    fn unstored(mut def: vocabulary::Definition, searched_word: &str) -> Vec<DefinitionDoc> {
+        let pros = keep_audio(&[], std::mem::take(&mut def.pronunciations));
+        let definitions = std::mem::take(&mut def.definitions)
+            .into_iter()
+            .map(|d| SubDefinition {
+                variant: d.variant,
+                description: d.description,
+                image_id: None,
+                short_examples: d.short_examples,
+                synonyms: d.synonyms,
+            })
+            .collect();
+        vec![into_doc(
+            def,
+            pros,
+            definitions,
+            searched_word,
+            DateTime::now(),
+        )]
+    }
+
+    fn failure_code(err: &vocabulary::ScrapeErr) -> Code {
+        err.code()
+    }
+
+    fn retry_after(err: &vocabulary::ScrapeErr) -> Option<std::time::Duration> {
+        err.retry_after()
+    }
+
+    fn suggestions(err: &vocabulary::ScrapeErr) -> Option<Vec<String>> {
+        match err {
+            vocabulary::ScrapeErr::NotFound(not_found) => Some(not_found.suggestions.clone()),
+            _ => None,
+        }
+    }
+
+    fn inflections(def: &vocabulary::Definition) -> Vec<(String, Vec<String>)> {
+        let lemma = match def.header.is_empty() {
+            true => def.voc_ref.clone(),
+            false => def.header.clone(),
+        };
+        vec![(lemma, def.other_forms.clone())]
+    }
+
+    fn completeness(def: &vocabulary::Definition) -> Completeness {
+        def.completeness.clone()
+    }
+}
+
+/// `def` with its pronunciations and definitions taken out, stored separately
+fn into_doc(
+    def: vocabulary::Definition,
+    pronunciations: Vec<PronunciationDoc>,
+    definitions: Vec<SubDefinition>,
+    searched_word: &str,
+    scraped_at: DateTime,
+) -> DefinitionDoc {
+    DefinitionDoc {
+        id: None,
+        voc_ref: def.voc_ref,
+        searched_word: searched_word.to_string(),
+        header: def.header,
+        pronunciations,
+        other_forms: def.other_forms,
+        short_description: def.short_description,
+        long_description: def.long_description,
+        definitions,
+        examples: def.examples,
+        incomplete: def
+            .completeness
+            .empty
+            .iter()
+            .map(|x| x.to_string())
+            .collect(),
+        scraped_at: Some(scraped_at),
+        source_version: Some(VocabularySource::VERSION),
+    }
+}
@@ -0,0 +1,74 @@
+use super::{DictionarySource, Progress};
+use crate::db::database::{upsert, DbErr};
+use crate::db::repository::Repository;
+use crate::models::wiktionary::DefinitionDoc;
+use crate::wiktionary::ScrapeErr;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::{Collection, Database};
+use rpc::dictionary::source_definition::Definition;
+
+/// Offline source, its definitions are only available after importing a dump with `import-wiktionary`
+pub struct WiktionarySource {
+    definitions: Collection<DefinitionDoc>,
+}
+
+impl WiktionarySource {
+    pub fn new(db: &Database) -> Self {
+        WiktionarySource {
+            definitions: db.collection("wiktionary_definitions"),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl DictionarySource for WiktionarySource {
+    type Scraped = DefinitionDoc;
+    type Doc = DefinitionDoc;
+    type Err = ScrapeErr;
+
+    fn name(&self) -> &'static str {
+        "wiktionary"
+    }
+
+    fn collection(&self) -> &Collection<DefinitionDoc> {
+        &self.definitions
+    }
+
+    fn lookup_filter(&self, word: &str) -> Document {
+        doc! {"word": word}
+    }
+
+    async fn scrape(&self, _word: &str, _progress: &Progress) -> Result<DefinitionDoc, ScrapeErr> {
+        // everything there is to know is imported already
+        Err(ScrapeErr::NotImported)
+    }
+
+    async fn persist(
+        &self,
+        _repository: &Repository,
+        def: DefinitionDoc,
+        _searched_word: &str,
+    ) -> Result<Option<ObjectId>, DbErr> {
+        let filter = doc! {"word": &def.word};
+        upsert(&self.definitions, filter, def).await
+    }
+
+    fn into_response(doc: DefinitionDoc) -> Definition {
+        Definition::Wiktionary(doc.into_response())
+    }
+
+    fn id(doc: &DefinitionDoc) -> Option<ObjectId> {
+        doc.id
+    }
+
+    /// imported from a dump, there is nothing to re-scrape
+    fn refreshable(&self) -> bool {
+        false
+    }
+
+    fn suggestions(err: &ScrapeErr) -> Option<Vec<String>> {
+        match err {
+            ScrapeErr::NotImported => Some(Vec::new()),
+        }
+    }
+}
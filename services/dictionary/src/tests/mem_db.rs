@@ -0,0 +1,639 @@
+//! MongoDB of the tests. Keeps the collections in memory and answers the driver over the wire
+//! protocol as a standalone server would. Only the commands and the operators the service
+//! sends are supported, anything else fails the command
+
+use crate::db::database::get_database_client;
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+use mongodb::Database;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const OP_MSG: i32 = 2013;
+/// flag of a message ending with a checksum
+const CHECKSUM_PRESENT: u32 = 1;
+const HEADER_LEN: usize = 16;
+
+type Collections = Arc<Mutex<HashMap<String, Vec<Document>>>>;
+
+/// empty db served on a free local port while the test runs, `DB_CONNECTION_URI` points at it
+pub async fn start(db_name: &str) -> Database {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let collections = Collections::default();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve(stream, collections.clone()));
+        }
+    });
+
+    let uri = format!("mongodb://{addr}/?directConnection=true");
+    // the only variable `Env` requires
+    std::env::set_var("DB_CONNECTION_URI", &uri);
+    get_database_client(uri, db_name).await.unwrap()
+}
+
+async fn serve(mut stream: TcpStream, collections: Collections) {
+    loop {
+        let mut header = [0u8; HEADER_LEN];
+        if stream.read_exact(&mut header).await.is_err() {
+            return;
+        }
+        let length = i32_at(&header, 0) as usize;
+        let request_id = i32_at(&header, 4);
+        let op_code = i32_at(&header, 12);
+
+        let mut body = vec![0u8; length.saturating_sub(HEADER_LEN)];
+        if stream.read_exact(&mut body).await.is_err() {
+            return;
+        }
+        let reply = match read_command(&body) {
+            Some(command) if op_code == OP_MSG => run(&collections, command),
+            _ => failure(format!("unsupported message, op code {op_code}")),
+        };
+        if stream
+            .write_all(&message(request_id, &reply))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+fn i32_at(bytes: &[u8], at: usize) -> i32 {
+    i32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// the body section of an OP_MSG with the document sequences, e.g. `documents` of an insert, added to it
+fn read_command(body: &[u8]) -> Option<Document> {
+    let flags = u32::from_le_bytes(body.get(..4)?.try_into().ok()?);
+    let end = match flags & CHECKSUM_PRESENT {
+        0 => body.len(),
+        _ => body.len().checked_sub(4)?,
+    };
+
+    let mut command = None;
+    let mut sequences = Vec::new();
+    let mut at = 4;
+    while at < end {
+        let kind = body[at];
+        let size = i32_at(body, at + 1) as usize;
+        let section = body.get(at + 1..at + 1 + size)?;
+        match kind {
+            0 => command = Some(Document::from_reader(section).ok()?),
+            1 => {
+                let name_len = section[4..].iter().position(|b| *b == 0)?;
+                let name = String::from_utf8(section[4..4 + name_len].to_vec()).ok()?;
+                let mut docs = &section[4 + name_len + 1..];
+                let mut sequence = Vec::new();
+                while !docs.is_empty() {
+                    sequence.push(Bson::Document(Document::from_reader(&mut docs).ok()?));
+                }
+                sequences.push((name, sequence));
+            }
+            _ => return None,
+        }
+        at += 1 + size;
+    }
+
+    let mut command = command?;
+    for (name, sequence) in sequences {
+        command.insert(name, sequence);
+    }
+    Some(command)
+}
+
+fn message(response_to: i32, reply: &Document) -> Vec<u8> {
+    let mut section = Vec::new();
+    reply.to_writer(&mut section).unwrap();
+    let length = HEADER_LEN + 4 + 1 + section.len();
+
+    let mut message = Vec::with_capacity(length);
+    message.extend((length as i32).to_le_bytes());
+    // request id, nothing answers to it
+    message.extend(0i32.to_le_bytes());
+    message.extend(response_to.to_le_bytes());
+    message.extend(OP_MSG.to_le_bytes());
+    // flags
+    message.extend(0u32.to_le_bytes());
+    // body section
+    message.push(0);
+    message.extend(section);
+    message
+}
+
+fn failure(errmsg: String) -> Document {
+    doc! {"ok": 0.0, "errmsg": errmsg, "code": 115, "codeName": "CommandNotSupported"}
+}
+
+fn run(collections: &Collections, command: Document) -> Document {
+    let name = match command.keys().next() {
+        Some(name) => name.clone(),
+        None => return failure("empty command".to_string()),
+    };
+    let collection = command.get_str(&name).unwrap_or_default().to_string();
+    let ns = format!(
+        "{}.{}",
+        command.get_str("$db").unwrap_or_default(),
+        collection
+    );
+    let mut collections = collections.lock().unwrap();
+
+    let reply = match name.as_str() {
+        "hello" | "isMaster" | "ismaster" => Ok(doc! {
+            "helloOk": true,
+            "isWritablePrimary": true,
+            "ismaster": true,
+            "maxBsonObjectSize": 16 * 1024 * 1024,
+            "maxMessageSizeBytes": 48_000_000,
+            "maxWriteBatchSize": 100_000,
+            "minWireVersion": 0,
+            "maxWireVersion": 17,
+        }),
+        "ping" | "endSessions" | "createIndexes" | "killCursors" => Ok(doc! {}),
+        "dropDatabase" => {
+            collections.clear();
+            Ok(doc! {})
+        }
+        "find" => find(collections.entry(collection).or_default(), &command, &ns),
+        "insert" => insert(collections.entry(collection).or_default(), &command),
+        "update" => update(collections.entry(collection).or_default(), &command),
+        "delete" => delete(collections.entry(collection).or_default(), &command),
+        "findAndModify" => find_and_modify(collections.entry(collection).or_default(), &command),
+        "distinct" => distinct(collections.entry(collection).or_default(), &command),
+        other => Err(format!("unsupported command {other}")),
+    };
+
+    match reply {
+        Ok(mut reply) => {
+            reply.insert("ok", 1.0);
+            reply
+        }
+        Err(errmsg) => failure(errmsg),
+    }
+}
+
+/// the projection is not applied, the docs are read into the projected types anyway
+fn find(docs: &[Document], command: &Document, ns: &str) -> Result<Document, String> {
+    let filter = command.get_document("filter").cloned().unwrap_or_default();
+    let mut found: Vec<Document> = matching(docs, &filter)?
+        .into_iter()
+        .map(|i| docs[i].clone())
+        .collect();
+    if let Ok(sort) = command.get_document("sort") {
+        found.sort_by(|a, b| compare_by(a, b, sort));
+    }
+    let skip = int(command, "skip").unwrap_or(0).unsigned_abs() as usize;
+    // negative for a single batch
+    let limit = match int(command, "limit").unwrap_or(0).unsigned_abs() as usize {
+        0 => usize::MAX,
+        limit => limit,
+    };
+    let found: Vec<Document> = found.into_iter().skip(skip).take(limit).collect();
+
+    Ok(doc! {"cursor": {"id": 0i64, "ns": ns, "firstBatch": found}})
+}
+
+fn insert(docs: &mut Vec<Document>, command: &Document) -> Result<Document, String> {
+    let inserted = command
+        .get_array("documents")
+        .map_err(|err| err.to_string())?;
+    for doc in inserted {
+        let doc = doc
+            .as_document()
+            .ok_or("a document to insert is no document")?;
+        docs.push(with_id(doc.clone()));
+    }
+    Ok(doc! {"n": inserted.len() as i32})
+}
+
+fn update(docs: &mut Vec<Document>, command: &Document) -> Result<Document, String> {
+    let statements = command
+        .get_array("updates")
+        .map_err(|err| err.to_string())?;
+    let (mut n, mut modified, mut upserted) = (0, 0, Vec::new());
+
+    for (index, statement) in statements.iter().enumerate() {
+        let statement = statement.as_document().ok_or("an update is no document")?;
+        let filter = statement.get_document("q").map_err(|err| err.to_string())?;
+        let update = statement
+            .get_document("u")
+            .map_err(|_| "only update documents are supported")?;
+
+        let mut matched = matching(docs, filter)?;
+        if !statement.get_bool("multi").unwrap_or(false) {
+            matched.truncate(1);
+        }
+        if matched.is_empty() && statement.get_bool("upsert").unwrap_or(false) {
+            let doc = upserted_doc(filter, update)?;
+            upserted.push(doc! {"index": index as i32, "_id": doc.get("_id").cloned()});
+            docs.push(doc);
+            n += 1;
+        }
+        for i in matched {
+            let before = docs[i].clone();
+            apply(&mut docs[i], update, false)?;
+            n += 1;
+            if docs[i] != before {
+                modified += 1;
+            }
+        }
+    }
+
+    Ok(doc! {"n": n, "nModified": modified, "upserted": upserted})
+}
+
+fn delete(docs: &mut Vec<Document>, command: &Document) -> Result<Document, String> {
+    let statements = command
+        .get_array("deletes")
+        .map_err(|err| err.to_string())?;
+    let mut n = 0;
+
+    for statement in statements {
+        let statement = statement.as_document().ok_or("a delete is no document")?;
+        let filter = statement.get_document("q").map_err(|err| err.to_string())?;
+        let mut matched = matching(docs, filter)?;
+        // 0 for all of them
+        if int(statement, "limit").unwrap_or(0) == 1 {
+            matched.truncate(1);
+        }
+        for i in matched.into_iter().rev() {
+            docs.remove(i);
+            n += 1;
+        }
+    }
+
+    Ok(doc! {"n": n})
+}
+
+fn find_and_modify(docs: &mut Vec<Document>, command: &Document) -> Result<Document, String> {
+    let filter = command.get_document("query").cloned().unwrap_or_default();
+    let mut matched = matching(docs, &filter)?;
+    if let Ok(sort) = command.get_document("sort") {
+        matched.sort_by(|a, b| compare_by(&docs[*a], &docs[*b], sort));
+    }
+    let remove = command.get_bool("remove").unwrap_or(false);
+    let new = command.get_bool("new").unwrap_or(false);
+    let update = match command.get_document("update") {
+        Ok(update) => update.clone(),
+        Err(_) if remove => Document::new(),
+        Err(_) => return Err("only update documents are supported".to_string()),
+    };
+
+    let (value, last_error) = match matched.first() {
+        Some(&i) if remove => (
+            Bson::Document(docs.remove(i)),
+            doc! {"n": 1, "updatedExisting": false},
+        ),
+        Some(&i) => {
+            let before = docs[i].clone();
+            apply(&mut docs[i], &update, false)?;
+            let value = if new { docs[i].clone() } else { before };
+            (
+                Bson::Document(value),
+                doc! {"n": 1, "updatedExisting": true},
+            )
+        }
+        None if command.get_bool("upsert").unwrap_or(false) => {
+            let doc = upserted_doc(&filter, &update)?;
+            let last_error =
+                doc! {"n": 1, "updatedExisting": false, "upserted": doc.get("_id").cloned()};
+            docs.push(doc.clone());
+            let value = if new { Bson::Document(doc) } else { Bson::Null };
+            (value, last_error)
+        }
+        None => (Bson::Null, doc! {"n": 0, "updatedExisting": false}),
+    };
+
+    Ok(doc! {"value": value, "lastErrorObject": last_error})
+}
+
+fn distinct(docs: &[Document], command: &Document) -> Result<Document, String> {
+    let key = command.get_str("key").map_err(|err| err.to_string())?;
+    let filter = command.get_document("query").cloned().unwrap_or_default();
+    let mut values: Vec<Bson> = Vec::new();
+
+    for i in matching(docs, &filter)? {
+        for value in lookup(&docs[i], key) {
+            let items = match value {
+                Bson::Array(items) => items.iter().collect(),
+                value => vec![value],
+            };
+            for item in items {
+                if !values.iter().any(|x| same(x, item)) {
+                    values.push(item.clone());
+                }
+            }
+        }
+    }
+
+    Ok(doc! {"values": values})
+}
+
+fn int(doc: &Document, key: &str) -> Option<i64> {
+    match doc.get(key)? {
+        Bson::Int32(val) => Some(*val as i64),
+        Bson::Int64(val) => Some(*val),
+        Bson::Double(val) => Some(*val as i64),
+        _ => None,
+    }
+}
+
+fn with_id(doc: Document) -> Document {
+    if doc.contains_key("_id") {
+        return doc;
+    }
+    let mut with_id = doc! {"_id": ObjectId::new()};
+    with_id.extend(doc);
+    with_id
+}
+
+fn matching(docs: &[Document], filter: &Document) -> Result<Vec<usize>, String> {
+    let mut found = Vec::new();
+    for (i, doc) in docs.iter().enumerate() {
+        if matches(doc, filter)? {
+            found.push(i);
+        }
+    }
+    Ok(found)
+}
+
+fn matches(doc: &Document, filter: &Document) -> Result<bool, String> {
+    for (key, condition) in filter {
+        let matched = match key.as_str() {
+            "$and" | "$or" => {
+                let filters = condition
+                    .as_array()
+                    .ok_or(format!("{key} needs an array"))?;
+                let mut matched = Vec::new();
+                for filter in filters {
+                    let filter = filter
+                        .as_document()
+                        .ok_or(format!("{key} needs documents"))?;
+                    matched.push(matches(doc, filter)?);
+                }
+                match key.as_str() {
+                    "$and" => matched.iter().all(|x| *x),
+                    _ => matched.iter().any(|x| *x),
+                }
+            }
+            op if op.starts_with('$') => return Err(format!("unsupported operator {op}")),
+            path => satisfies(&lookup(doc, path), condition)?,
+        };
+        if !matched {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// values at a dotted path, an array on the way is looked into element by element unless
+/// the next key is an index
+fn lookup<'a>(doc: &'a Document, path: &str) -> Vec<&'a Bson> {
+    let (key, rest) = match path.split_once('.') {
+        Some((key, rest)) => (key, Some(rest)),
+        None => (path, None),
+    };
+    match (doc.get(key), rest) {
+        (Some(value), None) => vec![value],
+        (Some(value), Some(rest)) => lookup_in(value, rest),
+        (None, _) => Vec::new(),
+    }
+}
+
+fn lookup_in<'a>(value: &'a Bson, path: &str) -> Vec<&'a Bson> {
+    match value {
+        Bson::Document(doc) => lookup(doc, path),
+        Bson::Array(items) => {
+            let (key, rest) = match path.split_once('.') {
+                Some((key, rest)) => (key, Some(rest)),
+                None => (path, None),
+            };
+            match (key.parse::<usize>().ok().and_then(|i| items.get(i)), rest) {
+                (Some(item), None) => vec![item],
+                (Some(item), Some(rest)) => lookup_in(item, rest),
+                (None, _) => items
+                    .iter()
+                    .flat_map(|item| lookup_in(item, path))
+                    .collect(),
+            }
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn is_operators(condition: &Bson) -> bool {
+    match condition {
+        Bson::Document(doc) => doc.keys().next().is_some_and(|key| key.starts_with('$')),
+        _ => false,
+    }
+}
+
+fn satisfies(values: &[&Bson], condition: &Bson) -> Result<bool, String> {
+    let operators = match condition {
+        Bson::Document(operators) if is_operators(condition) => operators,
+        expected => return Ok(equals_any(values, expected)),
+    };
+
+    for (op, arg) in operators {
+        let satisfied = match op.as_str() {
+            "$eq" => equals_any(values, arg),
+            "$ne" => !equals_any(values, arg),
+            "$in" | "$nin" => {
+                let expected = arg.as_array().ok_or(format!("{op} needs an array"))?;
+                let found = expected.iter().any(|x| equals_any(values, x));
+                found == (op == "$in")
+            }
+            "$exists" => {
+                let exists = !matches!(arg, Bson::Boolean(false) | Bson::Int32(0));
+                exists != values.is_empty()
+            }
+            "$type" => {
+                let name = arg.as_str().ok_or("$type needs a type name")?;
+                let mut any = false;
+                for value in values {
+                    any |= has_type(value, name)?;
+                }
+                any
+            }
+            "$gt" | "$gte" | "$lt" | "$lte" => {
+                values
+                    .iter()
+                    .any(|value| match (op.as_str(), compare(value, arg)) {
+                        (_, None) => false,
+                        ("$gt", Some(ordering)) => ordering == Ordering::Greater,
+                        ("$gte", Some(ordering)) => ordering != Ordering::Less,
+                        ("$lt", Some(ordering)) => ordering == Ordering::Less,
+                        (_, Some(ordering)) => ordering != Ordering::Greater,
+                    })
+            }
+            other => return Err(format!("unsupported operator {other}")),
+        };
+        if !satisfied {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// a missing value equals null, an array equals its elements too
+fn equals_any(values: &[&Bson], expected: &Bson) -> bool {
+    if values.is_empty() {
+        return *expected == Bson::Null;
+    }
+    values.iter().any(|value| match value {
+        Bson::Array(items) => same(value, expected) || items.iter().any(|x| same(x, expected)),
+        value => same(value, expected),
+    })
+}
+
+fn same(a: &Bson, b: &Bson) -> bool {
+    a == b || compare(a, b) == Some(Ordering::Equal)
+}
+
+fn number(value: &Bson) -> Option<f64> {
+    match value {
+        Bson::Int32(val) => Some(*val as f64),
+        Bson::Int64(val) => Some(*val as f64),
+        Bson::Double(val) => Some(*val),
+        _ => None,
+    }
+}
+
+/// `None` for values of different types
+fn compare(a: &Bson, b: &Bson) -> Option<Ordering> {
+    match (a, b) {
+        (Bson::String(a), Bson::String(b)) => Some(a.cmp(b)),
+        (Bson::DateTime(a), Bson::DateTime(b)) => Some(a.cmp(b)),
+        (Bson::ObjectId(a), Bson::ObjectId(b)) => Some(a.bytes().cmp(&b.bytes())),
+        (Bson::Boolean(a), Bson::Boolean(b)) => Some(a.cmp(b)),
+        _ => number(a)?.partial_cmp(&number(b)?),
+    }
+}
+
+/// missing values first
+fn compare_by(a: &Document, b: &Document, sort: &Document) -> Ordering {
+    for (path, direction) in sort {
+        let ordering = match (lookup(a, path).first(), lookup(b, path).first()) {
+            (Some(a), Some(b)) => compare(a, b).unwrap_or(Ordering::Equal),
+            (a, b) => a.is_some().cmp(&b.is_some()),
+        };
+        let ordering = match number(direction) {
+            Some(direction) if direction < 0.0 => ordering.reverse(),
+            _ => ordering,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+fn has_type(value: &Bson, name: &str) -> Result<bool, String> {
+    Ok(match name {
+        "string" => matches!(value, Bson::String(_)),
+        "date" => matches!(value, Bson::DateTime(_)),
+        "objectId" => matches!(value, Bson::ObjectId(_)),
+        "array" => matches!(value, Bson::Array(_)),
+        "object" => matches!(value, Bson::Document(_)),
+        "bool" => matches!(value, Bson::Boolean(_)),
+        "null" => matches!(value, Bson::Null),
+        "number" => number(value).is_some(),
+        other => return Err(format!("unsupported type {other}")),
+    })
+}
+
+/// the equality fields of the filter with the update applied
+fn upserted_doc(filter: &Document, update: &Document) -> Result<Document, String> {
+    let mut doc = Document::new();
+    for (path, value) in filter {
+        if !path.starts_with('$') && !is_operators(value) {
+            set(&mut doc, path, value.clone())?;
+        }
+    }
+    apply(&mut doc, update, true)?;
+    Ok(with_id(doc))
+}
+
+/// a doc without operators replaces the stored one but its id
+fn apply(doc: &mut Document, update: &Document, inserting: bool) -> Result<(), String> {
+    if !update.keys().any(|key| key.starts_with('$')) {
+        let id = doc.get("_id").cloned();
+        *doc = update.clone();
+        if let Some(id) = id {
+            doc.insert("_id", id);
+        }
+        return Ok(());
+    }
+
+    for (op, fields) in update {
+        let fields = fields
+            .as_document()
+            .ok_or(format!("{op} needs a document"))?;
+        for (path, value) in fields {
+            match op.as_str() {
+                "$set" => set(doc, path, value.clone())?,
+                "$setOnInsert" if inserting => set(doc, path, value.clone())?,
+                "$setOnInsert" => {}
+                "$unset" => unset(doc, path),
+                "$inc" => {
+                    let current = lookup(doc, path).first().copied().cloned();
+                    let sum = match (current, value) {
+                        (None, value) => value.clone(),
+                        (Some(Bson::Int32(a)), Bson::Int32(b)) => Bson::Int32(a + b),
+                        (Some(Bson::Int64(a)), Bson::Int32(b)) => Bson::Int64(a + *b as i64),
+                        (Some(Bson::Int64(a)), Bson::Int64(b)) => Bson::Int64(a + b),
+                        (Some(a), b) => match (number(&a), number(b)) {
+                            (Some(a), Some(b)) => Bson::Double(a + b),
+                            _ => return Err(format!("can not $inc {path}")),
+                        },
+                    };
+                    set(doc, path, sum)?;
+                }
+                "$push" => {
+                    let mut items = match lookup(doc, path).first() {
+                        Some(Bson::Array(items)) => items.clone(),
+                        Some(_) => return Err(format!("can not $push to {path}")),
+                        None => Vec::new(),
+                    };
+                    items.push(value.clone());
+                    set(doc, path, Bson::Array(items))?;
+                }
+                other => return Err(format!("unsupported operator {other}")),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn set(doc: &mut Document, path: &str, value: Bson) -> Result<(), String> {
+    match path.split_once('.') {
+        None => {
+            doc.insert(path, value);
+            Ok(())
+        }
+        Some((key, rest)) => match doc
+            .entry(key.to_string())
+            .or_insert_with(|| Bson::Document(Document::new()))
+        {
+            Bson::Document(inner) => set(inner, rest, value),
+            _ => Err(format!("can not set {path}, only documents are set into")),
+        },
+    }
+}
+
+fn unset(doc: &mut Document, path: &str) {
+    match path.split_once('.') {
+        None => {
+            doc.remove(path);
+        }
+        Some((key, rest)) => {
+            if let Some(Bson::Document(inner)) = doc.get_mut(key) {
+                unset(inner, rest);
+            }
+        }
+    }
+}
@@ -1 +1,3 @@
+pub mod mem_db;
+mod service;
 pub mod utils;
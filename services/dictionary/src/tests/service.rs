@@ -0,0 +1,76 @@
+use crate::blob::fs::FsStore;
+use crate::db::repository::Repository;
+use crate::jobs::JobQueue;
+use crate::service::WordDefinitions;
+use crate::sources::Sources;
+use crate::tests::mem_db;
+use crate::transcoder::Transcoder;
+use crate::DictionaryService;
+use rpc::dictionary::source_definition::Definition;
+use std::sync::Arc;
+
+/// whole lookup with the scrapers replaying `src/tests/cassettes`, stored in `mem_db`
+#[tokio::test]
+async fn get_word_definitions_replay() {
+    crate::http::replay_from("./src/tests/cassettes");
+    let db_name = format!("dictionary_test_{}", std::process::id());
+    let db = mem_db::start(&db_name).await;
+    let blob_dir = std::env::temp_dir().join(&db_name);
+
+    let sources = Sources::new(
+        &db,
+        Some(vec!["oxford".to_string(), "vocabulary".to_string()]),
+    );
+    let repository = Repository::new(
+        db.clone(),
+        Arc::new(FsStore::new(&blob_dir)),
+        Transcoder::new("ffmpeg".to_string()),
+    );
+    let service = DictionaryService::new(repository, sources, None, JobQueue::new(&db));
+    // set by `health::monitor` once the db is prepared
+    service.db_health.set(true);
+
+    let found = match service.get_word_definitions("cat".to_string()).await {
+        Ok(WordDefinitions::Found(found)) => found,
+        Ok(WordDefinitions::NotFound(_)) => panic!("cat should be found"),
+        Err(err) => panic!("{:?}", err),
+    };
+    let sources: Vec<_> = found
+        .definitions
+        .iter()
+        .map(|x| x.source.as_str())
+        .collect();
+    assert_eq!(sources, vec!["vocabulary", "oxford", "oxford"]);
+    match &found.definitions[0].definition {
+        Some(Definition::Vocabulary(vocabulary)) => {
+            assert_eq!(vocabulary.header, "cat");
+            assert_eq!(vocabulary.examples.len(), 2);
+            assert!(vocabulary.pronunciations[0].audio_id.is_some());
+        }
+        other => panic!("should be a vocabulary definition: {:?}", other),
+    }
+    let oxford_refs: Vec<_> = found.definitions[1..]
+        .iter()
+        .map(|x| match &x.definition {
+            Some(Definition::Oxford(oxford)) => oxford.oxford_ref.as_str(),
+            other => panic!("should be an oxford definition: {:?}", other),
+        })
+        .collect();
+    assert_eq!(oxford_refs, vec!["cat_1", "cat_2"]);
+
+    // served from the db the second time
+    assert!(matches!(
+        service.get_word_definitions("cat".to_string()).await,
+        Ok(WordDefinitions::Found(_))
+    ));
+
+    match service.get_word_definitions("catt".to_string()).await {
+        Ok(WordDefinitions::NotFound(not_found)) => {
+            assert!(not_found.suggestions.contains(&"cat".to_string()))
+        }
+        _ => panic!("catt should not be found"),
+    }
+
+    db.drop(None).await.unwrap();
+    _ = std::fs::remove_dir_all(blob_dir);
+}
@@ -0,0 +1,176 @@
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Canonical formats every stored audio is converted to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Opus,
+    Mp3,
+}
+
+impl Format {
+    pub const ALL: [Format; 2] = [Format::Opus, Format::Mp3];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Opus => "opus",
+            Format::Mp3 => "mp3",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Opus => "audio/ogg",
+            Format::Mp3 => "audio/mpeg",
+        }
+    }
+
+    fn codec_args(&self) -> &'static [&'static str] {
+        match self {
+            Format::Opus => &[
+                "-c:a", "libopus", "-b:a", "48k", "-ar", "48000", "-f", "ogg",
+            ],
+            Format::Mp3 => &[
+                "-c:a",
+                "libmp3lame",
+                "-q:a",
+                "4",
+                "-ar",
+                "44100",
+                "-f",
+                "mp3",
+            ],
+        }
+    }
+}
+
+/// trims the leading and trailing silence and normalizes the loudness to -16 LUFS
+const FILTERS: &str = "silenceremove=start_periods=1:start_threshold=-50dB:start_silence=0.05,\
+    areverse,\
+    silenceremove=start_periods=1:start_threshold=-50dB:start_silence=0.05,\
+    areverse,\
+    loudnorm=I=-16:TP=-1.5:LRA=11";
+
+#[derive(Debug)]
+pub enum TranscodeErr {
+    IoErr(std::io::Error),
+    FfmpegErr(String),
+}
+
+/// Converts audio with the ffmpeg binary, installed in the docker image
+#[derive(Debug, Clone)]
+pub struct Transcoder {
+    ffmpeg: String,
+}
+
+impl Transcoder {
+    pub fn new(ffmpeg: String) -> Self {
+        Transcoder { ffmpeg }
+    }
+
+    pub async fn transcode(&self, input: &[u8], format: Format) -> Result<Vec<u8>, TranscodeErr> {
+        // mp4 can not be probed from a pipe when its index is at the end of the file
+        let path = std::env::temp_dir().join(format!(
+            "transcode-{}-{}",
+            std::process::id(),
+            crate::blob::key(input)
+        ));
+        let mut file = tokio::fs::File::create(&path)
+            .await
+            .map_err(TranscodeErr::IoErr)?;
+        file.write_all(input).await.map_err(TranscodeErr::IoErr)?;
+        file.flush().await.map_err(TranscodeErr::IoErr)?;
+
+        let output = Command::new(&self.ffmpeg)
+            .args(args(&path.to_string_lossy(), format))
+            .stdin(Stdio::null())
+            .output()
+            .await;
+        _ = tokio::fs::remove_file(&path).await;
+
+        let output = output.map_err(TranscodeErr::IoErr)?;
+        if !output.status.success() || output.stdout.is_empty() {
+            return Err(TranscodeErr::FfmpegErr(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ));
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+fn args(input: &str, format: Format) -> Vec<&str> {
+    let mut args = vec![
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-i",
+        input,
+        "-vn",
+        "-ac",
+        "1",
+        "-af",
+        FILTERS,
+    ];
+    args.extend(format.codec_args());
+    args.push("pipe:1");
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_ok() {
+        let args = args("/tmp/input", Format::Opus);
+
+        assert_eq!(
+            args[..5],
+            ["-hide_banner", "-loglevel", "error", "-i", "/tmp/input"]
+        );
+        assert!(args.windows(2).any(|x| x == ["-c:a", "libopus"]));
+        assert_eq!(args.last(), Some(&"pipe:1"));
+    }
+
+    /// needs ffmpeg with libopus and libmp3lame on the path
+    #[tokio::test]
+    #[ignore]
+    async fn transcode_ok() {
+        let wav = sine_wav();
+        let transcoder = Transcoder::new("ffmpeg".to_string());
+
+        let opus = transcoder.transcode(&wav, Format::Opus).await.unwrap();
+        assert_eq!(&opus[..4], b"OggS");
+
+        let mp3 = transcoder.transcode(&wav, Format::Mp3).await.unwrap();
+        assert!(!mp3.is_empty());
+    }
+
+    /// one second of 440Hz, 8kHz mono 16 bit pcm
+    fn sine_wav() -> Vec<u8> {
+        let samples: Vec<i16> = (0..8000)
+            .map(|i| {
+                ((i as f32 * 440.0 * 2.0 * std::f32::consts::PI / 8000.0).sin() * 10000.0) as i16
+            })
+            .collect();
+        let data_len = (samples.len() * 2) as u32;
+
+        let mut wav = Vec::new();
+        wav.extend(b"RIFF");
+        wav.extend((36 + data_len).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(8000u32.to_le_bytes());
+        wav.extend(16000u32.to_le_bytes());
+        wav.extend(2u16.to_le_bytes());
+        wav.extend(16u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend(data_len.to_le_bytes());
+        wav.extend(samples.iter().flat_map(|x| x.to_le_bytes()));
+        wav
+    }
+}
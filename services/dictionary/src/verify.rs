@@ -0,0 +1,169 @@
+use crate::db::database::DbErr;
+use crate::db::repository::Repository;
+use crate::gc::{self, GRACE_PERIOD_MINUTES};
+use crate::models::definition::DefinitionDoc;
+use crate::sources::Sources;
+use chrono::{DateTime, Duration, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use std::collections::{HashMap, HashSet};
+
+/// word referring to a source doc and the id of the doc
+type Ref = (String, ObjectId);
+
+/// Result of checking that the definitions, the source docs and the audio refer to each other
+#[derive(Debug, Default, PartialEq)]
+pub struct VerifyReport {
+    /// word, source and the id of the source doc which does not exist
+    pub dangling: Vec<(String, String, ObjectId)>,
+    /// source and the id of the doc no definition refers to
+    pub orphaned_docs: Vec<(String, ObjectId)>,
+    /// audio no source doc refers to
+    pub orphaned_audio: Vec<ObjectId>,
+    pub repaired: bool,
+}
+
+/// Scans the definitions for ids of missing source docs, the source docs for the ones no definition
+/// refers to and the audio for the ones without an owner. Only the given sources are checked,
+/// their audio is the only one considered owned, so run it with all the sources enabled.
+/// `repair` drops the dangling ids, so the words are looked up again, removes the orphaned docs
+/// and collects the orphaned audio. Docs and audio younger than the gc grace period are kept,
+/// the definition referring to them may be just being stored.
+pub async fn verify(
+    repository: &Repository,
+    sources: &Sources,
+    repair: bool,
+) -> Result<VerifyReport, DbErr> {
+    let definitions: Vec<DefinitionDoc> = repository
+        .definitions
+        .find(
+            doc! {},
+            FindOptions::builder()
+                .projection(doc! {"word": 1, "sources": 1})
+                .build(),
+        )
+        .await
+        .map_err(DbErr::QueryErr)?
+        .try_collect()
+        .await
+        .map_err(DbErr::QueryErr)?;
+
+    let cutoff = Utc::now() - Duration::minutes(GRACE_PERIOD_MINUTES);
+    let mut report = VerifyReport::default();
+    let mut orphans = HashMap::new();
+
+    for source in sources.iter() {
+        let refs = refs(&definitions, source.name());
+        let ids: Vec<_> = refs.iter().map(|(_, id)| *id).collect();
+        let existing = source.existing(&ids).await?;
+
+        let (found, dangling) = split_dangling(refs, &existing);
+        report.dangling.extend(
+            dangling
+                .into_iter()
+                .map(|(word, id)| (word, source.name().to_string(), id)),
+        );
+
+        // imported docs are referred to only once looked up
+        if !source.refreshable() {
+            continue;
+        }
+        let referenced: HashSet<_> = found.into_iter().map(|(_, id)| id).collect();
+        let unreachable: Vec<_> = source
+            .unreachable(&referenced)
+            .await?
+            .into_iter()
+            .filter(|id| is_settled(id, cutoff))
+            .collect();
+        report.orphaned_docs.extend(
+            unreachable
+                .iter()
+                .map(|id| (source.name().to_string(), *id)),
+        );
+        orphans.insert(source.name(), unreachable);
+    }
+
+    report.orphaned_audio = gc::orphaned_audio(repository, sources).await?;
+
+    if repair {
+        for (word, source, _) in &report.dangling {
+            repository.unset_source(word, source).await?;
+        }
+        for source in sources.iter() {
+            match orphans.get(source.name()) {
+                Some(ids) if !ids.is_empty() => {
+                    source.delete(ids).await?;
+                }
+                _ => {}
+            }
+        }
+        // after the docs, so the audio only they referred to is collected too
+        gc::collect_audio(repository, sources).await?;
+        report.repaired = true;
+    }
+
+    Ok(report)
+}
+
+/// words referring to a doc of the source and its id
+fn refs(definitions: &[DefinitionDoc], source: &str) -> Vec<Ref> {
+    definitions
+        .iter()
+        .filter_map(|def| match def.sources.get(source) {
+            Some(Some(id)) => Some((def.word.clone(), *id)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// refs of the existing docs and the dangling ones
+fn split_dangling(refs: Vec<Ref>, existing: &HashSet<ObjectId>) -> (Vec<Ref>, Vec<Ref>) {
+    refs.into_iter().partition(|(_, id)| existing.contains(id))
+}
+
+fn is_settled(id: &ObjectId, cutoff: DateTime<Utc>) -> bool {
+    id.timestamp().to_chrono() < cutoff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_dangling_ok() {
+        let (cat, dog) = (ObjectId::new(), ObjectId::new());
+        let definitions = vec![
+            DefinitionDoc {
+                word: "cat".to_string(),
+                sources: HashMap::from([
+                    ("oxford".to_string(), Some(cat)),
+                    ("vocabulary".to_string(), None),
+                ]),
+                ..Default::default()
+            },
+            DefinitionDoc {
+                word: "dog".to_string(),
+                sources: HashMap::from([("oxford".to_string(), Some(dog))]),
+                ..Default::default()
+            },
+        ];
+
+        assert!(refs(&definitions, "vocabulary").is_empty());
+
+        let (found, dangling) = split_dangling(refs(&definitions, "oxford"), &HashSet::from([cat]));
+        assert_eq!(found, vec![("cat".to_string(), cat)]);
+        assert_eq!(dangling, vec![("dog".to_string(), dog)]);
+    }
+
+    #[test]
+    fn is_settled_ok() {
+        let id = ObjectId::new();
+
+        assert!(is_settled(&id, Utc::now() + Duration::minutes(1)));
+        assert!(!is_settled(
+            &id,
+            Utc::now() - Duration::minutes(GRACE_PERIOD_MINUTES)
+        ));
+    }
+}
@@ -1,17 +1,32 @@
+use crate::archive::{RawPage, DEFINITION_PAGE, EXAMPLES_PAGE};
+use crate::completeness::Completeness;
+use crate::fetcher::{FetchErr, Fetcher, RetryPolicy};
+use crate::http::HttpResponse;
+use crate::models::job::Stage;
 use crate::models::vocabulary::WordVariant;
+use crate::sources::Progress;
 use crate::{
-    cloudflare_bypasser,
     models::{
-        shared::{Audio, Pronunciation, PronunciationVariant},
-        vocabulary::{Example, SubDefinition},
+        shared::{Audio, Image, Pronunciation, PronunciationVariant},
+        vocabulary::Example,
     },
     utils::Css,
 };
 use scraper::{ElementRef, Html, Selector};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tonic::Code;
 
-static DEFINITION_BASE_URL: &str = "https://www.vocabulary.com/dictionary";
+pub static DEFINITION_BASE_URL: &str = "https://www.vocabulary.com/dictionary";
 static EXAMPLES_BASE_URL: &str = "https://corpus.vocabulary.com/api/1.0/examples.json";
+static BASE_URL: &str = "https://www.vocabulary.com";
+
+/// challenged often, so retried longer than the other sources
+const FETCH_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 6,
+    base_delay: Duration::from_secs(2),
+    max_delay: Duration::from_secs(60),
+};
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Definition {
@@ -23,6 +38,21 @@ pub struct Definition {
     pub long_description: String,
     pub definitions: Vec<SubDefinition>,
     pub examples: Vec<Example>,
+    #[serde(skip)]
+    pub completeness: Completeness,
+    /// pages the definition was parsed from, empty when re-parsed from them
+    #[serde(skip)]
+    pub pages: Vec<RawPage>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct SubDefinition {
+    pub variant: WordVariant,
+    pub description: String,
+    pub image_src: Option<String>,
+    pub image: Option<Image>,
+    pub short_examples: Vec<String>,
+    pub synonyms: Vec<String>,
 }
 
 pub fn get_word_url(word: &str) -> String {
@@ -35,10 +65,39 @@ pub fn get_word_examples_url(word: &str, max_results: u8) -> String {
     url
 }
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum ScrapeErr {
-    BypassErr(cloudflare_bypasser::BypassErr),
-    GetRequestErr(reqwest::Error),
+    #[error("could not fetch: {0:?}")]
+    FetchErr(FetchErr),
+    #[error("invalid examples: {0}")]
+    DecodeErr(#[source] serde_json::Error),
+    /// neither a definition nor the page of an unknown word, e.g. the markup changed
+    #[error("unexpected page {0}")]
+    UnexpectedPage(String),
+    #[error("not found, suggestions: {:?}", .0.suggestions)]
+    NotFound(NotFound),
+}
+
+impl ScrapeErr {
+    pub fn code(&self) -> Code {
+        match self {
+            ScrapeErr::FetchErr(err) => err.code(),
+            ScrapeErr::DecodeErr(_) | ScrapeErr::UnexpectedPage(_) => Code::Internal,
+            ScrapeErr::NotFound(_) => Code::NotFound,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ScrapeErr::FetchErr(err) => err.retry_after(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct NotFound {
+    pub suggestions: Vec<String>,
 }
 
 enum ElementSelector {
@@ -51,6 +110,10 @@ enum ElementSelector {
     Definition,
     Example,
     Synonym,
+    Image,
+    Canonical,
+    Suggestion,
+    NoResults,
 }
 
 impl From<ElementSelector> for Selector {
@@ -65,6 +128,10 @@ impl From<ElementSelector> for Selector {
             ElementSelector::Definition => ".definition",
             ElementSelector::Example => ".example",
             ElementSelector::Synonym => ".defContent > .instances .word",
+            ElementSelector::Image => ".defContent img",
+            ElementSelector::Canonical => "link[rel=canonical]",
+            ElementSelector::Suggestion => ".suggestions a",
+            ElementSelector::NoResults => ".noresults",
         };
 
         Selector::parse(css_selector)
@@ -72,17 +139,128 @@ impl From<ElementSelector> for Selector {
     }
 }
 
-pub async fn scrape(word: &str) -> Result<Definition, ScrapeErr> {
-    let bypasser = cloudflare_bypasser::Bypasser::new();
-    // TODO handle redirects and not found page
-    let html = bypasser
-        .get(get_word_url(word).as_str())
+pub async fn scrape(word: &str, progress: &Progress) -> Result<Definition, ScrapeErr> {
+    let url = get_word_url(word);
+    let page = Fetcher::new(FETCH_POLICY)
+        .get(&url)
         .await
-        .map_err(ScrapeErr::BypassErr)?;
+        .map_err(ScrapeErr::FetchErr)?;
+
+    let scraped = scrape_html(&page.text(), &page.url, word)?;
+    let completeness = scraped.completeness();
+
+    let examples_page = fetch_examples(&scraped.voc_ref).await?;
+    let examples = parse_examples(&examples_page.bytes)?;
+    let pages = vec![
+        RawPage::new(DEFINITION_PAGE, &url, &page),
+        RawPage::new(
+            EXAMPLES_PAGE,
+            &get_word_examples_url(&scraped.voc_ref, 24),
+            &examples_page,
+        ),
+    ];
+
+    progress.report(Stage::DownloadingAudio);
+    let definitions = load_images(scraped.definitions).await;
+    let pronunciations = load_audio(scraped.pronunciations).await;
+
+    let word = Definition {
+        voc_ref: scraped.voc_ref,
+        header: scraped.header,
+        pronunciations,
+        other_forms: scraped.other_forms,
+        short_description: scraped.short_description,
+        long_description: scraped.long_description,
+        definitions,
+        examples,
+        completeness,
+        pages,
+    };
+
+    Ok(word)
+}
+
+/// Page parsed before the audio, images and examples are fetched
+#[derive(Debug, Serialize)]
+pub struct Scraped {
+    pub voc_ref: String,
+    pub header: String,
+    pub pronunciations: Vec<ScrapedPronunciation>,
+    pub other_forms: Vec<String>,
+    pub short_description: String,
+    pub long_description: String,
+    pub definitions: Vec<SubDefinition>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScrapedPronunciation {
+    pub variant: PronunciationVariant,
+    pub ipa_str: String,
+    pub audio_src: Option<String>,
+}
 
-    let examples = scrape_examples(word).await?;
+impl Scraped {
+    /// definition with no audio and images downloaded, e.g. when re-parsing an archived page
+    pub fn into_definition_without_assets(self, examples: Vec<Example>) -> Definition {
+        let completeness = self.completeness();
+        let pronunciations = self
+            .pronunciations
+            .into_iter()
+            .map(|p| Pronunciation {
+                variant: p.variant,
+                ipa_str: p.ipa_str,
+                audio: None,
+            })
+            .collect();
+
+        Definition {
+            voc_ref: self.voc_ref,
+            header: self.header,
+            pronunciations,
+            other_forms: self.other_forms,
+            short_description: self.short_description,
+            long_description: self.long_description,
+            definitions: self.definitions,
+            examples,
+            completeness,
+            pages: Vec::new(),
+        }
+    }
 
-    let html_doc = Html::parse_document(html.as_str());
+    pub fn completeness(&self) -> Completeness {
+        Completeness::default()
+            .required("header", !self.header.is_empty())
+            .required("definitions", !self.definitions.is_empty())
+            .expected(
+                "definitions.description",
+                self.definitions.iter().all(|d| !d.description.is_empty()),
+            )
+            .expected(
+                "definitions.variant",
+                self.definitions
+                    .iter()
+                    .all(|d| !matches!(&d.variant, WordVariant::Other(v) if v.is_empty())),
+            )
+            .expected("pronunciations", !self.pronunciations.is_empty())
+            .expected(
+                "pronunciations.ipa",
+                self.pronunciations.iter().all(|p| !p.ipa_str.is_empty()),
+            )
+            .expected("short_description", !self.short_description.is_empty())
+            .expected("long_description", !self.long_description.is_empty())
+    }
+}
+
+/// `url` is the one the page ended up at, `word` the searched one
+pub fn scrape_html(html: &str, url: &str, word: &str) -> Result<Scraped, ScrapeErr> {
+    let html_doc = Html::parse_document(html);
+
+    if let Some(not_found) = parse_not_found_page(&html_doc, url)? {
+        return Err(ScrapeErr::NotFound(not_found));
+    }
+
+    // misspelled or inflected words are redirected to the canonical headword
+    let voc_ref = get_voc_ref(&html_doc, url).unwrap_or_else(|| word.to_string());
 
     let header = html_doc
         .select(&ElementSelector::Header.into())
@@ -120,71 +298,68 @@ pub async fn scrape(word: &str) -> Result<Definition, ScrapeErr> {
         .trim()
         .to_string();
 
-    let definitions = scrape_definitions(html_doc.clone())?;
-    let pronunciations = scrape_pronunciations(html_doc).await;
+    let pronunciations = get_pronunciations(&html_doc);
+    let definitions = scrape_definitions(html_doc)?;
 
-    let word = Definition {
-        voc_ref: word.to_string(),
+    Ok(Scraped {
+        voc_ref,
         header,
         pronunciations,
         other_forms,
         short_description,
         long_description,
         definitions,
-        examples,
-    };
-
-    Ok(word)
+    })
 }
 
-async fn scrape_pronunciations(html: Html) -> Vec<Pronunciation> {
-    let parsed: Vec<_> = {
-        html.select(&ElementSelector::IpaSection.into())
-            .map(|el| {
-                let variant = match el.select(&Css("div").into()).next() {
-                    Some(el) => {
-                        let sens = scraper::CaseSensitivity::AsciiCaseInsensitive;
-                        let el = el.value();
-                        if el.has_class("us-flag-icon", sens) {
-                            PronunciationVariant::Usa
-                        } else if el.has_class("uk-flag-icon", sens) {
-                            PronunciationVariant::Uk
-                        } else {
-                            PronunciationVariant::Other
-                        }
+fn get_pronunciations(html: &Html) -> Vec<ScrapedPronunciation> {
+    html.select(&ElementSelector::IpaSection.into())
+        .map(|el| {
+            let variant = match el.select(&Css("div").into()).next() {
+                Some(el) => {
+                    let sens = scraper::CaseSensitivity::AsciiCaseInsensitive;
+                    let el = el.value();
+                    if el.has_class("us-flag-icon", sens) {
+                        PronunciationVariant::Usa
+                    } else if el.has_class("uk-flag-icon", sens) {
+                        PronunciationVariant::Uk
+                    } else {
+                        PronunciationVariant::Other
                     }
-                    None => PronunciationVariant::Other,
-                };
-
-                let ipa_str = el
-                    .select(&Css("h3").into())
-                    .next()
-                    .map_or_else(|| "", |val| val.text().next().unwrap_or_default())
-                    .to_string();
-
-                let audio_src_url = get_audio_src_url(el);
-
-                (variant, ipa_str, audio_src_url)
-            })
-            .collect()
-    };
-
-    let async_result = parsed
-        .into_iter()
-        .map(|(variant, ipa_str, audio_src)| async {
-            let audio: Option<Audio> = match audio_src {
-                Some(url) => get_audio_from_url(url).await,
-                None => None,
+                }
+                None => PronunciationVariant::Other,
             };
 
-            Pronunciation {
+            let ipa_str = el
+                .select(&Css("h3").into())
+                .next()
+                .map_or_else(|| "", |val| val.text().next().unwrap_or_default())
+                .to_string();
+
+            ScrapedPronunciation {
                 variant,
                 ipa_str,
-                audio,
+                audio_src: get_audio_src_url(el),
             }
-        });
+        })
+        .collect()
+}
+
+async fn load_audio(scraped: Vec<ScrapedPronunciation>) -> Vec<Pronunciation> {
+    let pronunciations = scraped.into_iter().map(|p| async {
+        let audio: Option<Audio> = match p.audio_src {
+            Some(url) => get_audio_from_url(url).await,
+            None => None,
+        };
+
+        Pronunciation {
+            variant: p.variant,
+            ipa_str: p.ipa_str,
+            audio,
+        }
+    });
 
-    futures::future::join_all(async_result).await
+    futures::future::join_all(pronunciations).await
 }
 
 fn get_audio_src_url(el: ElementRef) -> Option<String> {
@@ -216,29 +391,12 @@ fn get_audio_src_url(el: ElementRef) -> Option<String> {
 }
 
 async fn get_audio_from_url(url: String) -> Option<Audio> {
-    let response = reqwest::get(url).await;
-
-    let audio = match response {
-        Ok(res) => {
-            let content_type = match res.headers().get(reqwest::header::CONTENT_TYPE) {
-                Some(val) => match val.to_str() {
-                    Ok(val) => Some(val.to_string()),
-                    Err(_) => None,
-                },
-                None => None,
-            };
-
-            let bytes = res.bytes().await;
-
-            match (content_type, bytes) {
-                (Some(val), Ok(bytes)) => Some(Audio {
-                    content_type: val,
-                    bytes: bytes.into_iter().collect::<Vec<u8>>(),
-                }),
-                _ => None,
-            }
-        }
-        Err(_err) => None,
+    let audio = match Fetcher::new(RetryPolicy::ASSETS).get(&url).await {
+        Ok(res) if res.status.is_success() => res.content_type().map(|content_type| Audio {
+            content_type,
+            bytes: res.bytes,
+        }),
+        _ => None,
     };
 
     audio
@@ -292,10 +450,20 @@ fn scrape_definitions(html: Html) -> Result<Vec<SubDefinition>, ScrapeErr> {
                 .map(|el| el.text().next().unwrap_or_default().to_string())
                 .collect();
 
+            let image_src = el
+                .select(&ElementSelector::Image.into())
+                .next()
+                .and_then(|img| {
+                    let attrs = img.value();
+                    attrs.attr("src").or_else(|| attrs.attr("data-src"))
+                })
+                .map(to_absolute_url);
+
             Ok(SubDefinition {
                 variant,
                 description,
-                image: None, // TODO image
+                image_src,
+                image: None,
                 short_examples,
                 synonyms,
             })
@@ -306,6 +474,88 @@ fn scrape_definitions(html: Html) -> Result<Vec<SubDefinition>, ScrapeErr> {
     Ok(definitions)
 }
 
+/// `None` when the page is an actual definition. Only a page with the no results marker
+/// means the word is unknown, anything else fails as unexpected instead of being cached as a miss
+fn parse_not_found_page(html: &Html, url: &str) -> Result<Option<NotFound>, ScrapeErr> {
+    let has_header = html
+        .select(&ElementSelector::Header.into())
+        .next()
+        .is_some();
+    let has_definitions = html
+        .select(&ElementSelector::Definitions.into())
+        .next()
+        .is_some();
+
+    if has_header || has_definitions {
+        return Ok(None);
+    }
+    if html
+        .select(&ElementSelector::NoResults.into())
+        .next()
+        .is_none()
+    {
+        return Err(ScrapeErr::UnexpectedPage(url.to_string()));
+    }
+
+    let suggestions = html
+        .select(&ElementSelector::Suggestion.into())
+        .map(|el| el.text().collect::<Vec<_>>().join("").trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect();
+
+    Ok(Some(NotFound { suggestions }))
+}
+
+/// headword ref from the canonical link, falls back to the url the request ended up at
+fn get_voc_ref(html: &Html, url: &str) -> Option<String> {
+    let canonical = html
+        .select(&ElementSelector::Canonical.into())
+        .next()
+        .and_then(|el| el.value().attr("href"))
+        .unwrap_or(url);
+
+    canonical
+        .split(['?', '#'])
+        .next()
+        .and_then(|x| x.trim_end_matches('/').rsplit('/').next())
+        .filter(|x| !x.is_empty() && *x != "dictionary")
+        .map(|x| x.to_string())
+}
+
+fn to_absolute_url(src: &str) -> String {
+    if src.starts_with("//") {
+        format!("https:{src}")
+    } else if src.starts_with('/') {
+        format!("{BASE_URL}{src}")
+    } else {
+        src.to_string()
+    }
+}
+
+async fn load_images(definitions: Vec<SubDefinition>) -> Vec<SubDefinition> {
+    let definitions = definitions.into_iter().map(|mut def| async {
+        if let Some(src) = &def.image_src {
+            def.image = get_image_from_url(src).await;
+        }
+        def
+    });
+
+    futures::future::join_all(definitions).await
+}
+
+async fn get_image_from_url(url: &str) -> Option<Image> {
+    let response = Fetcher::new(RetryPolicy::ASSETS).get(url).await.ok()?;
+
+    if !response.status.is_success() {
+        return None;
+    }
+
+    Some(Image {
+        content_type: response.content_type()?,
+        bytes: response.bytes,
+    })
+}
+
 #[derive(Deserialize, Debug)]
 struct GetExampleRes {
     result: ResultRes,
@@ -328,14 +578,16 @@ struct Volume {
     author: String,
 }
 
-async fn scrape_examples(word: &str) -> Result<Vec<Example>, ScrapeErr> {
-    let url = get_word_examples_url(word, 24);
-    let res = reqwest::get(url).await.map_err(ScrapeErr::GetRequestErr)?;
-
-    let res = res
-        .json::<GetExampleRes>()
+async fn fetch_examples(word: &str) -> Result<HttpResponse, ScrapeErr> {
+    Fetcher::new(FETCH_POLICY)
+        .get(&get_word_examples_url(word, 24))
         .await
-        .map_err(ScrapeErr::GetRequestErr)?;
+        .map_err(ScrapeErr::FetchErr)
+}
+
+/// `json` is the body of the examples response
+pub fn parse_examples(json: &[u8]) -> Result<Vec<Example>, ScrapeErr> {
+    let res: GetExampleRes = serde_json::from_slice(json).map_err(ScrapeErr::DecodeErr)?;
 
     let examples: Vec<Example> = res
         .result
@@ -361,6 +613,100 @@ mod tests {
         is_send::<Html>(); // compiles only if true
     }
 
+    #[test]
+    fn scrape_definitions_image_src() {
+        let html = r#"
+            <div class="word-definitions">
+                <ol>
+                    <li class="sense">
+                        <div class="defContent">
+                            <div class="definition"><div class="pos-icon">noun</div>feline mammal</div>
+                            <img class="image" src="//cdn.vocab.com/units/cat.jpg">
+                        </div>
+                    </li>
+                    <li class="sense">
+                        <div class="defContent">
+                            <div class="definition"><div class="pos-icon">verb</div>to vomit</div>
+                        </div>
+                    </li>
+                </ol>
+            </div>
+        "#;
+
+        let definitions =
+            scrape_definitions(Html::parse_document(html)).expect("should scrape definitions");
+
+        assert_eq!(definitions.len(), 2);
+        assert_eq!(
+            definitions[0].image_src,
+            Some("https://cdn.vocab.com/units/cat.jpg".to_string())
+        );
+        assert_eq!(definitions[0].image, None);
+        assert_eq!(definitions[1].image_src, None);
+    }
+
+    #[test]
+    fn parse_not_found_page_ok() {
+        let html = r#"
+            <div class="noresults">
+                <h1>Sorry, no dictionary entry found for "catt".</h1>
+                <div class="suggestions">
+                    <ol>
+                        <li><a href="/dictionary/cat">cat</a></li>
+                        <li><a href="/dictionary/cast"> cast </a></li>
+                    </ol>
+                </div>
+            </div>
+        "#;
+
+        let url = get_word_url("catt");
+        let not_found = parse_not_found_page(&Html::parse_document(html), &url)
+            .unwrap()
+            .expect("should be found");
+        assert_eq!(not_found.suggestions, vec!["cat", "cast"]);
+
+        let html = r#"<h1 id="hdr-word-area">cat</h1>"#;
+        assert!(parse_not_found_page(&Html::parse_document(html), &url)
+            .unwrap()
+            .is_none());
+
+        // e.g. a changed markup, not a word the source does not know
+        let html = r#"<div class="page-error">Something went wrong</div>"#;
+        assert!(matches!(
+            parse_not_found_page(&Html::parse_document(html), &url),
+            Err(ScrapeErr::UnexpectedPage(_))
+        ));
+    }
+
+    #[test]
+    fn get_voc_ref_ok() {
+        let html = Html::parse_document(
+            r#"<head><link rel="canonical" href="https://www.vocabulary.com/dictionary/cat"></head>"#,
+        );
+        assert_eq!(
+            get_voc_ref(&html, "https://www.vocabulary.com/dictionary/cats"),
+            Some("cat".to_string())
+        );
+
+        let html = Html::parse_document("<head></head>");
+        assert_eq!(
+            get_voc_ref(&html, "https://www.vocabulary.com/dictionary/slate?utm=1"),
+            Some("slate".to_string())
+        );
+    }
+
+    #[test]
+    fn to_absolute_url_ok() {
+        assert_eq!(
+            to_absolute_url("/images/cat.png"),
+            "https://www.vocabulary.com/images/cat.png"
+        );
+        assert_eq!(
+            to_absolute_url("https://cdn.vocab.com/cat.png"),
+            "https://cdn.vocab.com/cat.png"
+        );
+    }
+
     #[tokio::test]
     async fn scrape_pronunciations_success() {
         let html = r#"
@@ -381,8 +727,7 @@ mod tests {
 
         let html = Html::parse_document(html);
 
-        // TODO mock http request
-        let result = scrape_pronunciations(html).await;
+        let result = load_audio(get_pronunciations(&html)).await;
 
         let first = &result[0];
         let second = &result[1];
@@ -395,4 +740,57 @@ mod tests {
         assert_eq!(second.ipa_str, "/sleɪt/");
         assert_eq!(second.audio, None);
     }
+
+    /// replays `src/tests/cassettes`
+    #[tokio::test]
+    async fn scrape_replay() {
+        crate::http::replay_from("./src/tests/cassettes");
+        let definition = scrape("cat", &Progress::default())
+            .await
+            .expect("should be recorded");
+
+        assert_eq!(definition.voc_ref, "cat");
+        assert_eq!(definition.header, "cat");
+        assert_eq!(definition.other_forms, vec!["cats", "catting", "catted"]);
+        assert!(definition.short_description.starts_with("A cat is"));
+
+        assert_eq!(definition.pronunciations.len(), 2);
+        let us = &definition.pronunciations[0];
+        assert_eq!(us.variant, PronunciationVariant::Usa);
+        assert_eq!(us.ipa_str, "/kæt/");
+        assert_eq!(
+            us.audio.as_ref().map(|x| x.content_type.as_str()),
+            Some("audio/mpeg")
+        );
+        assert_eq!(definition.pronunciations[1].audio, None);
+
+        assert_eq!(definition.definitions.len(), 2);
+        let noun = &definition.definitions[0];
+        assert!(matches!(noun.variant, WordVariant::Noun));
+        assert!(noun.description.starts_with("feline mammal"));
+        assert_eq!(noun.short_examples, vec!["the cat curled up by the fire"]);
+        assert_eq!(noun.synonyms, vec!["true cat"]);
+        assert_eq!(
+            noun.image.as_ref().map(|x| x.content_type.as_str()),
+            Some("image/png")
+        );
+        assert!(matches!(
+            definition.definitions[1].variant,
+            WordVariant::Verb
+        ));
+
+        assert_eq!(definition.examples.len(), 2);
+        assert_eq!(definition.examples[0].author, "A. Writer");
+    }
+
+    #[tokio::test]
+    async fn scrape_replay_not_found() {
+        crate::http::replay_from("./src/tests/cassettes");
+        match scrape("catt", &Progress::default()).await {
+            Err(ScrapeErr::NotFound(not_found)) => {
+                assert_eq!(not_found.suggestions, vec!["cat", "cast"])
+            }
+            other => panic!("should not be found: {:?}", other.map(|x| x.voc_ref)),
+        }
+    }
 }
@@ -0,0 +1,309 @@
+use crate::db::database::DbErr;
+use crate::models::wiktionary::{DefinitionDoc, Entry, Form, ImportDoc, Sense, Sound, Translation};
+use chrono::Utc;
+use futures::future::join_all;
+use mongodb::bson::{doc, to_bson, Document};
+use mongodb::options::{ReplaceOptions, UpdateOptions};
+use mongodb::{Collection, Database};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, BufReader};
+
+/// how many lines are written before the progress is checkpointed
+const BATCH_SIZE: usize = 500;
+
+#[derive(Debug)]
+pub enum ScrapeErr {
+    NotImported,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportErr {
+    #[error("could not read the dump: {0}")]
+    IoErr(#[source] std::io::Error),
+    #[error("could not store the import: {0}")]
+    DbErr(DbErr),
+}
+
+/// Imports a Wiktextract JSONL dump (https://kaikki.org) into `wiktionary_definitions`.
+/// The dump is streamed line by line and the progress is stored in `imports`,
+/// running the import again on the same file continues where the last one stopped.
+/// Only the Wiktextract dump is supported, the raw MediaWiki XML dump holds wikitext
+/// which would need a parser of the templates and is out of scope.
+pub struct Importer {
+    definitions: Collection<DefinitionDoc>,
+    imports: Collection<ImportDoc>,
+    lang_code: String,
+}
+
+impl Importer {
+    pub fn new(db: &Database, lang_code: &str) -> Self {
+        Importer {
+            definitions: db.collection("wiktionary_definitions"),
+            imports: db.collection("imports"),
+            lang_code: lang_code.to_string(),
+        }
+    }
+
+    pub async fn import(&self, path: &Path) -> Result<ImportDoc, ImportErr> {
+        let file = File::open(path).await.map_err(ImportErr::IoErr)?;
+        let len = file.metadata().await.map_err(ImportErr::IoErr)?.len();
+        let import_id = format!(
+            "wiktionary:{}:{}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            len
+        );
+
+        let mut progress = self
+            .imports
+            .find_one(doc! {"_id": &import_id}, None)
+            .await
+            .map_err(|err| ImportErr::DbErr(DbErr::QueryErr(err)))?
+            .unwrap_or_else(|| ImportDoc {
+                id: import_id,
+                updated_at: Utc::now(),
+                ..Default::default()
+            });
+
+        if progress.done {
+            return Ok(progress);
+        }
+
+        let mut reader = BufReader::new(file);
+        reader
+            .seek(SeekFrom::Start(progress.offset))
+            .await
+            .map_err(ImportErr::IoErr)?;
+
+        let mut offset = progress.offset;
+        let mut line = String::new();
+        let mut batch: Vec<(u64, Entry, String)> = Vec::with_capacity(BATCH_SIZE);
+
+        loop {
+            line.clear();
+            let read = reader
+                .read_line(&mut line)
+                .await
+                .map_err(ImportErr::IoErr)?;
+            let line_offset = offset;
+            offset += read as u64;
+
+            if read > 0 {
+                match parse_line(&line, &self.lang_code) {
+                    Some((word, entry)) => batch.push((line_offset, entry, word)),
+                    None => progress.skipped += 1,
+                }
+            }
+
+            if batch.len() >= BATCH_SIZE || read == 0 {
+                progress.imported += self.save_batch(batch.drain(..)).await? as u64;
+                progress.offset = offset;
+                progress.done = read == 0;
+                progress.updated_at = Utc::now();
+                self.save_progress(&progress).await?;
+            }
+
+            if read == 0 {
+                break Ok(progress);
+            }
+        }
+    }
+
+    /// one upsert per word, the lines of the same word would race each other otherwise
+    async fn save_batch(
+        &self,
+        batch: impl Iterator<Item = (u64, Entry, String)>,
+    ) -> Result<usize, ImportErr> {
+        let mut saved = 0;
+        let mut entries: HashMap<String, Document> = HashMap::new();
+        for (offset, entry, word) in batch {
+            let entry =
+                to_bson(&entry).map_err(|err| ImportErr::DbErr(DbErr::SerializeBsonErr(err)))?;
+            entries
+                .entry(word)
+                .or_default()
+                .insert(format!("entries.{offset}"), entry);
+            saved += 1;
+        }
+
+        let updates = entries.into_iter().map(|(word, entries)| {
+            self.definitions.update_one(
+                doc! {"word": word},
+                doc! {"$set": entries},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+        });
+        for result in join_all(updates).await {
+            result.map_err(|err| ImportErr::DbErr(DbErr::QueryErr(err)))?;
+        }
+
+        Ok(saved)
+    }
+
+    async fn save_progress(&self, progress: &ImportDoc) -> Result<(), ImportErr> {
+        let mut replace_options = ReplaceOptions::default();
+        // inserts when finds None
+        replace_options.upsert = Some(true);
+        self.imports
+            .replace_one(doc! {"_id": &progress.id}, progress, replace_options)
+            .await
+            .map_err(|err| ImportErr::DbErr(DbErr::QueryErr(err)))?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RawEntry {
+    word: String,
+    #[serde(default)]
+    pos: String,
+    #[serde(default)]
+    lang_code: String,
+    #[serde(default)]
+    etymology_text: String,
+    #[serde(default)]
+    senses: Vec<RawSense>,
+    #[serde(default)]
+    sounds: Vec<RawSound>,
+    #[serde(default)]
+    forms: Vec<RawForm>,
+    #[serde(default)]
+    translations: Vec<RawTranslation>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RawSense {
+    #[serde(default)]
+    glosses: Vec<String>,
+    #[serde(default)]
+    examples: Vec<RawExample>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RawExample {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RawSound {
+    ipa: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RawForm {
+    form: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RawTranslation {
+    #[serde(default)]
+    lang: String,
+    #[serde(default)]
+    code: String,
+    word: Option<String>,
+    #[serde(default)]
+    sense: String,
+}
+
+/// parses a single dump line, `None` when it is malformed or in a different language
+fn parse_line(line: &str, lang_code: &str) -> Option<(String, Entry)> {
+    let raw: RawEntry = serde_json::from_str(line).ok()?;
+
+    if raw.lang_code != lang_code {
+        return None;
+    }
+
+    let entry = Entry {
+        pos: raw.pos,
+        etymology: raw.etymology_text,
+        senses: raw
+            .senses
+            .into_iter()
+            .map(|s| Sense {
+                glosses: s.glosses,
+                examples: s.examples.into_iter().map(|e| e.text).collect(),
+                tags: s.tags,
+            })
+            .collect(),
+        sounds: raw
+            .sounds
+            .into_iter()
+            .filter_map(|s| s.ipa.map(|ipa| Sound { ipa, tags: s.tags }))
+            .collect(),
+        forms: raw
+            .forms
+            .into_iter()
+            .map(|f| Form {
+                form: f.form,
+                tags: f.tags,
+            })
+            .collect(),
+        translations: raw
+            .translations
+            .into_iter()
+            .filter_map(|t| {
+                t.word.map(|word| Translation {
+                    lang: t.lang,
+                    code: t.code,
+                    word,
+                    sense: t.sense,
+                })
+            })
+            .collect(),
+    };
+
+    Some((raw.word, entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line_ok() {
+        let line = r#"{"word": "cat", "pos": "noun", "lang": "English", "lang_code": "en",
+            "etymology_text": "From Middle English cat, catte.",
+            "senses": [{"glosses": ["A domesticated species of feline."], "tags": ["countable"],
+                "examples": [{"text": "The cat sat on the mat.", "type": "example"}]}],
+            "sounds": [{"ipa": "/kæt/", "tags": ["UK"]}, {"audio": "En-us-cat.ogg"}],
+            "forms": [{"form": "cats", "tags": ["plural"]}],
+            "translations": [{"lang": "German", "code": "de", "word": "Katze", "sense": "domestic species"},
+                {"lang": "French", "code": "fr", "sense": "domestic species"}]}"#
+            .replace('\n', "");
+
+        let (word, entry) = parse_line(&line, "en").expect("should parse");
+
+        assert_eq!(&word, "cat");
+        assert_eq!(&entry.pos, "noun");
+        assert_eq!(&entry.etymology, "From Middle English cat, catte.");
+        assert_eq!(entry.senses.len(), 1);
+        assert_eq!(
+            &entry.senses[0].glosses[0],
+            "A domesticated species of feline."
+        );
+        assert_eq!(&entry.senses[0].examples[0], "The cat sat on the mat.");
+        assert_eq!(entry.sounds.len(), 1);
+        assert_eq!(&entry.sounds[0].ipa, "/kæt/");
+        assert_eq!(&entry.forms[0].form, "cats");
+        assert_eq!(&entry.forms[0].tags[0], "plural");
+        assert_eq!(entry.translations.len(), 1);
+        assert_eq!(&entry.translations[0].word, "Katze");
+    }
+
+    #[test]
+    fn parse_line_skips_other_languages_and_garbage() {
+        let line = r#"{"word": "Katze", "pos": "noun", "lang_code": "de"}"#;
+        assert!(parse_line(line, "en").is_none());
+        assert!(parse_line("not json", "en").is_none());
+    }
+}
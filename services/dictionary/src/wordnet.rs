@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// In-memory Princeton WordNet 3.x loaded from the `index.*` and `data.*` database files
+#[derive(Debug, Default)]
+pub struct WordNet {
+    synsets: HashMap<SynsetId, Synset>,
+    /// lemma => synsets ordered by pos and then by sense frequency
+    index: HashMap<String, Vec<SynsetId>>,
+}
+
+#[derive(Debug)]
+pub enum WordNetErr {
+    IoErr(std::io::Error),
+    ParseErr(String),
+    NoDatabaseFiles,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pos {
+    Noun,
+    Verb,
+    Adjective,
+    Adverb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SynsetId {
+    pub pos: Pos,
+    pub offset: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Synset {
+    pub id: SynsetId,
+    pub words: Vec<String>,
+    pub gloss: String,
+    pointers: Vec<Pointer>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pointer {
+    symbol: String,
+    target: SynsetId,
+    /// 1-based index of the word in the source synset, 0 for relations of the whole synset
+    source_word: usize,
+    /// 1-based index of the word in the target synset, 0 for relations of the whole synset
+    target_word: usize,
+}
+
+/// Relations of one sense (synset) of a word
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sense<'a> {
+    pub synset: &'a Synset,
+    pub antonyms: Vec<String>,
+    pub hypernyms: Vec<&'a Synset>,
+    pub hyponyms: Vec<&'a Synset>,
+    pub meronyms: Vec<&'a Synset>,
+    pub holonyms: Vec<&'a Synset>,
+}
+
+static FILES: [(Pos, &str); 4] = [
+    (Pos::Noun, "noun"),
+    (Pos::Verb, "verb"),
+    (Pos::Adjective, "adj"),
+    (Pos::Adverb, "adv"),
+];
+
+impl Pos {
+    fn parse(val: &str) -> Option<Pos> {
+        match val {
+            "n" => Some(Pos::Noun),
+            "v" => Some(Pos::Verb),
+            // adjective satellites live in the same files as adjectives
+            "a" | "s" => Some(Pos::Adjective),
+            "r" => Some(Pos::Adverb),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Pos::Noun => "noun",
+            Pos::Verb => "verb",
+            Pos::Adjective => "adjective",
+            Pos::Adverb => "adverb",
+        }
+    }
+
+    fn as_char(&self) -> char {
+        match self {
+            Pos::Noun => 'n',
+            Pos::Verb => 'v',
+            Pos::Adjective => 'a',
+            Pos::Adverb => 'r',
+        }
+    }
+}
+
+impl SynsetId {
+    /// e.g. n02121620
+    pub fn to_key(self) -> String {
+        format!("{}{:08}", self.pos.as_char(), self.offset)
+    }
+}
+
+impl WordNet {
+    /// loads the database files from `dir`, e.g. `/usr/share/wordnet/dict`.
+    /// Missing parts of speech are skipped, but at least one has to be present
+    pub fn load(dir: &Path) -> Result<WordNet, WordNetErr> {
+        let mut wordnet = WordNet::default();
+        let mut loaded = false;
+
+        for (pos, name) in FILES.iter() {
+            let data_path = dir.join(format!("data.{name}"));
+            let index_path = dir.join(format!("index.{name}"));
+            if !data_path.exists() || !index_path.exists() {
+                continue;
+            }
+
+            let data = fs::read_to_string(data_path).map_err(WordNetErr::IoErr)?;
+            for line in data.lines().filter(|l| !is_license_line(l)) {
+                let synset = parse_data_line(line)?;
+                wordnet.synsets.insert(synset.id, synset);
+            }
+
+            let index = fs::read_to_string(index_path).map_err(WordNetErr::IoErr)?;
+            for line in index.lines().filter(|l| !is_license_line(l)) {
+                let (lemma, offsets) = parse_index_line(line)?;
+                wordnet.index.entry(lemma).or_default().extend(
+                    offsets
+                        .into_iter()
+                        .map(|offset| SynsetId { pos: *pos, offset }),
+                );
+            }
+
+            loaded = true;
+        }
+
+        match loaded {
+            true => Ok(wordnet),
+            false => Err(WordNetErr::NoDatabaseFiles),
+        }
+    }
+
+    /// all senses of the word, `None` when WordNet does not know it
+    pub fn senses(&self, word: &str) -> Option<Vec<Sense<'_>>> {
+        let lemma = to_lemma(word);
+        let ids = self.index.get(&lemma)?;
+
+        let senses = ids
+            .iter()
+            .filter_map(|id| self.synsets.get(id))
+            .map(|synset| self.sense(synset, &lemma))
+            .collect();
+
+        Some(senses)
+    }
+
+    fn sense<'a>(&'a self, synset: &'a Synset, lemma: &str) -> Sense<'a> {
+        let word_index = synset
+            .words
+            .iter()
+            .position(|w| to_lemma(w) == lemma)
+            .map_or(0, |i| i + 1);
+
+        let related = |symbols: &[&str]| -> Vec<&'a Synset> {
+            synset
+                .pointers
+                .iter()
+                .filter(|p| symbols.contains(&p.symbol.as_str()))
+                .filter_map(|p| self.synsets.get(&p.target))
+                .collect()
+        };
+
+        let antonyms = synset
+            .pointers
+            .iter()
+            .filter(|p| p.symbol == "!" && (p.source_word == 0 || p.source_word == word_index))
+            .filter_map(|p| {
+                let target = self.synsets.get(&p.target)?;
+                match p.target_word {
+                    0 => target.words.first(),
+                    i => target.words.get(i - 1),
+                }
+            })
+            .cloned()
+            .collect();
+
+        Sense {
+            synset,
+            antonyms,
+            hypernyms: related(&["@", "@i"]),
+            hyponyms: related(&["~", "~i"]),
+            meronyms: related(&["%m", "%s", "%p"]),
+            holonyms: related(&["#m", "#s", "#p"]),
+        }
+    }
+}
+
+fn is_license_line(line: &str) -> bool {
+    line.starts_with("  ") || line.trim().is_empty()
+}
+
+/// "ice_cream" => "ice cream", "long(a)" => "long"
+fn to_word(raw: &str) -> String {
+    let word = match raw.find('(') {
+        Some(i) if raw.ends_with(')') => &raw[..i],
+        _ => raw,
+    };
+    word.replace('_', " ")
+}
+
+fn to_lemma(word: &str) -> String {
+    word.trim().to_lowercase().replace(' ', "_")
+}
+
+/// `synset_offset lex_filenum ss_type w_cnt word lex_id [word lex_id...] p_cnt [ptr...] [frames...] | gloss`
+fn parse_data_line(line: &str) -> Result<Synset, WordNetErr> {
+    let err = || WordNetErr::ParseErr(format!("Invalid data line: {line}"));
+
+    let (fields, gloss) = line.split_once(" | ").unwrap_or((line, ""));
+    let mut fields = fields.split_whitespace();
+    let mut next = || fields.next().ok_or_else(err);
+
+    let offset: u32 = next()?.parse().map_err(|_| err())?;
+    let _lex_filenum = next()?;
+    let pos = Pos::parse(next()?).ok_or_else(err)?;
+    let w_cnt = usize::from_str_radix(next()?, 16).map_err(|_| err())?;
+
+    let mut words = Vec::with_capacity(w_cnt);
+    for _ in 0..w_cnt {
+        words.push(to_word(next()?));
+        let _lex_id = next()?;
+    }
+
+    let p_cnt: usize = next()?.parse().map_err(|_| err())?;
+    let mut pointers = Vec::with_capacity(p_cnt);
+    for _ in 0..p_cnt {
+        let symbol = next()?.to_string();
+        let target_offset: u32 = next()?.parse().map_err(|_| err())?;
+        let target_pos = Pos::parse(next()?).ok_or_else(err)?;
+        let source_target = next()?;
+        let source_word = usize::from_str_radix(&source_target[..2], 16).map_err(|_| err())?;
+        let target_word = usize::from_str_radix(&source_target[2..], 16).map_err(|_| err())?;
+
+        pointers.push(Pointer {
+            symbol,
+            target: SynsetId {
+                pos: target_pos,
+                offset: target_offset,
+            },
+            source_word,
+            target_word,
+        });
+    }
+
+    Ok(Synset {
+        id: SynsetId { pos, offset },
+        words,
+        gloss: gloss.trim().to_string(),
+        pointers,
+    })
+}
+
+/// `lemma pos synset_cnt p_cnt [ptr_symbol...] sense_cnt tagsense_cnt synset_offset [synset_offset...]`
+fn parse_index_line(line: &str) -> Result<(String, Vec<u32>), WordNetErr> {
+    let err = || WordNetErr::ParseErr(format!("Invalid index line: {line}"));
+
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let lemma = fields.first().ok_or_else(err)?.to_lowercase();
+    let synset_cnt: usize = fields.get(2).ok_or_else(err)?.parse().map_err(|_| err())?;
+
+    let offsets = fields
+        .iter()
+        .rev()
+        .take(synset_cnt)
+        .rev()
+        .map(|x| x.parse().map_err(|_| err()))
+        .collect::<Result<Vec<u32>, WordNetErr>>()?;
+
+    Ok((lemma, offsets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load() -> WordNet {
+        WordNet::load(Path::new("./src/tests/wordnet")).expect("Should load the test WordNet")
+    }
+
+    #[test]
+    fn load_fails_without_files() {
+        let result = WordNet::load(Path::new("./src/tests/htmls"));
+        assert!(matches!(result, Err(WordNetErr::NoDatabaseFiles)));
+    }
+
+    #[test]
+    fn senses_ok() {
+        let wordnet = load();
+        let senses = wordnet.senses("Cat").expect("should exist");
+
+        assert_eq!(senses.len(), 1);
+        let sense = &senses[0];
+        assert_eq!(sense.synset.id.to_key(), "n00000001");
+        assert_eq!(sense.synset.words, vec!["cat", "true cat"]);
+        assert_eq!(
+            &sense.synset.gloss,
+            "feline mammal usually having thick soft fur"
+        );
+        assert_eq!(sense.hypernyms[0].words, vec!["feline"]);
+        assert_eq!(sense.hyponyms[0].words, vec!["house cat"]);
+        assert_eq!(sense.meronyms[0].words, vec!["whisker"]);
+        assert_eq!(sense.holonyms[0].words, vec!["Felis"]);
+        assert!(sense.antonyms.is_empty());
+    }
+
+    #[test]
+    fn senses_antonyms_ok() {
+        let wordnet = load();
+        let senses = wordnet.senses("hot").expect("should exist");
+
+        assert_eq!(senses[0].antonyms, vec!["cold"]);
+        assert_eq!(senses[0].synset.id.pos.as_str(), "adjective");
+    }
+
+    #[test]
+    fn senses_unknown_word() {
+        assert!(load().senses("nonexistentword").is_none());
+    }
+}
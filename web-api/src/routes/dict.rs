@@ -1,20 +1,37 @@
+use super::problem::Problem;
 use crate::Rpc;
 use axum::{
-    body::{Bytes, Full},
-    extract::Path,
-    http::{Response, StatusCode},
+    body::{boxed, BoxBody, Bytes, Empty, Full, StreamBody},
+    extract::{Path, Query},
+    http::{
+        header::{
+            ACCEPT_RANGES, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+            IF_NONE_MATCH, IF_RANGE, RANGE,
+        },
+        HeaderMap, HeaderValue, Response, StatusCode,
+    },
     response::IntoResponse,
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
+use futures::StreamExt;
+use rpc::dictionary::{
+    dictionary_client::DictionaryClient, AudioChunk, AudioFormat, AudioInfo, ByteRange,
+};
+use serde::Deserialize;
+use tonic::{transport::Channel, Streaming};
 
 pub fn routes() -> Router {
     Router::new()
         .route("/word/:word", get(get_word))
+        .route("/word/:word/progress", get(watch_word))
+        .route("/words", post(get_words))
         .route("/audio/:id", get(get_audio))
+        .route("/image/:id", get(get_image))
+        .route("/relations/:word", get(get_relations))
 }
 
-async fn get_word(Path(word): Path<String>) -> impl IntoResponse {
+async fn get_word(Path(word): Path<String>) -> Result<impl IntoResponse, Problem> {
     let mut client = Rpc::get_dictionary_client().await?;
 
     let request = tonic::Request::new(rpc::dictionary::GetWordDefinitionsRequest { word });
@@ -24,16 +41,231 @@ async fn get_word(Path(word): Path<String>) -> impl IntoResponse {
             let response: get_word_response::Response = res.into_inner().into();
             Ok((StatusCode::OK, Json(response)))
         }
-        Err(_status) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(status) => {
+            // the word and the suggestions stay top level members of the problem
+            let details: Option<rpc::dictionary::WordNotFound> =
+                rpc::status::detail(&status, "dictionary.WordNotFound");
+            let problem = Problem::from(status);
+            Err(match details {
+                Some(details) => problem.extend(get_word_response::NotFound::from(details)),
+                None => problem,
+            })
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GetWordsRequest {
+    words: Vec<String>,
+}
+
+/// streams one json line per word as soon as the word is resolved
+async fn get_words(Json(body): Json<GetWordsRequest>) -> Result<Response<BoxBody>, Problem> {
+    let mut client = Rpc::get_dictionary_client().await?;
+
+    let request =
+        tonic::Request::new(rpc::dictionary::GetWordDefinitionsBatchRequest { words: body.words });
+
+    let results = client
+        .get_word_definitions_batch(request)
+        .await?
+        .into_inner();
+
+    let lines = results.map(|result| {
+        let line: get_word_response::BatchLine = result.map_err(Box::new)?.into();
+        let mut line = serde_json::to_vec(&line).map_err(Box::new)?;
+        line.push(b'\n');
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Bytes::from(line))
+    });
+
+    Response::builder()
+        .header(CONTENT_TYPE, "application/x-ndjson")
+        .body(boxed(StreamBody::new(lines)))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into())
+}
+
+/// queues the word and streams the progress of its sources as NDJSON, one line per change
+async fn watch_word(Path(word): Path<String>) -> Result<Response<BoxBody>, Problem> {
+    let mut client = Rpc::get_dictionary_client().await?;
+
+    let request = tonic::Request::new(rpc::dictionary::WatchWordRequest { word });
+
+    let events = client.watch_word(request).await?.into_inner();
+
+    let lines = events.map(|event| {
+        let line: get_word_response::WatchLine = event.map_err(Box::new)?.into();
+        let mut line = serde_json::to_vec(&line).map_err(Box::new)?;
+        line.push(b'\n');
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Bytes::from(line))
+    });
+
+    Response::builder()
+        .header(CONTENT_TYPE, "application/x-ndjson")
+        .body(boxed(StreamBody::new(lines)))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into())
+}
+
+#[derive(Deserialize)]
+struct AudioQuery {
+    /// "opus" or "mp3", the original audio when not set
+    format: Option<String>,
+}
+
+async fn get_audio(
+    Path(id): Path<String>,
+    Query(query): Query<AudioQuery>,
+    headers: HeaderMap,
+) -> Result<Response<BoxBody>, Response<BoxBody>> {
+    let mut client = Rpc::get_dictionary_client()
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let mut range = headers
+        .get(RANGE)
+        .and_then(|val| val.to_str().ok())
+        .and_then(parse_range);
+    let format = match query.format.as_deref() {
+        None => AudioFormat::Original,
+        Some("opus") => AudioFormat::Opus,
+        Some("mp3") => AudioFormat::Mp3,
+        Some(format) => {
+            return Err(Problem::new(StatusCode::BAD_REQUEST)
+                .detail(format!("unknown audio format {format}"))
+                .into_response())
+        }
+    };
+    // matched by the service before it reads the audio
+    let if_none_match = headers
+        .get(IF_NONE_MATCH)
+        .map(parse_etags)
+        .unwrap_or_default();
+    let (mut info, mut first, mut stream) = open_audio_stream(
+        &mut client,
+        id.clone(),
+        range.clone(),
+        format,
+        if_none_match.clone(),
+    )
+    .await?;
+    let etag = format!("\"{}\"", info.hash);
+
+    // range of a different version of the audio is ignored
+    if let Some(if_range) = headers.get(IF_RANGE) {
+        if !info.not_modified && range.is_some() && if_range.as_bytes() != etag.as_bytes() {
+            range = None;
+            (info, first, stream) =
+                open_audio_stream(&mut client, id, None, format, if_none_match).await?;
+        }
+    }
+
+    // the original is sent until the requested format is transcoded, the client should ask again soon
+    let cache_control = if info.format() == format {
+        "public, max-age=31536000, immutable"
+    } else {
+        "public, max-age=300"
+    };
+    let builder = Response::builder()
+        .header(ETAG, etag.as_str())
+        .header(CACHE_CONTROL, cache_control)
+        .header(ACCEPT_RANGES, "bytes");
+
+    if info.not_modified {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(boxed(Empty::new()))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response());
     }
+
+    let builder = match range {
+        Some(_) => builder.status(StatusCode::PARTIAL_CONTENT).header(
+            CONTENT_RANGE,
+            format!(
+                "bytes {}-{}/{}",
+                info.start,
+                info.start + info.length - 1,
+                info.size
+            ),
+        ),
+        None => builder.status(StatusCode::OK),
+    };
+
+    let body = futures::stream::once(async { Ok(Bytes::from(first)) })
+        .chain(stream.map(|chunk| chunk.map(|chunk| Bytes::from(chunk.data)).map_err(Box::new)));
+
+    builder
+        .header(CONTENT_TYPE, info.content_type.as_str())
+        .header(CONTENT_LENGTH, info.length)
+        .body(boxed(StreamBody::new(body)))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// starts streaming the audio, returns its info with the data of the first chunk and the rest of the stream
+async fn open_audio_stream(
+    client: &mut DictionaryClient<Channel>,
+    id: String,
+    range: Option<ByteRange>,
+    format: AudioFormat,
+    if_none_match: Vec<String>,
+) -> Result<(AudioInfo, Vec<u8>, Streaming<AudioChunk>), Response<BoxBody>> {
+    let request = tonic::Request::new(rpc::dictionary::StreamAudioRequest {
+        id,
+        range,
+        format: format.into(),
+        if_none_match,
+    });
+
+    // a range past the end is a 416 with the `Content-Range` of the size
+    let mut stream = client
+        .stream_audio(request)
+        .await
+        .map_err(|status| Problem::from(status).into_response())?
+        .into_inner();
+
+    match stream.message().await {
+        Ok(Some(AudioChunk {
+            info: Some(info),
+            data,
+        })) => Ok((info, data, stream)),
+        _ => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// single range of the `Range` header, `None` when it can not be served as one range
+fn parse_range(header: &str) -> Option<ByteRange> {
+    let (first, last) = header.strip_prefix("bytes=")?.trim().split_once('-')?;
+    let parse = |val: &str| match val.trim() {
+        "" => Ok(None),
+        val => val.parse::<u64>().map(Some),
+    };
+
+    match (parse(first).ok()?, parse(last).ok()?) {
+        (None, None) => None,
+        (Some(first), Some(last)) if last < first => None,
+        (first, last) => Some(ByteRange { first, last }),
+    }
+}
+
+/// hashes of the `If-None-Match` etags, weak ones included, "*" as is
+fn parse_etags(if_none_match: &HeaderValue) -> Vec<String> {
+    let Ok(if_none_match) = if_none_match.to_str() else {
+        return Vec::new();
+    };
+
+    if_none_match
+        .split(',')
+        .map(|val| val.trim())
+        .map(|val| val.strip_prefix("W/").unwrap_or(val))
+        .map(|val| val.trim_matches('"').to_string())
+        .filter(|val| !val.is_empty())
+        .collect()
 }
 
-async fn get_audio(Path(id): Path<String>) -> impl IntoResponse {
+async fn get_image(Path(id): Path<String>) -> Result<impl IntoResponse, Problem> {
     let mut client = Rpc::get_dictionary_client().await?;
 
-    let request = tonic::Request::new(rpc::dictionary::GetAudioRequest { id });
+    let request = tonic::Request::new(rpc::dictionary::GetImageRequest { id });
 
-    match client.get_audio(request).await {
+    match client.get_image(request).await {
         Ok(res) => {
             let res = res.into_inner();
             let response = Response::builder()
@@ -41,12 +273,84 @@ async fn get_audio(Path(id): Path<String>) -> impl IntoResponse {
                 .header("Content-type", res.content_type.as_str())
                 .body(Full::new(Bytes::from(res.bytes)));
 
-            response.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+            response.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into())
+        }
+        Err(status) => Err(status.into()),
+    }
+}
+
+async fn get_relations(Path(word): Path<String>) -> Result<impl IntoResponse, Problem> {
+    let mut client = Rpc::get_dictionary_client().await?;
+
+    let request = tonic::Request::new(rpc::dictionary::GetWordRelationsRequest { word });
+
+    match client.get_word_relations(request).await {
+        Ok(res) => {
+            let response: get_relations_response::Response = res.into_inner().into();
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(status) => Err(status.into()),
+    }
+}
+
+mod get_relations_response {
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    pub struct Response {
+        pub word: String,
+        pub senses: Vec<Sense>,
+    }
+
+    impl From<rpc::dictionary::GetWordRelationsResponse> for Response {
+        fn from(value: rpc::dictionary::GetWordRelationsResponse) -> Self {
+            Response {
+                word: value.word,
+                senses: value.senses.into_iter().map(|x| x.into()).collect(),
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct Sense {
+        pub synset: Option<Synset>,
+        pub antonyms: Vec<String>,
+        pub hypernyms: Vec<Synset>,
+        pub hyponyms: Vec<Synset>,
+        pub meronyms: Vec<Synset>,
+        pub holonyms: Vec<Synset>,
+    }
+
+    impl From<rpc::dictionary::WordSense> for Sense {
+        fn from(value: rpc::dictionary::WordSense) -> Self {
+            Sense {
+                synset: value.synset.map(|x| x.into()),
+                antonyms: value.antonyms,
+                hypernyms: value.hypernyms.into_iter().map(|x| x.into()).collect(),
+                hyponyms: value.hyponyms.into_iter().map(|x| x.into()).collect(),
+                meronyms: value.meronyms.into_iter().map(|x| x.into()).collect(),
+                holonyms: value.holonyms.into_iter().map(|x| x.into()).collect(),
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct Synset {
+        pub id: String,
+        pub pos: String,
+        pub words: Vec<String>,
+        pub gloss: String,
+    }
+
+    impl From<rpc::dictionary::Synset> for Synset {
+        fn from(value: rpc::dictionary::Synset) -> Self {
+            Synset {
+                id: value.id,
+                pos: value.pos,
+                words: value.words,
+                gloss: value.gloss,
+            }
         }
-        Err(status) => match status.code() {
-            tonic::Code::NotFound => Err(StatusCode::NOT_FOUND),
-            _ => Err(StatusCode::INTERNAL_SERVER_ERROR),
-        },
     }
 }
 
@@ -56,16 +360,120 @@ mod get_word_response {
     #[derive(Serialize)]
     pub struct Response {
         pub word: String,
-        pub vocabulary: Option<VocabularyDefinition>,
-        pub oxford: Option<OxfordDefinition>,
+        /// headword the word was resolved to
+        pub lemma: String,
+        pub definitions: Vec<SourceDefinition>,
+        pub suggestions: Vec<String>,
     }
 
     impl From<rpc::dictionary::GetWordDefinitionsResponse> for Response {
         fn from(value: rpc::dictionary::GetWordDefinitionsResponse) -> Self {
             Response {
                 word: value.word,
-                vocabulary: value.vocabulary_definition.map(|v| v.into()),
-                oxford: value.oxford_definition.map(|x| x.into()),
+                lemma: value.lemma,
+                definitions: value
+                    .definitions
+                    .into_iter()
+                    .filter_map(|d| d.definition.map(|x| x.into()))
+                    .collect(),
+                suggestions: value.suggestions,
+            }
+        }
+    }
+
+    /// line of the `POST /dict/words` response
+    #[derive(Serialize)]
+    #[serde(tag = "status", rename_all = "snake_case")]
+    pub enum BatchLine {
+        Found(Response),
+        NotFound(NotFound),
+        Error { word: String, message: String },
+    }
+
+    impl From<rpc::dictionary::WordDefinitionsResult> for BatchLine {
+        fn from(value: rpc::dictionary::WordDefinitionsResult) -> Self {
+            use rpc::dictionary::word_definitions_result::Result;
+            match value.result {
+                Some(Result::Found(v)) => BatchLine::Found(v.into()),
+                Some(Result::NotFound(v)) => BatchLine::NotFound(v.into()),
+                Some(Result::Error(err)) => BatchLine::Error {
+                    word: value.word,
+                    message: err.message,
+                },
+                None => BatchLine::Error {
+                    word: value.word,
+                    message: "Missing result".to_string(),
+                },
+            }
+        }
+    }
+
+    /// line of the `GET /dict/word/:word/progress` response
+    #[derive(Serialize)]
+    pub struct WatchLine {
+        pub word: String,
+        pub source: String,
+        /// "queued", "scraping", "downloading_audio", "done" or "failed"
+        pub stage: &'static str,
+        pub definition: Option<SourceDefinition>,
+        pub error: Option<String>,
+    }
+
+    impl From<rpc::dictionary::WatchWordEvent> for WatchLine {
+        fn from(value: rpc::dictionary::WatchWordEvent) -> Self {
+            use rpc::dictionary::SourceStage;
+            let stage = match value.stage() {
+                SourceStage::Queued => "queued",
+                SourceStage::Scraping => "scraping",
+                SourceStage::DownloadingAudio => "downloading_audio",
+                SourceStage::Done => "done",
+                SourceStage::Failed => "failed",
+            };
+            WatchLine {
+                word: value.word,
+                source: value.source,
+                stage,
+                definition: value
+                    .definition
+                    .and_then(|d| d.definition)
+                    .map(|d| d.into()),
+                error: value.error,
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct NotFound {
+        pub word: String,
+        pub lemma: String,
+        pub suggestions: Vec<String>,
+    }
+
+    impl From<rpc::dictionary::WordNotFound> for NotFound {
+        fn from(value: rpc::dictionary::WordNotFound) -> Self {
+            NotFound {
+                word: value.word,
+                lemma: value.lemma,
+                suggestions: value.suggestions,
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    #[serde(tag = "source", content = "definition", rename_all = "lowercase")]
+    pub enum SourceDefinition {
+        Vocabulary(VocabularyDefinition),
+        Oxford(OxfordDefinition),
+        Wiktionary(WiktionaryDefinition),
+    }
+
+    impl From<rpc::dictionary::source_definition::Definition> for SourceDefinition {
+        fn from(value: rpc::dictionary::source_definition::Definition) -> Self {
+            use rpc::dictionary::source_definition::Definition;
+            match value {
+                Definition::Vocabulary(v) => SourceDefinition::Vocabulary(v.into()),
+                Definition::Oxford(v) => SourceDefinition::Oxford(v.into()),
+                Definition::Wiktionary(v) => SourceDefinition::Wiktionary(v.into()),
             }
         }
     }
@@ -86,6 +494,7 @@ mod get_word_response {
                         short_examples: d.short_examples,
                         synonyms: d.synonyms,
                         variant: d.word_variant.into(),
+                        image_id: d.image_id,
                     })
                     .collect(),
                 examples: v
@@ -127,6 +536,7 @@ mod get_word_response {
         pub see_also: Vec<WordRef>,
         pub idioms: Vec<Idiom>,
         pub phrasal_verbs: Vec<WordRef>,
+        pub verb_forms: Vec<VerbForm>,
     }
 
     impl From<rpc::dictionary::OxfordDefinition> for OxfordDefinition {
@@ -149,10 +559,128 @@ mod get_word_response {
                 see_also: value.see_also.into_iter().map(|x| x.into()).collect(),
                 idioms: value.idioms.into_iter().map(|x| x.into()).collect(),
                 phrasal_verbs: value.phrasal_verbs.into_iter().map(|x| x.into()).collect(),
+                verb_forms: value.verb_forms.into_iter().map(|x| x.into()).collect(),
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct VerbForm {
+        pub form: String,
+        pub note: String,
+        pub word: String,
+        pub pronunciations: Vec<Pronunciation>,
+    }
+
+    impl From<rpc::dictionary::VerbForm> for VerbForm {
+        fn from(value: rpc::dictionary::VerbForm) -> Self {
+            VerbForm {
+                form: value.form,
+                note: value.note,
+                word: value.word,
+                pronunciations: value.pronunciations.into_iter().map(|x| x.into()).collect(),
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct WiktionaryDefinition {
+        pub id: String,
+        pub word: String,
+        pub entries: Vec<WiktionaryEntry>,
+    }
+
+    impl From<rpc::dictionary::WiktionaryDefinition> for WiktionaryDefinition {
+        fn from(value: rpc::dictionary::WiktionaryDefinition) -> Self {
+            WiktionaryDefinition {
+                id: value.id,
+                word: value.word,
+                entries: value.entries.into_iter().map(|x| x.into()).collect(),
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct WiktionaryEntry {
+        pub pos: String,
+        pub etymology: String,
+        pub senses: Vec<WiktionarySense>,
+        pub sounds: Vec<WiktionarySound>,
+        pub forms: Vec<WiktionaryForm>,
+        pub translations: Vec<WiktionaryTranslation>,
+    }
+
+    impl From<rpc::dictionary::WiktionaryEntry> for WiktionaryEntry {
+        fn from(value: rpc::dictionary::WiktionaryEntry) -> Self {
+            WiktionaryEntry {
+                pos: value.pos,
+                etymology: value.etymology,
+                senses: value
+                    .senses
+                    .into_iter()
+                    .map(|s| WiktionarySense {
+                        glosses: s.glosses,
+                        examples: s.examples,
+                        tags: s.tags,
+                    })
+                    .collect(),
+                sounds: value
+                    .sounds
+                    .into_iter()
+                    .map(|s| WiktionarySound {
+                        ipa: s.ipa,
+                        tags: s.tags,
+                    })
+                    .collect(),
+                forms: value
+                    .forms
+                    .into_iter()
+                    .map(|f| WiktionaryForm {
+                        form: f.form,
+                        tags: f.tags,
+                    })
+                    .collect(),
+                translations: value
+                    .translations
+                    .into_iter()
+                    .map(|t| WiktionaryTranslation {
+                        lang: t.lang,
+                        code: t.code,
+                        word: t.word,
+                        sense: t.sense,
+                    })
+                    .collect(),
             }
         }
     }
 
+    #[derive(Serialize)]
+    pub struct WiktionarySense {
+        pub glosses: Vec<String>,
+        pub examples: Vec<String>,
+        pub tags: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    pub struct WiktionarySound {
+        pub ipa: String,
+        pub tags: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    pub struct WiktionaryForm {
+        pub form: String,
+        pub tags: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    pub struct WiktionaryTranslation {
+        pub lang: String,
+        pub code: String,
+        pub word: String,
+        pub sense: String,
+    }
+
     impl From<rpc::dictionary::WordRef> for WordRef {
         fn from(value: rpc::dictionary::WordRef) -> Self {
             WordRef {
@@ -272,12 +800,7 @@ mod get_word_response {
         pub description: String,
         pub short_examples: Vec<String>,
         pub synonyms: Vec<String>,
-    }
-
-    #[derive(Serialize)]
-    pub struct Image {
-        pub bytes: Vec<u8>,
-        pub format: String,
+        pub image_id: Option<String>,
     }
 
     #[derive(Serialize, Default)]
@@ -316,3 +839,32 @@ mod get_word_response {
         pub source_title: String,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_ok() {
+        let range = |first, last| Some(ByteRange { first, last });
+
+        assert_eq!(parse_range("bytes=0-9"), range(Some(0), Some(9)));
+        assert_eq!(parse_range("bytes=10-"), range(Some(10), None));
+        assert_eq!(parse_range("bytes=-500"), range(None, Some(500)));
+        assert_eq!(parse_range("bytes=9-0"), None);
+        assert_eq!(parse_range("bytes=0-1,5-9"), None);
+        assert_eq!(parse_range("bytes=-"), None);
+        assert_eq!(parse_range("items=0-9"), None);
+    }
+
+    #[test]
+    fn parse_etags_ok() {
+        let parse = |val| parse_etags(&HeaderValue::from_static(val));
+
+        assert_eq!(parse("\"abc\""), vec!["abc"]);
+        assert_eq!(parse("W/\"abc\""), vec!["abc"]);
+        assert_eq!(parse("\"x\", \"abc\""), vec!["x", "abc"]);
+        assert_eq!(parse("*"), vec!["*"]);
+        assert!(parse("").is_empty());
+    }
+}
@@ -11,6 +11,7 @@ use serde::Serialize;
 
 mod auth;
 mod dict;
+mod problem;
 
 pub fn api_routes() -> Router {
     Router::new()
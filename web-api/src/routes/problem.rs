@@ -0,0 +1,213 @@
+use axum::{
+    http::{
+        header::{CONTENT_RANGE, CONTENT_TYPE, RETRY_AFTER},
+        HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// RFC 7807 problem details of a failed request, sent as `application/problem+json`
+#[derive(Serialize, Debug)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    kind: String,
+    title: String,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    /// `ErrorInfo` reason of the service, e.g. "WORD_NOT_FOUND"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    domain: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    metadata: HashMap<String, String>,
+    /// members specific to the problem, e.g. the suggestions of a word not found
+    #[serde(flatten)]
+    extensions: Map<String, Value>,
+    /// seconds to wait before retrying, sent as the `Retry-After` header
+    #[serde(skip)]
+    retry_after: Option<u64>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode) -> Self {
+        Problem {
+            kind: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: None,
+            reason: None,
+            domain: None,
+            metadata: HashMap::new(),
+            extensions: Map::new(),
+            retry_after: None,
+        }
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// adds the fields of `value` to the problem, `value` must serialize to an object
+    pub fn extend(mut self, value: impl Serialize) -> Self {
+        if let Ok(Value::Object(fields)) = serde_json::to_value(value) {
+            self.extensions.extend(fields);
+        }
+        self
+    }
+
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// HTTP status matching the gRPC code of a failed call
+pub fn http_status(code: tonic::Code) -> StatusCode {
+    match code {
+        tonic::Code::NotFound => StatusCode::NOT_FOUND,
+        tonic::Code::InvalidArgument => StatusCode::BAD_REQUEST,
+        tonic::Code::OutOfRange => StatusCode::RANGE_NOT_SATISFIABLE,
+        tonic::Code::AlreadyExists | tonic::Code::Aborted => StatusCode::CONFLICT,
+        tonic::Code::FailedPrecondition => StatusCode::PRECONDITION_FAILED,
+        tonic::Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        tonic::Code::PermissionDenied => StatusCode::FORBIDDEN,
+        tonic::Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        tonic::Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        tonic::Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        tonic::Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl From<StatusCode> for Problem {
+    fn from(status: StatusCode) -> Self {
+        Problem::new(status)
+    }
+}
+
+impl From<tonic::Status> for Problem {
+    fn from(status: tonic::Status) -> Self {
+        let http = http_status(status.code());
+        let mut problem = Problem::new(http);
+        // the messages of the internal errors are not for the clients
+        if http != StatusCode::INTERNAL_SERVER_ERROR {
+            problem.detail = Some(status.message().to_string());
+        }
+
+        if let Some(info) = rpc::status::error_info(&status) {
+            problem.kind = format!(
+                "urn:problem:{}:{}",
+                info.domain,
+                info.reason.to_lowercase().replace('_', "-")
+            );
+            problem.reason = Some(info.reason);
+            problem.domain = Some(info.domain);
+            problem.metadata = info.metadata;
+        }
+        // whole seconds, rounded up not to retry too early
+        problem.retry_after = rpc::status::retry_delay(&status)
+            .map(|delay| delay.as_secs() + u64::from(delay.subsec_nanos() > 0));
+
+        problem
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        let content_range = match status {
+            StatusCode::RANGE_NOT_SATISFIABLE => Some(format!(
+                "bytes */{}",
+                self.metadata.get("size").map_or("*", String::as_str)
+            )),
+            _ => None,
+        };
+        let retry_after = self.retry_after;
+
+        let mut response = (status, Json(self)).into_response();
+        let headers = response.headers_mut();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        if let Some(val) = content_range.and_then(|val| HeaderValue::from_str(&val).ok()) {
+            headers.insert(CONTENT_RANGE, val);
+        }
+        if let Some(secs) = retry_after {
+            headers.insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn from_status_ok() {
+        let status = rpc::status::with_error_info(
+            tonic::Code::ResourceExhausted,
+            "source oxford failed: 429",
+            "dictionary",
+            "SOURCE_RATE_LIMITED",
+            HashMap::from([("source".to_string(), "oxford".to_string())]),
+            vec![rpc::status::retry_info(Duration::from_millis(2500))],
+        );
+
+        let problem = Problem::from(status);
+        assert_eq!(problem.status_code(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(problem.retry_after, Some(3));
+
+        let body = serde_json::to_value(&problem).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "type": "urn:problem:dictionary:source-rate-limited",
+                "title": "Too Many Requests",
+                "status": 429,
+                "detail": "source oxford failed: 429",
+                "reason": "SOURCE_RATE_LIMITED",
+                "domain": "dictionary",
+                "metadata": {"source": "oxford"},
+            })
+        );
+    }
+
+    #[test]
+    fn into_response_ok() {
+        let status = rpc::status::with_error_info(
+            tonic::Code::OutOfRange,
+            "range not satisfiable",
+            "dictionary",
+            "RANGE_NOT_SATISFIABLE",
+            HashMap::from([("size".to_string(), "1024".to_string())]),
+            Vec::new(),
+        );
+
+        let response = Problem::from(status).into_response();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes */1024");
+        assert!(response.headers().get(RETRY_AFTER).is_none());
+
+        let mut rate_limited = Problem::new(StatusCode::TOO_MANY_REQUESTS);
+        rate_limited.retry_after = Some(30);
+        let response = rate_limited.into_response();
+        assert_eq!(response.headers()[RETRY_AFTER], "30");
+
+        let internal = Problem::from(tonic::Status::internal("connection refused"));
+        assert_eq!(internal.detail, None);
+        assert_eq!(
+            http_status(tonic::Code::DeadlineExceeded),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+    }
+}